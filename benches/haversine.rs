@@ -0,0 +1,76 @@
+// benches/haversine.rs
+
+// Timing of `haversine_batch` and `haversine_rad` against a plain `haversine`
+// loop, on 2 million pairs (figures of DEV.md):
+//
+//     cargo bench --bench haversine
+//
+// No harness: each variant runs a few times, the best time is kept.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ektaon::util::{haversine, haversine_batch, haversine_rad, HaversineError};
+
+const PAIRS: usize = 2_000_000;
+const RUNS: usize = 5;
+
+// Deterministic pseudo-random pairs over the whole globe (xorshift).
+fn pairs() -> Vec<(f64, f64, f64, f64)> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = |range: f64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 11) as f64 / (1u64 << 53) as f64 * 2.0 * range - range
+    };
+    (0..PAIRS).map(|_| (next(90.0), next(180.0), next(90.0), next(180.0))).collect()
+}
+
+// Best time of `RUNS` runs of `f`.
+fn best<T>(mut f: impl FnMut() -> T) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn report(name: &str, time: Duration, reference: Duration) {
+    let gain = 100.0 * (1.0 - time.as_secs_f64() / reference.as_secs_f64());
+    println!("{name:<28} {:>8.1} ms  {gain:>+6.1}%", time.as_secs_f64() * 1e3);
+}
+
+fn main() {
+    let pairs = pairs();
+    let radians: Vec<_> = pairs
+        .iter()
+        .map(|&(lat1, lon1, lat2, lon2)| (lat1.to_radians(), lon1.to_radians(), lat2.to_radians(), lon2.to_radians()))
+        .collect();
+
+    let loop_time = best(|| {
+        let mut out: Vec<Result<f64, HaversineError>> = Vec::new();
+        for &(lat1, lon1, lat2, lon2) in black_box(&pairs) {
+            out.push(haversine(lat1, lon1, lat2, lon2));
+        }
+        out
+    });
+    let batch_time = best(|| haversine_batch(black_box(&pairs)));
+
+    // Per call, without the allocation: degrees against radians.
+    let degrees_time = best(|| {
+        black_box(&pairs).iter().map(|&(a, b, c, d)| haversine(a, b, c, d).unwrap_or_default()).sum::<f64>()
+    });
+    let radians_time = best(|| {
+        black_box(&radians).iter().map(|&(a, b, c, d)| haversine_rad(a, b, c, d).unwrap_or_default()).sum::<f64>()
+    });
+
+    println!("{PAIRS} pairs, best of {RUNS} runs");
+    report("haversine loop into a Vec", loop_time, loop_time);
+    report("haversine_batch", batch_time, loop_time);
+    report("haversine (sum)", degrees_time, degrees_time);
+    report("haversine_rad (sum)", radians_time, degrees_time);
+}
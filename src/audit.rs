@@ -0,0 +1,176 @@
+// src/audit.rs
+
+use std::fmt::Write;
+
+use csv::StringRecord;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::InputFormat;
+use crate::geo::looks_like_ddm;
+use crate::geo::looks_like_dms;
+use crate::geo::parse_number;
+
+/* ---------------- CONSTANTES ---------------- */
+
+// Coordinate columns inspected by the audit: pair, then single-point input
+// (--destination has the A ones only).
+const COORD_HEADERS: &[&str] = &["lat_a", "lon_a", "lat_b", "lon_b", "lat", "lon"];
+
+// Last number of a coordinate string: its finest field
+// (DD value, DDM minutes, DMS seconds).
+static LAST_NUMBER_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\d+(?:\.(\d*))?").expect("Invalid number regex")
+});
+
+/* ---------------- PRECISION AUDIT ---------------- */
+
+// Distribution of the precision of the input coordinate values.
+#[derive(Debug)]
+pub struct PrecisionAudit {
+    format: InputFormat,
+    counts: Vec<u64>,   // one counter per bucket label
+}
+
+impl PrecisionAudit {
+    pub fn new(format: InputFormat) -> Self {
+        let buckets = Self::labels(format).len();
+        PrecisionAudit { format, counts: vec![0; buckets] }
+    }
+
+    // Bucket labels, from the coarsest to the finest precision.
+    fn labels(format: InputFormat) -> &'static [&'static str] {
+        match format {
+            InputFormat::Dd => &["<= 4 decimals", "5 decimals", ">= 6 decimals"],
+            InputFormat::Dms => &["whole seconds", "sub-second"],
+            InputFormat::Ddm => &["whole minutes", "decimal minutes"],
+        }
+    }
+
+    // Number of decimals of the finest field (None if no number).
+    fn decimals(raw: &str) -> Option<usize> {
+        let caps = LAST_NUMBER_RE.captures_iter(raw).last()?;
+        Some(caps.get(1).map_or(0, |d| d.as_str().len()))
+    }
+
+    // Accounts for one raw coordinate value.
+    // Values without any number are not counted.
+    pub fn record(&mut self, raw: &str) {
+        let Some(decimals) = Self::decimals(raw) else {
+            return;
+        };
+        let bucket = match self.format {
+            InputFormat::Dd => decimals.saturating_sub(4).min(2),
+            InputFormat::Dms | InputFormat::Ddm => usize::from(decimals > 0),
+        };
+        self.counts[bucket] += 1;
+    }
+
+    // Accounts for the four coordinate values of a raw record.
+    pub fn record_row(&mut self, headers: &StringRecord, record: &StringRecord) {
+        for (value, header) in record.iter().zip(headers.iter()) {
+            if COORD_HEADERS.contains(&header) {
+                self.record(value);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn count(&self, label: &str) -> u64 {
+        Self::labels(self.format)
+            .iter()
+            .position(|&l| l == label)
+            .map_or(0, |i| self.counts[i])
+    }
+
+    // Small text table, one line per bucket.
+    pub fn report(&self) -> String {
+        let total: u64 = self.counts.iter().sum();
+        let mut out = format!(
+            "Precision audit ({}, {} coordinate value(s))\n",
+            self.format.label(),
+            total,
+        );
+        for (label, &count) in Self::labels(self.format).iter().zip(&self.counts) {
+            let percent = if total > 0 { 100.0 * count as f64 / total as f64 } else { 0.0 };
+            let _ = writeln!(out, "  {label:<16} {count:>8} {percent:>6.1}%");
+        }
+        out
+    }
+}
+
+/* ---------------- FORMAT REPORT ---------------- */
+
+// Labels of the detected notations, `other` last.
+const FORMAT_LABELS: [&str; 4] = ["DD", "DMS", "DDM", "other"];
+
+// Distribution of the detected coordinate notation, per column.
+// Diagnostic only: the processing itself never guesses the format.
+#[derive(Debug)]
+pub struct FormatReport {
+    columns: Vec<(&'static str, [u64; 4])>,  // one counter per FORMAT_LABELS
+}
+
+impl FormatReport {
+    // One counter per coordinate column of the input header.
+    pub fn new(headers: &StringRecord) -> Self {
+        let columns = COORD_HEADERS
+            .iter()
+            .filter(|&&h| headers.iter().any(|c| c == h))
+            .map(|&h| (h, [0; 4]))
+            .collect();
+        FormatReport { columns }
+    }
+
+    // Notation of a raw value, from its shape only.
+    pub fn detect(raw: &str) -> Option<InputFormat> {
+        if parse_number(raw.trim()).is_ok() {
+            Some(InputFormat::Dd)
+        } else if looks_like_dms(raw) {
+            Some(InputFormat::Dms)
+        } else if looks_like_ddm(raw) {
+            Some(InputFormat::Ddm)
+        } else {
+            None
+        }
+    }
+
+    // Accounts for the four coordinate values of a raw record.
+    pub fn record_row(&mut self, headers: &StringRecord, record: &StringRecord) {
+        for (value, header) in record.iter().zip(headers.iter()) {
+            let Some((_, counts)) = self.columns.iter_mut().find(|(h, _)| *h == header) else {
+                continue;
+            };
+            let bucket = match Self::detect(value) {
+                Some(InputFormat::Dd) => 0,
+                Some(InputFormat::Dms) => 1,
+                Some(InputFormat::Ddm) => 2,
+                None => 3,
+            };
+            counts[bucket] += 1;
+        }
+    }
+
+    // One line per column, e.g. `lat_a: 90.0% DMS, 10.0% DD (10 value(s))`.
+    // Notations are listed from the most to the least frequent.
+    pub fn report(&self) -> String {
+        let mut out = String::from("Coordinate format report\n");
+        for (header, counts) in &self.columns {
+            let total: u64 = counts.iter().sum();
+            let mut found: Vec<(u64, &str)> = counts
+                .iter()
+                .zip(FORMAT_LABELS)
+                .filter(|&(&c, _)| c > 0)
+                .map(|(&c, label)| (c, label))
+                .collect();
+            found.sort_by_key(|&(count, _)| std::cmp::Reverse(count));
+
+            let parts: Vec<String> = found
+                .iter()
+                .map(|(c, label)| format!("{:.1}% {label}", 100.0 * *c as f64 / total as f64))
+                .collect();
+            let _ = writeln!(out, "  {header}: {} ({total} value(s))", parts.join(", "));
+        }
+        out
+    }
+}
@@ -0,0 +1,786 @@
+// src/cli.rs
+
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::{Args, CommandFactory, FromArgMatches, Subcommand};
+use clap::ValueEnum;
+use clap::Parser;
+use serde::Serialize;
+
+use crate::util::NearlyMode;
+use crate::util::RoundingMode;
+use crate::util::EARTH_RADIUS_KM;
+use crate::util::Pole;
+use crate::input::SourceFormat;
+use crate::geo::DmsSymbols;
+use crate::geo::Locale;
+use crate::geo::datum::Datum;
+
+use crate::parse::KNOWN_HEADERS;
+use crate::output::OUTPUT_COLUMNS;
+
+/* ---------------- CLI ---------------- */
+
+// Top-level command line: a subcommand, or directly the `convert` options
+// (the original flat syntax, kept for existing scripts).
+#[derive(Parser, Debug)]
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
+pub(crate) struct App {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+
+    #[command(flatten)]
+    pub(crate) convert: Option<Cli>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Enrich a file of coordinate pairs (same options as without subcommand)
+    Convert(Cli),
+    /// Parse and check an input file without writing anything (convert --validate)
+    Validate(Cli),
+    /// Print the distance between two points given on the command line
+    Distance(DistanceArgs),
+    /// Print distance_km, distance_miles and the A-B bearing of two points given on the command line
+    Pair(PairArgs),
+}
+
+// Options of the `distance` subcommand.
+#[derive(Args, Debug)]
+pub(crate) struct DistanceArgs {
+    /// Start point, as LAT,LON (e.g. 48.85,2.29)
+    #[arg(long, value_name = "LAT,LON", allow_hyphen_values = true)]
+    pub(crate) from: String,
+
+    /// End point, as LAT,LON (e.g. 40.68,-74.04)
+    #[arg(long, value_name = "LAT,LON", allow_hyphen_values = true)]
+    pub(crate) to: String,
+
+    /// Coordinate format of both points
+    #[arg(short = 'f', long, value_enum, default_value_t = InputFormat::Dd)]
+    pub(crate) input_format: InputFormat,
+
+    /// Sphere radius in kilometers
+    #[arg(long, value_name = "KM", default_value_t = EARTH_RADIUS_KM, value_parser = parse_radius)]
+    pub(crate) earth_radius: f64,
+}
+
+// Options of the `pair` subcommand.
+#[derive(Args, Debug)]
+pub(crate) struct PairArgs {
+    /// Point A, as LAT,LON (e.g. 48°51'29"N,2°17'40"E with -f dms)
+    #[arg(long, value_name = "LAT,LON", allow_hyphen_values = true)]
+    pub(crate) a: String,
+
+    /// Point B, as LAT,LON
+    #[arg(long, value_name = "LAT,LON", allow_hyphen_values = true)]
+    pub(crate) b: String,
+
+    /// Coordinate format of both points
+    #[arg(short = 'f', long, value_enum, default_value_t = InputFormat::Dd)]
+    pub(crate) input_format: InputFormat,
+
+    /// Sphere radius in kilometers
+    #[arg(long, value_name = "KM", default_value_t = EARTH_RADIUS_KM, value_parser = parse_radius)]
+    pub(crate) earth_radius: f64,
+}
+
+// Command-line interface definition.
+#[derive(Parser, Debug, Serialize)]
+#[command(author, version, about)]
+pub struct Cli {
+    /// TOML file of default options, keyed by long option name (e.g. input-format = "dms"); command-line options win
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Input CSV file path (repeatable: the files are read in order into one output)
+    #[arg(short, long, required = true)]
+    pub(crate) input: Vec<PathBuf>,
+
+    /// Input file format (JSON: array of objects with the required fields)
+    #[arg(long, value_enum, default_value_t = SourceFormat::Csv)]
+    pub(crate) input_format_file: SourceFormat,
+
+    /// Worksheet of an XLSX input (default: the first sheet)
+    #[cfg(feature = "xlsx")]
+    #[arg(long, value_name = "NAME")]
+    pub(crate) sheet: Option<String>,
+
+    /// Output CSV file path
+    #[arg(short, long, required_unless_present = "validate")]
+    pub(crate) output: Option<PathBuf>,
+
+    /// Append to the output file instead of replacing it: no new header, ids continue after its last id
+    #[arg(long, requires = "output")]
+    pub(crate) append: bool,
+
+    /// Create the missing parent directories of the output and manifest files
+    #[arg(long)]
+    pub(crate) mkdir: bool,
+
+    /// Id of the first written row (default 1, or the last id + 1 with --append)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub(crate) start_id: Option<u64>,
+
+    /// Only parse and validate the rows, report the valid / invalid counts and write no output (exit code 1 if any row is invalid)
+    #[arg(long, conflicts_with_all = ["output", "manifest", "waypoints"])]
+    pub(crate) validate: bool,
+
+    /// Coordinate input format
+    #[arg(short ='f', long, value_enum)]
+    pub(crate) input_format: InputFormat,
+
+    /// Strict mode: stop on first error
+    #[arg(long)]
+    pub(crate) strict: bool,
+
+    /// Permissive mode, but abort once more than N lines are invalid
+    #[arg(long, value_name = "N", conflicts_with = "strict")]
+    pub(crate) max_errors: Option<u64>,
+
+    /// Empty coordinate cell: reject the row (error), skip it without counting it as invalid (skip) or read it as 0.0 (zero)
+    #[arg(long, value_enum, value_name = "POLICY", default_value = "error")]
+    pub(crate) empty: EmptyCells,
+
+    /// Log errors only: no end-of-run summary (ignored lines, skipped duplicates), reports nor warnings on stderr
+    #[arg(long, short = 'q')]
+    quiet: bool,
+
+    /// Also log the reason of every ignored line to stderr
+    #[arg(long, short = 'v', conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Level of the stderr messages, instead of --quiet / --verbose / RUST_LOG (warn: ignored lines and warnings, info: summary and reports too)
+    #[arg(long, value_enum, value_name = "LEVEL", conflicts_with_all = ["quiet", "verbose"])]
+    log_level: Option<LogLevel>,
+
+    /// Ignore the first N data rows (not counted as read nor invalid)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub(crate) skip: usize,
+
+    /// Stop after reading N data rows (after --skip); with --sort-by, write the first N sorted rows instead
+    #[arg(long, value_name = "N")]
+    pub(crate) limit: Option<usize>,
+
+    /// Abort when more than N entries are held in memory (--skip-duplicate-pairs, --bbox, --cross-group, --matrix, --sort-by)
+    #[arg(long, value_name = "N")]
+    pub(crate) max_memory: Option<usize>,
+
+    /// Write the rows sorted by this key instead of the input order (all rows are held in memory)
+    #[arg(long, value_enum, value_name = "KEY",
+          conflicts_with_all = ["waypoints", "cross_group", "destination", "track"])]
+    pub(crate) sort_by: Option<SortKey>,
+
+    /// Read a required column from another header, as TARGET=SOURCE (e.g. lat_a=origin_lat), repeatable
+    #[arg(long = "map", value_name = "TARGET=SOURCE", value_parser = parse_header_map)]
+    pub(crate) header_map: Vec<(String, String)>,
+
+    /// Fail if the input has columns other than the expected ones (and --allow-column)
+    #[arg(long)]
+    pub(crate) strict_headers: bool,
+
+    /// Extra column accepted by --strict-headers, repeatable
+    #[arg(long, value_name = "NAME", requires = "strict_headers")]
+    pub(crate) allow_column: Vec<String>,
+
+    /// CSV file of manual coordinate corrections (columns: by,key,lat,lon)
+    #[arg(long, visible_alias = "coordinate-fixups", value_name = "FILE")]
+    pub(crate) fixups: Option<PathBuf>,
+
+    /// Coordinate columns hold [lon, lat] pairs (GeoJSON order): lat_* columns are read as longitudes, lon_* as latitudes
+    #[arg(long)]
+    pub(crate) lonlat: bool,
+
+    /// Keep DD values out of the latitude/longitude bounds, flagged in a `suspect` column (still an error in strict mode)
+    #[arg(long)]
+    pub(crate) sanity_check: bool,
+
+    /// Skip (A,B) pairs already seen, in either order (B,A)
+    #[arg(long)]
+    pub(crate) skip_duplicate_pairs: bool,
+
+    /// Add a `colocated` column: distance_km <= KM (25 km when no value is given)
+    #[arg(long, value_name = "KM", num_args = 0..=1, default_missing_value = "25", value_parser = parse_radius)]
+    pub(crate) colocated_km: Option<f64>,
+
+    /// Fail when a distance exceeds half the Earth's circumference (20037.5 km): a bug or a wrong --earth-radius / radius_km
+    #[arg(long)]
+    pub(crate) check_distance: bool,
+
+    /// Print a warning (line and names) for every distance above KM, a likely data error; the row is still written
+    #[arg(long, value_name = "KM", value_parser = parse_radius)]
+    pub(crate) warn_above: Option<f64>,
+
+    /// nearly_* flags require identical coordinates (after rounding to 6 decimals) instead of a 1e-6° tolerance
+    #[arg(long)]
+    pub(crate) exact_match: bool,
+
+    /// Tolerance of the nearly_* flags on both axes, in degrees (default 1e-6, about 11 cm)
+    #[arg(long, value_name = "DEG", value_parser = parse_tolerance, conflicts_with = "exact_match")]
+    pub(crate) tolerance: Option<f64>,
+
+    /// Tolerance of nearly_lat, in degrees (overrides --tolerance)
+    #[arg(long, value_name = "DEG", value_parser = parse_tolerance, conflicts_with = "exact_match")]
+    pub(crate) tolerance_lat: Option<f64>,
+
+    /// Tolerance of nearly_lon, in degrees (overrides --tolerance); a degree of longitude shrinks with cos(lat)
+    #[arg(long, value_name = "DEG", value_parser = parse_tolerance, conflicts_with = "exact_match")]
+    pub(crate) tolerance_lon: Option<f64>,
+
+    /// nearly_both combination: `and` = same point (latitude and longitude match), `or` = either axis matches (fuzzy joins)
+    #[arg(long, value_enum, default_value_t = NearlyMode::And)]
+    pub(crate) nearly_mode: NearlyMode,
+
+    /// Write a distance of exactly 0 when A and B are nearly identical (nearly_lat and nearly_lon)
+    #[arg(long)]
+    pub(crate) snap_zero: bool,
+
+    /// Add a `distance_long_km` column: length of the long great-circle arc (circumference - distance_km)
+    #[arg(long)]
+    pub(crate) emit_long_arc: bool,
+
+    /// Add an `antipodal` column: A and B are exact antipodes (within 1e-6°)
+    #[arg(long)]
+    pub(crate) flag_antipodal: bool,
+
+    /// Add `delta_lat_deg` / `delta_lon_deg` columns: absolute A-B differences per axis, compared by the nearly_* flags
+    #[arg(long)]
+    pub(crate) emit_deltas: bool,
+
+    /// Add a `format_used` column: notation the coordinates of the row were parsed with (dd, dms or ddm)
+    #[arg(long)]
+    pub(crate) emit_format_used: bool,
+
+    /// Add a `lon_sep_deg` column: minimal longitude separation of A and B, in [0, 180]
+    #[arg(long)]
+    pub(crate) emit_lon_sep: bool,
+
+    /// Add a `crosses_antimeridian` column: the shortest path from A to B crosses the 180° meridian (lon 179 to -179)
+    #[arg(long)]
+    pub(crate) flag_antimeridian: bool,
+
+    /// Notations of the coordinate columns of each point (comma-separated): dms (*_dms), ddm (*_ddm), iso (point_*_iso)
+    #[arg(long, value_enum, value_name = "FORMATS", value_delimiter = ',', default_value = "dms")]
+    pub(crate) emit_formats: Vec<EmitFormat>,
+
+    /// Add `point_a_iso` / `point_b_iso` columns: points as ISO 6709 strings (e.g. +48.858056+002.294500/), as --emit-formats iso
+    #[arg(long)]
+    pub(crate) iso6709: bool,
+
+    /// Add WKT columns for PostGIS: `geom_a` / `geom_b` as POINT(lon lat), `geom_line` as LINESTRING(lon_a lat_a, lon_b lat_b)
+    #[arg(long)]
+    pub(crate) wkt: bool,
+
+    /// Add a `polyline` column: Google encoded polyline of A -> B, or of N great circle points (A and B included)
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "2",
+          value_parser = clap::value_parser!(u32).range(2..), conflicts_with = "waypoints")]
+    pub(crate) emit_polyline: Option<u32>,
+
+    /// Add `x_a` / `y_a` / `x_b` / `y_b` columns: Web Mercator (EPSG:3857) meters, latitude clamped to ±85.0511°
+    #[arg(long)]
+    pub(crate) emit_mercator: bool,
+
+    /// Datum of the input coordinates, written in a `datum` column
+    #[arg(long, value_enum, conflicts_with_all = ["destination", "track"])]
+    pub(crate) datum: Option<Datum>,
+
+    /// Move the --datum coordinates to WGS84 before any computation (NAD27: Molodensky, about 5 m in the US)
+    #[arg(long, requires = "datum")]
+    pub(crate) datum_shift: bool,
+
+    /// Add `cell_a` / `cell_b` columns: cell of a DEG-degree grid containing each point, as `row_col` (e.g. 12_-3)
+    #[arg(long, value_name = "DEG", value_parser = parse_grid_size)]
+    pub(crate) grid_size: Option<f64>,
+
+    /// Add `tz_a` / `tz_b` columns: nautical time zone of each point, round(lon / 15) hours (not the legal time zone)
+    #[arg(long)]
+    pub(crate) emit_tz: bool,
+
+    /// Add `bearing_deg` (initial bearing from A to B) and `bearing_compass` (16-wind point, e.g. NNE) columns
+    #[arg(long)]
+    pub(crate) emit_bearing: bool,
+
+    /// Add distance and bearing from each point to a pole
+    #[arg(long, value_enum, value_name = "POLE")]
+    pub(crate) to_pole: Option<Pole>,
+
+    /// Instead of distances, write N evenly spaced points (A and B included) along each A-B great circle
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(2..))]
+    pub(crate) waypoints: Option<u32>,
+
+    /// Print the distribution of input coordinate precisions to stderr
+    #[arg(long, visible_alias = "coordinate-precision-audit")]
+    pub(crate) precision_audit: bool,
+
+    /// Print the extent of all written points to stderr, as min_lat,min_lon,max_lat,max_lon
+    #[arg(long)]
+    pub(crate) bbox: bool,
+
+    /// Antimeridian-aware --bbox: smallest longitude range, min_lon > max_lon when it crosses 180°
+    #[arg(long, requires = "bbox")]
+    pub(crate) wrap_longitude: bool,
+
+    /// Expected extent of every point, as MIN_LAT,MIN_LON,MAX_LAT,MAX_LON: adds an `in_expected_bbox` column (error in strict mode)
+    #[arg(long, value_name = "BBOX", allow_hyphen_values = true, value_parser = parse_expect_bbox)]
+    pub(crate) expect_bbox: Option<[f64; 4]>,
+
+    /// Print, per coordinate column, the distribution of detected formats (DD, DMS, DDM) to stderr
+    #[arg(long, visible_alias = "coordinate-format-report")]
+    pub(crate) format_report: bool,
+
+    /// Write a JSON provenance manifest (file hashes, row counts, options, version)
+    #[arg(long, value_name = "FILE")]
+    pub(crate) manifest: Option<PathBuf>,
+
+    /// Number of decimals on the seconds of the DMS output columns
+    #[arg(long, value_name = "N", default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=10))]
+    pub(crate) dms_precision: u8,
+
+    /// Write the DD columns (*_dd) of a CSV output and the WKT columns with exactly N decimals (2.000000 instead of 2.0)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(0..=6))]
+    pub(crate) coord_precision: Option<u8>,
+
+    /// Number of decimals of the km distances (distance_*_km, dist_to_pole_km_*, --matrix square cells, --track segments and length)
+    #[arg(long, value_name = "N", default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=10))]
+    pub(crate) km_precision: u8,
+
+    /// Number of decimals of the distance_miles column
+    #[arg(long, value_name = "N", default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=10))]
+    pub(crate) miles_precision: u8,
+
+    /// Add the computed coordinates (--waypoints points, --destination point) in this notation, e.g. the --input-format one
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub(crate) output_coord_format: Option<InputFormat>,
+
+    /// Direction letters of the DMS output columns (fr: O instead of W for western longitudes)
+    #[arg(long, value_enum, default_value_t = Locale::En)]
+    pub(crate) locale: Locale,
+
+    /// Minute / second symbols of the DMS output columns (unicode: 48°51′29.00″N)
+    #[arg(long, value_enum, default_value_t = DmsSymbols::Ascii)]
+    pub(crate) dms_symbols: DmsSymbols,
+
+    /// Number format of the computed numeric columns of a CSV output (fr: 1 234,56 with a decimal comma)
+    #[arg(long, value_enum, default_value_t = Locale::En)]
+    pub(crate) number_locale: Locale,
+
+    /// Tie-breaking of the coordinate and distance rounding (half-even: 2.5 -> 2, 3.5 -> 4)
+    #[arg(long, value_enum, default_value_t = RoundingMode::HalfUp)]
+    pub(crate) rounding: RoundingMode,
+
+    /// Add a `seconds_rounded` column: DMS input seconds have more decimals than --dms-precision
+    #[arg(long)]
+    pub(crate) flag_seconds_rounded: bool,
+
+    /// Add a `roundtrip_ok` column: every DD value survives DD -> DMS output -> DD within the nearly tolerance
+    #[arg(long)]
+    pub(crate) roundtrip_check: bool,
+
+    /// Read DMS / DDM values with swapped degree and minute marks (48'51°29"N as 48°51'29"N) and add a `repaired` column
+    #[arg(long)]
+    pub(crate) repair: bool,
+
+    /// Swap points A and B in the output (names, coordinates and per-point columns)
+    #[arg(long)]
+    pub(crate) reverse: bool,
+
+    /// Copy the other input columns to the output, after the computed columns
+    #[arg(long, conflicts_with = "cross_group")]
+    pub(crate) passthrough: bool,
+
+    /// Start-point input (lat_a,lon_a,bearing,distance_km): write the destination point of each row
+    #[arg(long, conflicts_with_all = ["cross_group", "passthrough", "waypoints"])]
+    pub(crate) destination: bool,
+
+    /// Single-point input (name,lat,lon) read as an ordered track: write each point with its segment and cumulative length
+    #[arg(long, conflicts_with_all = ["cross_group", "destination", "passthrough", "waypoints"])]
+    pub(crate) track: bool,
+
+    /// Single-point input (name,lat,lon): compute every pair across the two values of COLUMN
+    #[arg(long, value_name = "COLUMN")]
+    pub(crate) cross_group: Option<String>,
+
+    /// Single-point input (name,lat,lon): pair every row (A) with this fixed LAT,LON point (B), in the --input-format notation
+    #[arg(long, value_name = "LAT,LON", conflicts_with_all = ["cross_group", "destination", "track"])]
+    pub(crate) anchor: Option<String>,
+
+    /// Single-point input (name,lat,lon): distance of every pair of points, as pair rows (long) or a name x name table (square)
+    #[arg(long, value_enum, value_name = "FORM",
+          conflicts_with_all = ["cross_group", "anchor", "destination", "track", "waypoints", "passthrough", "sort_by"])]
+    pub(crate) matrix: Option<MatrixForm>,
+
+    /// With --matrix, each pair once, A before B in the input: N(N-1)/2 distances instead of N(N-1)
+    #[arg(long, requires = "matrix")]
+    pub(crate) upper_triangular: bool,
+
+    /// Write only these output columns, in this order (comma-separated, e.g. id,name_a,name_b,distance_km)
+    #[arg(long, value_name = "NAMES", value_delimiter = ',', value_parser = parse_column,
+          conflicts_with_all = ["destination", "track", "waypoints"])]
+    pub(crate) columns: Vec<&'static str>,
+
+    /// Prefix the names of the computed output columns (geo_ -> geo_distance_km); the input columns keep their name
+    #[arg(long, value_name = "PREFIX")]
+    pub(crate) column_prefix: Option<String>,
+
+    /// Output file format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    pub(crate) output_format: OutputFormat,
+
+    /// Headerless CSV input: columns are read by position (name_a,lat_a,lon_a,name_b,lat_b,lon_b)
+    #[arg(long, conflicts_with_all = ["header_map", "cross_group", "strict_headers", "passthrough"])]
+    pub(crate) no_header: bool,
+
+    /// Tab-separated input and output (no quoting)
+    #[arg(long, conflicts_with_all = ["quote_style", "quote_char"])]
+    pub(crate) tsv: bool,
+
+    /// CSV output quoting
+    #[arg(long, value_enum, default_value_t = QuoteStyle::Necessary)]
+    pub(crate) quote_style: QuoteStyle,
+
+    /// CSV output quote character (single ASCII character)
+    #[arg(long, value_name = "CHAR", default_value = "\"", value_parser = parse_quote_char)]
+    pub(crate) quote_char: u8,
+
+    /// Sphere radius in kilometers used for distances (e.g. 3389.5 for Mars)
+    #[arg(long, value_name = "KM", default_value_t = EARTH_RADIUS_KM, value_parser = parse_radius)]
+    pub(crate) earth_radius: f64,
+}
+
+// Options of a library run, without an argument list: `Cli::new` gives those
+// of `-i INPUT -o OUTPUT -f FORMAT`, every other option at its default, and
+// the setters below turn on the usual switches.
+impl Cli {
+    pub fn new(input: impl Into<PathBuf>, output: impl Into<PathBuf>, format: InputFormat) -> Cli {
+        // The defaults are the clap ones, so that both cannot drift apart.
+        let mut cli = Cli::parse_from(["ektaon", "-i", "-", "-o", "-", "-f", "dd"]);
+        cli.input = vec![input.into()];
+        cli.output = Some(output.into());
+        cli.input_format = format;
+        cli
+    }
+
+    // One more input file, read after the previous ones (repeated -i).
+    pub fn add_input(mut self, path: impl Into<PathBuf>) -> Cli {
+        self.input.push(path.into());
+        self
+    }
+
+    // --strict: stop on the first invalid line.
+    pub fn strict(mut self, on: bool) -> Cli {
+        self.strict = on;
+        self
+    }
+
+    // --tsv: tab-separated input and output.
+    pub fn tsv(mut self, on: bool) -> Cli {
+        self.tsv = on;
+        self
+    }
+
+    // --passthrough: copy the other input columns to the output.
+    pub fn passthrough(mut self, on: bool) -> Cli {
+        self.passthrough = on;
+        self
+    }
+
+    // --reverse: swap points A and B in the output.
+    pub fn reverse(mut self, on: bool) -> Cli {
+        self.reverse = on;
+        self
+    }
+}
+
+// Validates the `--earth-radius` value (positive and finite).
+pub(crate) fn parse_radius(s: &str) -> Result<f64, String> {
+    let radius: f64 = s.parse().map_err(|_| format!("`{s}` is not a number"))?;
+    if !radius.is_finite() || radius <= 0.0 {
+        return Err(format!("`{s}` must be a positive, finite number of km"));
+    }
+    Ok(radius)
+}
+
+// Validates the `--grid-size` value (positive and finite).
+fn parse_grid_size(s: &str) -> Result<f64, String> {
+    let size: f64 = s.parse().map_err(|_| format!("`{s}` is not a number"))?;
+    if !size.is_finite() || size <= 0.0 {
+        return Err(format!("`{s}` must be a positive, finite number of degrees"));
+    }
+    Ok(size)
+}
+
+// Validates a `--tolerance*` value (zero or positive, finite).
+fn parse_tolerance(s: &str) -> Result<f64, String> {
+    let tolerance: f64 = s.parse().map_err(|_| format!("`{s}` is not a number"))?;
+    if !tolerance.is_finite() || tolerance < 0.0 {
+        return Err(format!("`{s}` must be a non-negative, finite number of degrees"));
+    }
+    Ok(tolerance)
+}
+
+// Parses the `--expect-bbox` value: four finite numbers, latitudes in
+// [-90 ; 90] and ordered, longitudes in [-180 ; 180]. min_lon > max_lon is
+// a box across the antimeridian.
+pub(crate) fn parse_expect_bbox(s: &str) -> Result<[f64; 4], String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().ok().filter(|v| v.is_finite()))
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(|| format!("`{s}` must be four finite numbers"))?;
+    let Ok([min_lat, min_lon, max_lat, max_lon]) = <[f64; 4]>::try_from(values) else {
+        return Err(format!("`{s}` must be written MIN_LAT,MIN_LON,MAX_LAT,MAX_LON"));
+    };
+    if ![min_lat, max_lat].iter().all(|lat| (-90.0..=90.0).contains(lat))
+        || ![min_lon, max_lon].iter().all(|lon| (-180.0..=180.0).contains(lon))
+    {
+        return Err(format!("`{s}` is out of range (latitudes within ±90, longitudes within ±180)"));
+    }
+    if min_lat > max_lat {
+        return Err(format!("`{s}`: MIN_LAT is above MAX_LAT"));
+    }
+    Ok([min_lat, min_lon, max_lat, max_lon])
+}
+
+// Validates a `--columns` name against the OutputRecord columns.
+fn parse_column(s: &str) -> Result<&'static str, String> {
+    OUTPUT_COLUMNS
+        .iter()
+        .find(|&&c| c == s)
+        .copied()
+        .ok_or_else(|| format!("unknown column `{s}` (expected one of: {})", OUTPUT_COLUMNS.join(", ")))
+}
+
+// Validates the `--quote-char` value (one ASCII character).
+fn parse_quote_char(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [c] if c.is_ascii() => Ok(*c),
+        _ => Err(format!("`{s}` must be a single ASCII character")),
+    }
+}
+
+// Parses one `--map TARGET=SOURCE` value.
+pub(crate) fn parse_header_map(s: &str) -> Result<(String, String), String> {
+    let (target, source) = s.split_once('=')
+        .ok_or_else(|| format!("`{s}` must be written TARGET=SOURCE"))?;
+    let (target, source) = (target.trim(), source.trim());
+
+    if !KNOWN_HEADERS.iter().any(|set| set.contains(&target)) {
+        return Err(format!(
+            "unknown column `{target}` (expected one of: {})",
+            KNOWN_HEADERS.concat().join(", "),
+        ));
+    }
+    if source.is_empty() {
+        return Err(format!("missing source column for `{target}`"));
+    }
+    Ok((target.to_string(), source.to_string()))
+}
+
+// Supported coordinate formats.
+#[derive(Copy, Clone, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    Dd,
+    Dms,
+    Ddm,
+}
+
+impl InputFormat {
+    // Name used in messages.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            InputFormat::Dd => "DD",
+            InputFormat::Dms => "DMS",
+            InputFormat::Ddm => "DDM",
+        }
+    }
+}
+
+// Supported output file formats.
+#[derive(Copy, Clone, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OutputFormat {
+    Csv,
+    Ndjson,
+}
+
+// Handling of an empty coordinate cell (--empty).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EmptyCells {
+    Error,
+    Skip,
+    Zero,
+}
+
+// Output order of --sort-by. Ties keep the input order.
+#[derive(Copy, Clone, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SortKey {
+    // Shortest distance first.
+    Distance,
+    // name_a, in byte order.
+    #[value(name = "name_a")]
+    NameA,
+}
+
+// Coordinate columns written by --emit-formats.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EmitFormat {
+    // lat_a_dms…: `48°51'29.00"N` (--dms-precision, --locale, --dms-symbols).
+    Dms,
+    // lat_a_ddm…: `48°51.4834'N`, 4 decimals on the minutes.
+    Ddm,
+    // point_a_iso / point_b_iso: `+48.858056+002.294500/` (as --iso6709).
+    Iso,
+}
+
+// Layout of the --matrix output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MatrixForm {
+    // One pair row per ordered pair of points (all the pair columns).
+    Long,
+    // One row per point: its name, then its distance (km) to every point.
+    Square,
+}
+
+// CSV output quoting, mapped to `csv::QuoteStyle`.
+#[derive(Copy, Clone, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum QuoteStyle {
+    Necessary,
+    Always,
+    Never,
+}
+
+impl From<QuoteStyle> for csv::QuoteStyle {
+    fn from(style: QuoteStyle) -> Self {
+        match style {
+            QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+            QuoteStyle::Always => csv::QuoteStyle::Always,
+            QuoteStyle::Never => csv::QuoteStyle::Never,
+        }
+    }
+}
+
+// Level of the --log-level option, mapped to `log::LevelFilter`.
+#[derive(Copy, Clone, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/* ---------------- PARSING ---------------- */
+
+// Log target of the ignored lines, off by default (see init_logger).
+pub(crate) const IGNORED_LINES: &str = "ektaon::ignored";
+
+// Diagnostics on stderr (ignored lines, warnings, summary, reports) through
+// `log`, as plain messages. Level: --log-level, else --quiet (errors only) /
+// --verbose (everything, ignored lines included), else RUST_LOG, else the
+// summary, reports and warnings without the ignored lines.
+pub(crate) fn init_logger(cli: &Cli) {
+    let mut builder = env_logger::Builder::new();
+    builder.format(|buf, record| writeln!(buf, "{}", record.args()));
+    match (cli.log_level, std::env::var("RUST_LOG")) {
+        (Some(level), _) => builder.filter_level(level.into()),
+        _ if cli.quiet => builder.filter_level(log::LevelFilter::Error),
+        _ if cli.verbose => builder.filter_level(log::LevelFilter::Info),
+        (None, Ok(filters)) => builder.parse_filters(&filters),
+        (None, Err(_)) => builder.parse_filters(&format!("info,{IGNORED_LINES}=error")),
+    };
+    builder.init();
+}
+
+// Parses the command line. `validate` is `convert --validate`: the flag is
+// added to the arguments so that its conflicts (--output, --manifest…) apply.
+// The --config options are added too, for the arguments the command line does
+// not set: command line, then config file, then built-in defaults.
+pub(crate) fn parse_app(args: impl IntoIterator<Item = impl Into<OsString>>) -> App {
+    let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+    if args.get(1).is_some_and(|a| a == "validate") {
+        args.insert(2, "--validate".into());
+    }
+    let command = App::command()
+        .mut_subcommand("validate", |c| c.mut_arg("validate", |a| a.hide(true)).mut_arg("output", |a| a.hide(true)));
+    if let Some((at, config)) = config_args(&command, &args) {
+        args.splice(at..at, config);
+    }
+    let matches = command.get_matches_from(args);
+    App::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+}
+
+// Arguments read from the --config file, as `--name=value`, with the index
+// where they go (after the subcommand name, if any). Options already on the
+// command line are left out. Exits on an unreadable file or an unknown key,
+// like clap on a bad argument; values are checked by clap afterwards.
+fn config_args(command: &clap::Command, args: &[OsString]) -> Option<(usize, Vec<OsString>)> {
+    // First pass without the checks: the required options may be in the file.
+    let matches = command.clone().ignore_errors(true).try_get_matches_from(args).ok()?;
+    let (options, matches, at) = match matches.subcommand() {
+        Some((name @ ("convert" | "validate"), sub)) => (command.find_subcommand(name)?, sub, 2),
+        Some(_) => return None,
+        None => (command, &matches, 1),
+    };
+    let path = matches.get_one::<PathBuf>("config")?;
+    let fail = |message: String| -> ! {
+        command.clone().error(clap::error::ErrorKind::InvalidValue, format!("--config `{}`: {message}", path.display())).exit()
+    };
+    let table: toml::Table = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| text.parse().map_err(|e: toml::de::Error| e.message().to_string()))
+        .unwrap_or_else(|e| fail(e));
+
+    let mut config = Vec::new();
+    for (key, value) in table {
+        let arg = options
+            .get_arguments()
+            .find(|a| a.get_long() == Some(key.as_str()) && key != "config")
+            .unwrap_or_else(|| fail(format!("unknown option `{key}`")));
+        if matches.value_source(arg.get_id().as_str()) == Some(clap::parser::ValueSource::CommandLine) {
+            continue;
+        }
+        let values = match value {
+            toml::Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                // A flag: `true` sets it, `false` leaves the default.
+                toml::Value::Boolean(on) if !arg.get_action().takes_values() => {
+                    if on {
+                        config.push(format!("--{key}").into());
+                    }
+                    continue;
+                }
+                toml::Value::String(s) => s,
+                toml::Value::Integer(n) => n.to_string(),
+                toml::Value::Float(x) => x.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                _ => fail(format!("unsupported value for `{key}` (expected a string, number, boolean or array)")),
+            };
+            config.push(format!("--{key}={value}").into());
+        }
+    }
+    Some((at, config))
+}
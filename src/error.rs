@@ -0,0 +1,197 @@
+// src/error.rs
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::util::HaversineError;
+use crate::fixup::FixupError;
+use crate::input::InputError;
+use crate::geo::DmsError;
+use crate::geo::DdmError;
+
+/* ---------------- ERRORS ---------------- */
+
+// Application-level errors.
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Input error: {0}")]
+    Input(#[from] InputError),
+
+    #[error("Missing header field '{0}'")]
+    MissingHeaderField(String),
+
+    #[error("Header of `{}` differs from the first input file", .0.display())]
+    InputHeaderMismatch(PathBuf),
+
+    #[error("--{0} reads a single input file")]
+    SingleInputMode(&'static str),
+
+    #[error("--matrix square cannot be used with {0}")]
+    SquareMatrix(&'static str),
+
+    #[error("Unexpected header field(s): {}", .0.join(", "))]
+    UnexpectedHeaderFields(Vec<String>),
+
+    #[error("Cannot map '{column}' to '{target}': the input already has a '{target}' column")]
+    HeaderMapConflict {
+        target: String,
+        column: String,
+    },
+
+    #[error("Invalid coordinate format on line {line} (expected: {expected})")]
+    MixedCoordinateFormat {
+        line: usize,
+        expected: &'static str,
+    },
+
+    #[error("Line {line}: {found} field(s) instead of {expected}")]
+    RaggedRow {
+        line: usize,
+        expected: u64,
+        found: u64,
+    },
+
+    #[error("Line {line}: empty {field}")]
+    EmptyCoordinate {
+        line: usize,
+        field: &'static str,
+    },
+
+    #[error("Line {line}: invalid DD value `{value}` for {field}")]
+    InvalidDd {
+        line: usize,
+        field: &'static str,
+        value: String,
+    },
+
+    #[error("Line {line}: invalid DMS ({source})")]
+    InvalidDms {
+        line: usize,
+        source: DmsError,
+    },
+
+    #[error("Line {line}: invalid DDM ({source})")]
+    InvalidDdm {
+        line: usize,
+        source: DdmError,
+    },
+
+    #[error("Line {line}: third value `{value}` in group column '{column}' (expected two groups)")]
+    TooManyGroups {
+        line: usize,
+        column: String,
+        value: String,
+    },
+
+    #[error("Line {line}: {field} `{value}` is out of range (latitude and longitude swapped?)")]
+    OutOfRange {
+        line: usize,
+        field: &'static str,
+        value: f64,
+    },
+
+    #[error("Line {line}: point {point} ({lat}, {lon}) is outside the --expect-bbox extent")]
+    OutsideExpectedBbox {
+        line: usize,
+        point: &'static str,
+        lat: f64,
+        lon: f64,
+    },
+
+    #[error("Line {line}: invalid radius_km `{value}` (expected a positive number of km)")]
+    InvalidRowRadius {
+        line: usize,
+        value: String,
+    },
+
+    #[error("Line {line}: invalid {field} `{value}` (expected a number of meters)")]
+    InvalidElevation {
+        line: usize,
+        field: &'static str,
+        value: String,
+    },
+
+    #[error("Line {line}: invalid {field} `{value}`")]
+    InvalidDestination {
+        line: usize,
+        field: &'static str,
+        value: String,
+    },
+
+    #[error("Cannot write `{0}` without quotes (--quote-style never)")]
+    UnquotableField(String),
+
+    #[error("Column `{0}` is not written with the current options (see --columns)")]
+    ColumnNotWritten(&'static str),
+
+    #[error("Line {line}: more than {limit} entries held in memory (--max-memory)")]
+    MemoryLimit {
+        line: usize,
+        limit: usize,
+    },
+
+    #[error("Too many invalid lines: {invalid} after {read} row(s) read (--max-errors {limit})")]
+    TooManyErrors {
+        invalid: u64,
+        read: u64,
+        limit: u64,
+    },
+
+    #[error("Invalid {field} point `{value}` (expected LAT,LON in the --input-format notation)")]
+    InvalidPointArg {
+        field: &'static str,
+        value: String,
+    },
+
+    #[error("Cannot read the last id of `{}` to append to it (no `id` column?): use --start-id", .0.display())]
+    AppendId(PathBuf),
+
+    #[error("Directory of `{}` does not exist (create it, or use --mkdir)", .0.display())]
+    MissingOutputDir(PathBuf),
+
+    #[error("Validation failed: {0} invalid line(s)")]
+    ValidationFailed(u64),
+
+    #[error("Interrupted: {0} row(s) written, output flushed")]
+    Interrupted(u64),
+
+    #[error("Cannot install the Ctrl-C handler: {0}")]
+    Signal(#[from] ctrlc::Error),
+
+    #[error("Fixups error: {0}")]
+    Fixup(#[from] FixupError),
+
+    #[error("Distance calculation error: {0}")]
+    Distance(#[from] HaversineError),
+}
+
+impl AppError {
+    // Input line of a per-line error, when its message gives it.
+    pub(crate) fn line(&self) -> Option<usize> {
+        match self {
+            AppError::MixedCoordinateFormat { line, .. }
+            | AppError::RaggedRow { line, .. }
+            | AppError::EmptyCoordinate { line, .. }
+            | AppError::InvalidDd { line, .. }
+            | AppError::InvalidDms { line, .. }
+            | AppError::InvalidDdm { line, .. }
+            | AppError::TooManyGroups { line, .. }
+            | AppError::OutOfRange { line, .. }
+            | AppError::OutsideExpectedBbox { line, .. }
+            | AppError::InvalidRowRadius { line, .. }
+            | AppError::InvalidElevation { line, .. }
+            | AppError::InvalidDestination { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+}
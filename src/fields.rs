@@ -0,0 +1,316 @@
+// src/fields.rs
+
+use csv::StringRecord;
+use serde::ser::{Impossible, SerializeStruct, Serializer};
+use serde::Serialize;
+
+/* ---------------- CSV FIELDS ---------------- */
+
+// Header and values of an output record, as the CSV writer writes them
+// (same number, bool and option text), collected by serializing the record
+// straight into string records. The header is built with the first record
+// only: every row of a run has the same columns.
+#[derive(Debug, Default)]
+pub struct CsvFields {
+    pub header: StringRecord,
+    pub row: StringRecord,
+    value: String,  // text of the field being serialized, reused
+}
+
+impl CsvFields {
+    pub fn fill<T: Serialize>(&mut self, record: &T) -> Result<(), csv::Error> {
+        let with_header = self.header.is_empty();
+        self.row.clear();
+        record.serialize(RecordSerializer { fields: self, with_header })
+    }
+
+    // Header of a single record.
+    pub fn header_of<T: Serialize>(record: &T) -> Result<StringRecord, csv::Error> {
+        let mut fields = CsvFields::default();
+        fields.fill(record)?;
+        Ok(fields.header)
+    }
+}
+
+fn unsupported(what: &str) -> csv::Error {
+    serde::ser::Error::custom(format!("cannot write {what} as a CSV field"))
+}
+
+/* ---------------- RECORD ---------------- */
+
+// Top level: only a struct (one field per column) is a record.
+struct RecordSerializer<'a> {
+    fields: &'a mut CsvFields,
+    with_header: bool,
+}
+
+impl SerializeStruct for RecordSerializer<'_> {
+    type Ok = ();
+    type Error = csv::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), csv::Error> {
+        if self.with_header {
+            self.fields.header.push_field(key);
+        }
+        self.fields.value.clear();
+        value.serialize(FieldSerializer { out: &mut self.fields.value })?;
+        self.fields.row.push_field(&self.fields.value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), csv::Error> {
+        Ok(())
+    }
+}
+
+impl<'a> Serializer for RecordSerializer<'a> {
+    type Ok = ();
+    type Error = csv::Error;
+    type SerializeSeq = Impossible<(), csv::Error>;
+    type SerializeTuple = Impossible<(), csv::Error>;
+    type SerializeTupleStruct = Impossible<(), csv::Error>;
+    type SerializeTupleVariant = Impossible<(), csv::Error>;
+    type SerializeMap = Impossible<(), csv::Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), csv::Error>;
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, csv::Error> {
+        Ok(self)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_i8(self, _: i8) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_i16(self, _: i16) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_i32(self, _: i32) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_i64(self, _: i64) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_u8(self, _: u8) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_u16(self, _: u16) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_u32(self, _: u32) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_u64(self, _: u64) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_f32(self, _: f32) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_f64(self, _: f64) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_char(self, _: char) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_str(self, _: &str) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_bytes(self, _: &[u8]) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_none(self) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_unit(self) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_unit_variant(self, _: &'static str, _: u32, _: &'static str) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _: &'static str, _: &T) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), csv::Error> {
+        Err(unsupported("a value"))
+    }
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, csv::Error> {
+        Err(unsupported("a sequence"))
+    }
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, csv::Error> {
+        Err(unsupported("a tuple"))
+    }
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeTupleStruct, csv::Error> {
+        Err(unsupported("a tuple"))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, csv::Error> {
+        Err(unsupported("a tuple"))
+    }
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, csv::Error> {
+        Err(unsupported("a map"))
+    }
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, csv::Error> {
+        Err(unsupported("a struct variant"))
+    }
+}
+
+/* ---------------- FIELD ---------------- */
+
+// One field value: the scalars, written as the csv crate writes them
+// (ryu for floats, empty for None and unit, the name of a unit variant).
+struct FieldSerializer<'a> {
+    out: &'a mut String,
+}
+
+impl FieldSerializer<'_> {
+    fn display(self, value: impl std::fmt::Display) -> Result<(), csv::Error> {
+        use std::fmt::Write;
+
+        write!(self.out, "{value}").map_err(serde::ser::Error::custom)
+    }
+}
+
+impl<'a> Serializer for FieldSerializer<'a> {
+    type Ok = ();
+    type Error = csv::Error;
+    type SerializeSeq = Impossible<(), csv::Error>;
+    type SerializeTuple = Impossible<(), csv::Error>;
+    type SerializeTupleStruct = Impossible<(), csv::Error>;
+    type SerializeTupleVariant = Impossible<(), csv::Error>;
+    type SerializeMap = Impossible<(), csv::Error>;
+    type SerializeStruct = Impossible<(), csv::Error>;
+    type SerializeStructVariant = Impossible<(), csv::Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), csv::Error> {
+        self.display(v)
+    }
+    fn serialize_i8(self, v: i8) -> Result<(), csv::Error> {
+        self.display(v)
+    }
+    fn serialize_i16(self, v: i16) -> Result<(), csv::Error> {
+        self.display(v)
+    }
+    fn serialize_i32(self, v: i32) -> Result<(), csv::Error> {
+        self.display(v)
+    }
+    fn serialize_i64(self, v: i64) -> Result<(), csv::Error> {
+        self.display(v)
+    }
+    fn serialize_u8(self, v: u8) -> Result<(), csv::Error> {
+        self.display(v)
+    }
+    fn serialize_u16(self, v: u16) -> Result<(), csv::Error> {
+        self.display(v)
+    }
+    fn serialize_u32(self, v: u32) -> Result<(), csv::Error> {
+        self.display(v)
+    }
+    fn serialize_u64(self, v: u64) -> Result<(), csv::Error> {
+        self.display(v)
+    }
+    fn serialize_f32(self, v: f32) -> Result<(), csv::Error> {
+        self.out.push_str(ryu::Buffer::new().format(v));
+        Ok(())
+    }
+    fn serialize_f64(self, v: f64) -> Result<(), csv::Error> {
+        self.out.push_str(ryu::Buffer::new().format(v));
+        Ok(())
+    }
+    fn serialize_char(self, v: char) -> Result<(), csv::Error> {
+        self.out.push(v);
+        Ok(())
+    }
+    fn serialize_str(self, v: &str) -> Result<(), csv::Error> {
+        self.out.push_str(v);
+        Ok(())
+    }
+    fn serialize_bytes(self, _: &[u8]) -> Result<(), csv::Error> {
+        Err(unsupported("bytes"))
+    }
+    fn serialize_none(self) -> Result<(), csv::Error> {
+        Ok(())
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), csv::Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), csv::Error> {
+        Ok(())
+    }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), csv::Error> {
+        Ok(())
+    }
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str) -> Result<(), csv::Error> {
+        self.out.push_str(variant);
+        Ok(())
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _: &'static str, value: &T) -> Result<(), csv::Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), csv::Error> {
+        Err(unsupported("an enum with data"))
+    }
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, csv::Error> {
+        Err(unsupported("a sequence"))
+    }
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, csv::Error> {
+        Err(unsupported("a tuple"))
+    }
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeTupleStruct, csv::Error> {
+        Err(unsupported("a tuple"))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, csv::Error> {
+        Err(unsupported("a tuple"))
+    }
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, csv::Error> {
+        Err(unsupported("a map"))
+    }
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, csv::Error> {
+        Err(unsupported("a nested struct"))
+    }
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, csv::Error> {
+        Err(unsupported("a struct variant"))
+    }
+}
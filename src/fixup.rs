@@ -0,0 +1,135 @@
+// src/fixup.rs
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use csv::{ReaderBuilder, StringRecord};
+use serde::Deserialize;
+
+/* ---------------- DOMAIN TYPES ---------------- */
+
+// Side of an input row a correction applies to: point A or B of a pair
+// row, or the point of a single-point row (--cross-group, --anchor, ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Side {
+    A,
+    B,
+    Point,
+}
+
+impl Side {
+    fn columns(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Side::A => ("name_a", "lat_a", "lon_a"),
+            Side::B => ("name_b", "lat_b", "lon_b"),
+            Side::Point => ("name", "lat", "lon"),
+        }
+    }
+}
+
+// Corrected coordinates, written in the same format as the input file.
+#[derive(Debug, Clone)]
+struct Correction {
+    lat: String,
+    lon: String,
+}
+
+// One line of the fixups file.
+// `by` selects the matching key:
+// - `line_a` / `line_b`: `key` is an input line number (header = line 1),
+//   the correction replaces point A / point B of that row.
+// - `line`: the same for the point of a single-point row.
+// - `name`: `key` is a point name, the correction replaces every
+//   point carrying exactly that name.
+#[derive(Debug, Deserialize)]
+struct FixupRow {
+    by: String,
+    key: String,
+    lat: String,
+    lon: String,
+}
+
+// Errors specific to the fixups file.
+#[derive(Debug, thiserror::Error)]
+pub enum FixupError {
+    #[error("cannot read fixups file: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("fixups line {line}: unknown match key `{by}` (expected line_a, line_b, line or name)")]
+    UnknownKey { line: usize, by: String },
+    #[error("fixups line {line}: invalid line number `{key}`")]
+    InvalidLine { line: usize, key: String },
+}
+
+/* ---------------- FIXUPS ---------------- */
+
+// Manual coordinate corrections applied to raw input rows before parsing.
+#[derive(Debug, Default)]
+pub struct Fixups {
+    by_line: HashMap<(usize, Side), Correction>,
+    by_name: HashMap<String, Correction>,
+}
+
+impl Fixups {
+    // Loads a fixups CSV file (headers: by,key,lat,lon).
+    pub fn load(path: &Path) -> Result<Self, FixupError> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .trim(csv::Trim::All)
+            .from_path(path)?;
+
+        let mut fixups = Fixups::default();
+        for (i, row) in reader.deserialize::<FixupRow>().enumerate() {
+            let row = row?;
+            let line = i + 2;
+            let correction = Correction { lat: row.lat, lon: row.lon };
+
+            let side = match row.by.as_str() {
+                "name" => {
+                    fixups.by_name.insert(row.key, correction);
+                    continue;
+                }
+                "line_a" => Side::A,
+                "line_b" => Side::B,
+                "line" => Side::Point,
+                _ => return Err(FixupError::UnknownKey { line, by: row.by }),
+            };
+            let target: usize = row.key.parse()
+                .map_err(|_| FixupError::InvalidLine { line, key: row.key.clone() })?;
+            fixups.by_line.insert((target, side), correction);
+        }
+
+        Ok(fixups)
+    }
+
+    // Patches the coordinates of a raw record in place, for every side whose
+    // columns are in the header (the name is only needed by name).
+    // Line-based corrections take precedence over name-based ones.
+    pub fn apply(&self, line: usize, headers: &StringRecord, record: &mut StringRecord) {
+        let index = |column: &str| headers.iter().position(|h| h == column);
+
+        for side in [Side::A, Side::B, Side::Point] {
+            let (name_col, lat_col, lon_col) = side.columns();
+            let (Some(lat_i), Some(lon_i)) = (index(lat_col), index(lon_col)) else {
+                continue;
+            };
+
+            let correction = self.by_line.get(&(line, side)).or_else(|| {
+                index(name_col)
+                    .and_then(|name_i| record.get(name_i))
+                    .and_then(|name| self.by_name.get(name))
+            });
+
+            if let Some(c) = correction {
+                *record = record
+                    .iter()
+                    .enumerate()
+                    .map(|(i, field)| match i {
+                        _ if i == lat_i => c.lat.as_str(),
+                        _ if i == lon_i => c.lon.as_str(),
+                        _ => field,
+                    })
+                    .collect();
+            }
+        }
+    }
+}
@@ -3,16 +3,48 @@
 use regex::Regex;
 use once_cell::sync::Lazy;
 
+use crate::util::RoundingMode;
+use crate::util::round_with;
+
+pub mod datum;
+pub mod encoding;
+
 /* ---------------- DOMAIN TYPES ---------------- */
 
 // Indicates whether a coordinate is a latitude or a longitude.
 // Used to apply correct bounds and valid directions.
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CoordinateKind {
     Latitude,
     Longitude,
 }
 
+impl CoordinateKind {
+    // Largest absolute value in decimal degrees (90 or 180).
+    pub fn max_degrees(self) -> f64 {
+        match self {
+            CoordinateKind::Latitude => 90.0,
+            CoordinateKind::Longitude => 180.0,
+        }
+    }
+
+    // Accepted direction letters (uppercase), `O` being the French west.
+    pub fn directions(self) -> &'static [char] {
+        match self {
+            CoordinateKind::Latitude => &['N', 'S'],
+            CoordinateKind::Longitude => &['E', 'W', 'O'],
+        }
+    }
+
+    // Name used in error messages.
+    pub fn label(self) -> &'static str {
+        match self {
+            CoordinateKind::Latitude => "latitude",
+            CoordinateKind::Longitude => "longitude",
+        }
+    }
+}
+
 // Identifies which field failed during parsing.
 // This allows precise and explicit error reporting.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -47,6 +79,42 @@ struct Coordinate {
     dir: char,
 }
 
+// Components of a parsed DMS / DDM coordinate with its decimal value,
+// for callers that need more than the decimal degrees (round-trip tools,
+// validators). `sec` is 0 for DDM, `dir` is uppercase.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParsedCoord {
+    pub deg: f64,
+    pub min: f64,
+    pub sec: f64,
+    pub dir: char,
+    pub dd: f64,
+}
+
+impl ParsedCoord {
+    // Validates the coordinate and attaches its decimal value.
+    fn new(coord: Coordinate, kind: CoordinateKind) -> Result<Self, CoordError> {
+        let Coordinate { deg, min, sec, dir } = coord;
+        let dd = coordinate_to_dd(coord, kind)?;
+        Ok(ParsedCoord { deg, min, sec, dir, dd })
+    }
+}
+
+/* ---------------- NUMBERS ---------------- */
+
+// Minus signs found in text copied from documents, read as ASCII `-`:
+// minus sign, hyphen, non-breaking hyphen, figure dash, en dash,
+// small and fullwidth hyphen-minus.
+const MINUS_VARIANTS: [char; 7] = ['\u{2212}', '\u{2010}', '\u{2011}', '\u{2012}', '\u{2013}', '\u{FE63}', '\u{FF0D}'];
+
+// Parses a number, accepting the Unicode minus variants (`−2.5`).
+pub fn parse_number(input: &str) -> Result<f64, std::num::ParseFloatError> {
+    if input.contains(MINUS_VARIANTS) {
+        input.replace(MINUS_VARIANTS, "-").parse()
+    } else {
+        input.parse()
+    }
+}
 
 /* ---------------- LOW LEVEL VALIDATION ---------------- */
 
@@ -61,15 +129,44 @@ pub enum CoordError {
     InvalidMinutes { min: f64 },
     #[error("invalid seconds value")]
     InvalidSeconds { sec: f64 },
-    #[error("invalid direction `{0}`")]
-    InvalidDirection(char),
+    #[error(
+        "invalid direction `{dir}` for {} (expected {})",
+        kind.label(),
+        kind.directions().iter().map(char::to_string).collect::<Vec<_>>().join("/"),
+    )]
+    InvalidDirection { dir: char, kind: CoordinateKind },
+}
+
+// Tolerance on the geographic bounds (floating-point noise of the
+// minutes / seconds conversion).
+const BOUNDS_EPS: f64 = 1e-12;
+
+// Validates a decimal degrees value against the latitude [-90, 90] /
+// longitude [-180, 180] bounds. Non-finite values are invalid.
+// Nothing is clamped: the value is returned unchanged (-0 normalized to 0),
+// including the floating-point noise within BOUNDS_EPS past a bound, or
+// rejected.
+// This function is the single source of truth for geographic bounds,
+// whatever the input format.
+pub fn normalize_coordinate(value: f64, kind: CoordinateKind) -> Result<f64, CoordError> {
+    if !value.is_finite() {
+        return Err(CoordError::InvalidDegree { deg: value });
+    }
+    if value.abs() > kind.max_degrees() + BOUNDS_EPS {
+        return Err(CoordError::OutOfRange { deg: value });
+    }
+    Ok(value + 0.0)
+}
+
+// Direction of a DMS / DDM value written without one: accepted for an exact
+// zero only (`0°0'0.00"`, as written by dd_to_dms), read as N / E.
+fn zero_direction(deg: f64, min: f64, sec: f64, kind: CoordinateKind) -> Option<char> {
+    (deg == 0.0 && min == 0.0 && sec == 0.0).then(|| kind.directions()[0])
 }
 
 // Validates a parsed coordinate and converts it to decimal degrees.
-// This function is the single source of truth for geographic rules.
+// This function is the single source of truth for the DMS / DDM rules.
 fn coordinate_to_dd(coord: Coordinate, kind: CoordinateKind) -> Result<f64, CoordError> {
-    let eps = 1e-12;
-
     // Validation degree / minutes / seconds
     if coord.deg < 0.0 {
         return Err(CoordError::InvalidDegree { deg: coord.deg });
@@ -81,28 +178,9 @@ fn coordinate_to_dd(coord: Coordinate, kind: CoordinateKind) -> Result<f64, Coor
         return Err(CoordError::InvalidSeconds { sec: coord.sec });
     }
 
-    // Validation of geographical boundaries
-    if kind == CoordinateKind::Latitude {
-        if coord.deg > 90.0 + eps {
-            return Err(CoordError::OutOfRange { deg: coord.deg });
-        }
-        if (coord.deg - 90.0).abs() < eps && (coord.min > 0.0 || coord.sec > 0.0) {
-            return Err(CoordError::OutOfRange { deg: coord.deg });
-        }
-        if !matches!(coord.dir, 'N' | 'S') {
-            return Err(CoordError::InvalidDirection(coord.dir));
-        }
-    }
-    if kind == CoordinateKind::Longitude {
-        if coord.deg > 180.0 + eps {
-            return Err(CoordError::OutOfRange { deg: coord.deg });
-        }
-        if (coord.deg - 180.0).abs() < eps && (coord.min > 0.0 || coord.sec > 0.0) {
-            return Err(CoordError::OutOfRange { deg: coord.deg });
-        }
-        if !matches!(coord.dir, 'E' | 'O' | 'W') {
-            return Err(CoordError::InvalidDirection(coord.dir));
-        }
+    // Validation of the direction
+    if !kind.directions().contains(&coord.dir) {
+        return Err(CoordError::InvalidDirection { dir: coord.dir, kind });
     }
 
     // Conversion to decimal
@@ -113,29 +191,38 @@ fn coordinate_to_dd(coord: Coordinate, kind: CoordinateKind) -> Result<f64, Coor
         value = -value;
     }
 
-    Ok(value)
+    // Validation of geographical boundaries
+    normalize_coordinate(value, kind)
 
 }
 
 /* ---------------- DMS ---------------- */
 
 // Regex for Degrees / Minutes / Seconds format.
-// Supports ASCII and Unicode symbols.
+// Supports ASCII and Unicode symbols, and the letters `d` / `m` / `s` of
+// aviation and amateur radio notations (`48d51m29sN`; `D` and `M` too, but
+// only a lowercase `s`: `S` is the south direction). Apart from these and the
+// direction letters, the pattern is case sensitive. The direction letter may
+// come first (`N48°51'29"`) or last (`48°51'29"N`): exactly one of the two is
+// expected.
 static DMS_RE: Lazy<Regex> = Lazy::new(|| {
     Regex::new(
-        r#"(?ix)^\s*
+        r#"(?x)^\s*
+            ([NSEWOnsewo])? # direction en tête (optionnelle, toute casse)
+            \s*
             (.+?)      # degrés (brut)
-            \s*°\s*
+            \s*[°dD]\s*
             (.+?)      # minutes (brut)
-            \s*['′]\s*
+            \s*['′mM]\s*
             (.+?)      # secondes (brut)
-            \s*["″]\s*
-            (.)        # direction (brut)
+            \s*["″s]\s*
+            (.*?)      # direction (brut, validée ensuite)
             \s*$"#
     ).expect("Invalid DMS regex")
 });
 
 // Errors specific to DMS parsing.
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, thiserror::Error)]
 pub enum DmsError {
     #[error("invalid DMS format")]
@@ -148,30 +235,101 @@ pub enum DmsError {
 
 // Parses a DMS string and converts it to decimal degrees.
 pub fn dms_to_dd(input: &str, kind: CoordinateKind) -> Result<f64, DmsError> {
-    let caps = DMS_RE.captures(input)
-        .ok_or(DmsError::InvalidFormat)?;
-
-    let deg_str = caps.get(1).ok_or(DmsError::InvalidFormat)?.as_str().trim();
-    let deg: f64 = deg_str.parse().map_err(|_| DmsError::InvalidField { field: CoordField::Deg })?;
-    let min_str= caps.get(2).ok_or(DmsError::InvalidFormat)?.as_str().trim();
-    let min: f64 = min_str.parse().map_err(|_| DmsError::InvalidField { field: CoordField::Min })?;
-    let sec_str = caps.get(3).ok_or(DmsError::InvalidFormat)?.as_str().trim();
-    let sec: f64 = sec_str.parse().map_err(|_| DmsError::InvalidField { field: CoordField::Sec })?;
-    let dir_str = caps.get(4).ok_or(DmsError::InvalidFormat)?.as_str().trim();
-    let dir = dir_str
-        .chars()
-        .next()
-        .ok_or(DmsError::InvalidField { field: CoordField::Dir })?
-        .to_ascii_uppercase();
+    Ok(parse_dms(input, kind)?.dd)
+}
+
+// Parses a DMS string into its validated components.
+// Seconds may be omitted: the full DMS shape is tried first, then the DDM
+// one, so that `48°51'N` (seconds 0) and `48°51.5'N` (decimal minutes) are
+// accepted too. Both shapes share `Coordinate` and its validation.
+pub fn parse_dms(input: &str, kind: CoordinateKind) -> Result<ParsedCoord, DmsError> {
+    let Some(caps) = DMS_RE.captures(input) else {
+        return parse_dms_without_seconds(input, kind);
+    };
+
+    let deg_str = caps.get(2).ok_or(DmsError::InvalidFormat)?.as_str().trim();
+    let deg: f64 = parse_number(deg_str).map_err(|_| DmsError::InvalidField { field: CoordField::Deg })?;
+    let min_str= caps.get(3).ok_or(DmsError::InvalidFormat)?.as_str().trim();
+    let min: f64 = parse_number(min_str).map_err(|_| DmsError::InvalidField { field: CoordField::Min })?;
+    let sec_str = caps.get(4).ok_or(DmsError::InvalidFormat)?.as_str().trim();
+    let sec: f64 = parse_number(sec_str).map_err(|_| DmsError::InvalidField { field: CoordField::Sec })?;
+    // Exactly one direction letter, leading or trailing: `North` or `NE` are
+    // typos, not `N`, and `N48°51'29"N` is ambiguous. None for an exact zero.
+    let trailing = caps.get(5).ok_or(DmsError::InvalidFormat)?.as_str().trim();
+    let dir_str = match (caps.get(1), trailing) {
+        (Some(leading), "") => leading.as_str(),
+        (None, trailing) => trailing,
+        (Some(_), _) => return Err(DmsError::InvalidField { field: CoordField::Dir }),
+    };
+    let mut dir_chars = dir_str.chars();
+    let dir = match (dir_chars.next(), dir_chars.next()) {
+        (Some(dir), None) => dir.to_ascii_uppercase(),
+        (None, _) => zero_direction(deg, min, sec, kind).ok_or(DmsError::InvalidField { field: CoordField::Dir })?,
+        _ => return Err(DmsError::InvalidField { field: CoordField::Dir }),
+    };
 
     if !deg.is_finite() || !min.is_finite() || !sec.is_finite() {
         return Err(DmsError::InvalidFormat);
     }
+    // Only the seconds may be decimal: `48.5°51'29"N` mixes notations.
+    if deg.fract() != 0.0 {
+        return Err(DmsError::InvalidField { field: CoordField::Deg });
+    }
+    if min.fract() != 0.0 {
+        return Err(DmsError::InvalidField { field: CoordField::Min });
+    }
 
     let coord = Coordinate { deg, min, sec, dir};
-    let value = coordinate_to_dd(coord, kind)?;
 
-    Ok(value)
+    Ok(ParsedCoord::new(coord, kind)?)
+}
+
+// DMS value without its seconds field, read as DDM (minutes may be decimal).
+fn parse_dms_without_seconds(input: &str, kind: CoordinateKind) -> Result<ParsedCoord, DmsError> {
+    match parse_ddm(input, kind) {
+        Ok(parsed) => Ok(parsed),
+        Err(DdmError::InvalidFormat) => Err(DmsError::InvalidFormat),
+        Err(DdmError::InvalidField { field }) => Err(DmsError::InvalidField { field }),
+        Err(DdmError::InvalidCoord(e)) => Err(DmsError::InvalidCoord(e)),
+    }
+}
+
+// Number of decimals written on the seconds of a DMS string
+// (`29.75"` gives 2), from the raw seconds field.
+// Returns None when the string is not in DMS format.
+pub fn dms_seconds_decimals(input: &str) -> Option<usize> {
+    let sec_str = DMS_RE.captures(input)?.get(4)?.as_str().trim();
+    Some(sec_str.split_once('.').map_or(0, |(_, decimals)| decimals.len()))
+}
+
+// True when the string has the shape of a DMS value (values not validated).
+pub fn looks_like_dms(input: &str) -> bool {
+    DMS_RE.is_match(input)
+}
+
+/* ---------------- REPAIR ---------------- */
+
+// Degrees and minutes marks swapped (`48'51°29"N`, `48'51.5°N`), a common
+// typo or OCR error. The rest (seconds, direction) is kept as is.
+static SWAPPED_MARKS_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?ix)^\s*
+            ([NSEWO]?) # direction en tête (optionnelle)
+            \s*
+            (\d+)      # degrés
+            \s*['′]\s*
+            (\d+(?:[.,]\d*)?) # minutes
+            \s*°
+            (.*)       # secondes, direction
+            $"#
+    ).expect("Invalid swapped marks regex")
+});
+
+// The value with its degrees and minutes marks put back in order, when they
+// look swapped (`48'51°29"N` gives `48°51'29"N`). None otherwise.
+pub fn repair_swapped_marks(input: &str) -> Option<String> {
+    let caps = SWAPPED_MARKS_RE.captures(input)?;
+    Some(format!("{}{}°{}'{}", &caps[1], &caps[2], &caps[3], &caps[4]))
 }
 
 /* ---------------- DDM ---------------- */
@@ -185,12 +343,24 @@ static DDM_RE: Lazy<Regex> = Lazy::new(|| {
             \s*°\s*
             (.+?)      # minutes (brut)
             \s*['′]\s*
-            (.)        # direction (brut)
+            (.?)       # direction (brut, absente pour zéro)
             \s*$"#
     ).expect("Invalid DMS regex")
 });
 
+// Regex for a degrees-only value (`48°N`), read with minutes and seconds 0.
+static DEG_ONLY_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?ix)^\s*
+            (.+?)      # degrés (brut)
+            \s*°\s*
+            (.)        # direction (brut)
+            \s*$"#
+    ).expect("Invalid degrees-only regex")
+});
+
 // Errors specific to DDM parsing.
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, thiserror::Error)]
 pub enum DdmError {
     #[error("invalid DMS format")]
@@ -203,48 +373,250 @@ pub enum DdmError {
 
 // Parses a DDM string and converts it to decimal degrees.
 pub fn ddm_to_dd(input: &str, kind: CoordinateKind) -> Result<f64, DdmError> {
-    let caps = DDM_RE.captures(input)
-        .ok_or(DdmError::InvalidFormat)?;
+    Ok(parse_ddm(input, kind)?.dd)
+}
+
+// Parses a DDM string into its validated components (`sec` is 0).
+// Minutes may be omitted: `48°N` is read as `48°0'N`.
+pub fn parse_ddm(input: &str, kind: CoordinateKind) -> Result<ParsedCoord, DdmError> {
+    let Some(caps) = DDM_RE.captures(input) else {
+        return parse_degrees_only(input, kind);
+    };
 
     let deg_str = caps.get(1).ok_or(DdmError::InvalidFormat)?.as_str().trim();
-    let deg: f64 = deg_str.parse().map_err(|_| DdmError::InvalidField { field:CoordField::Deg })?;
+    let deg: f64 = parse_number(deg_str).map_err(|_| DdmError::InvalidField { field:CoordField::Deg })?;
     let min_str= caps.get(2).ok_or(DdmError::InvalidFormat)?.as_str().trim();
-    let min: f64 = min_str.parse().map_err(|_| DdmError::InvalidField { field:CoordField::Min })?;
+    let min: f64 = parse_number(min_str).map_err(|_| DdmError::InvalidField { field:CoordField::Min })?;
     let dir_str = caps.get(3).ok_or(DdmError::InvalidFormat)?.as_str().trim();
-    let dir = dir_str
+    let sec: f64 = 0.0;
+    let dir = match dir_str.chars().next() {
+        Some(dir) => dir.to_ascii_uppercase(),
+        None => zero_direction(deg, min, sec, kind).ok_or(DdmError::InvalidFormat)?,
+    };
+
+    if !deg.is_finite() || !min.is_finite() {
+        return Err(DdmError::InvalidFormat);
+    }
+    // Only the minutes may be decimal.
+    if deg.fract() != 0.0 {
+        return Err(DdmError::InvalidField { field: CoordField::Deg });
+    }
+
+    let coord = Coordinate { deg, min, sec, dir};
+
+    Ok(ParsedCoord::new(coord, kind)?)
+}
+
+// Degrees-only value (`48°N`): minutes and seconds default to 0, the
+// degrees must be whole as in DMS / DDM.
+fn parse_degrees_only(input: &str, kind: CoordinateKind) -> Result<ParsedCoord, DdmError> {
+    let caps = DEG_ONLY_RE.captures(input)
+        .ok_or(DdmError::InvalidFormat)?;
+
+    let deg_str = caps.get(1).ok_or(DdmError::InvalidFormat)?.as_str().trim();
+    let deg: f64 = parse_number(deg_str).map_err(|_| DdmError::InvalidField { field: CoordField::Deg })?;
+    let dir = caps.get(2).ok_or(DdmError::InvalidFormat)?.as_str()
         .chars()
         .next()
         .ok_or(DdmError::InvalidField { field: CoordField::Dir })?
         .to_ascii_uppercase();
 
-    let sec: f64 = 0.0;
-
-    if !deg.is_finite() || !min.is_finite() {
+    if !deg.is_finite() {
         return Err(DdmError::InvalidFormat);
     }
+    if deg.fract() != 0.0 {
+        return Err(DdmError::InvalidField { field: CoordField::Deg });
+    }
 
-    let coord = Coordinate { deg, min, sec, dir};
-    let value = coordinate_to_dd(coord, kind)?;
+    let coord = Coordinate { deg, min: 0.0, sec: 0.0, dir };
+
+    Ok(ParsedCoord::new(coord, kind)?)
+}
 
-    Ok(value)
+// True when the string has the shape of a DDM value (values not validated),
+// degrees-only values included.
+pub fn looks_like_ddm(input: &str) -> bool {
+    DDM_RE.is_match(input) || DEG_ONLY_RE.is_match(input)
 }
 
 /* ---------------- FORMATTING ---------------- */
 
-// Converts decimal degrees to a DMS string.
+// Language of the direction letters written in DMS output.
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    // N / S / E / W.
+    #[default]
+    En,
+    // N / S / E / O (ouest), as accepted on input.
+    Fr,
+}
+
+// Minute / second symbols of the DMS and DDM output (--dms-symbols).
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DmsSymbols {
+    // ' and ".
+    #[default]
+    Ascii,
+    // Primes ′ and ″, as accepted on input.
+    Unicode,
+}
+
+impl DmsSymbols {
+    // Minute and second symbols.
+    fn marks(self) -> (char, char) {
+        match self {
+            DmsSymbols::Ascii => ('\'', '"'),
+            DmsSymbols::Unicode => ('′', '″'),
+        }
+    }
+}
+
+// ISO 6709 point string in decimal degrees, e.g. `+48.858056+002.294500/`:
+// signed fixed-width degrees (2 digits for the latitude, 3 for the
+// longitude), 6 decimals, and the trailing solidus. Zero is written `+`.
 // This function does not perform validation.
-pub fn dd_to_dms(value: f64, kind: CoordinateKind) -> String {
-    let dir = if kind == CoordinateKind::Latitude {
-        if value >= 0.0 { 'N' } else { 'S' }
-    } else {
-        if value >= 0.0 { 'E' } else { 'W' }
-    };
+pub fn dd_to_iso6709(lat: f64, lon: f64) -> String {
+    format!("{:+010.6}{:+011.6}/", lat + 0.0, lon + 0.0)
+}
+
+// One WKT coordinate: `decimals` fixed decimals rounded with `rounding`
+// (--coord-precision), else the shortest plain decimal notation (never an
+// exponent), so the text stays valid for any finite coordinate. Zero is
+// written `0`.
+fn wkt_number(value: f64, decimals: Option<usize>, rounding: RoundingMode) -> String {
+    match decimals {
+        Some(decimals) => format!("{:.decimals$}", round_with(value, decimals as u32, rounding) + 0.0),
+        None => (value + 0.0).to_string(),
+    }
+}
+
+// WKT point, longitude first (OGC axis order), e.g. `POINT(2.2945 48.858056)`.
+pub fn to_wkt_point(lat: f64, lon: f64, decimals: Option<usize>, rounding: RoundingMode) -> String {
+    let n = |value| wkt_number(value, decimals, rounding);
+    format!("POINT({} {})", n(lon), n(lat))
+}
+
+// WKT two-point linestring from A to B, longitude first.
+pub fn to_wkt_linestring(
+    (lat_a, lon_a): (f64, f64),
+    (lat_b, lon_b): (f64, f64),
+    decimals: Option<usize>,
+    rounding: RoundingMode,
+) -> String {
+    let n = |value| wkt_number(value, decimals, rounding);
+    format!("LINESTRING({} {}, {} {})", n(lon_a), n(lat_a), n(lon_b), n(lat_b))
+}
 
+// Converts decimal degrees to a DMS string with `precision` decimals on
+// the seconds (max 10), rounded with `rounding`, with the direction
+// letters of `locale` and ASCII symbols.
+// Seconds are rounded first and carried into minutes / degrees, so that a
+// field never reads 60 (59.999" at 2 decimals gives 1' more, not 60.00").
+// This function does not perform validation.
+pub fn dd_to_dms(
+    value: f64,
+    kind: CoordinateKind,
+    precision: usize,
+    locale: Locale,
+    rounding: RoundingMode,
+) -> String {
+    dd_to_dms_with(value, kind, precision, locale, rounding, DmsSymbols::Ascii)
+}
+
+// Same as `dd_to_dms`, with the minute / second `symbols`
+// (`48°51′29.00″N` in Unicode).
+pub fn dd_to_dms_with(
+    value: f64,
+    kind: CoordinateKind,
+    precision: usize,
+    locale: Locale,
+    rounding: RoundingMode,
+    symbols: DmsSymbols,
+) -> String {
+    let dir = direction(value, kind, locale).map(String::from).unwrap_or_default();
+    let precision = precision.min(10);
     let abs = value.abs();
-    let deg = abs.floor();
+    let mut deg = abs.floor();
     let min_f = (abs - deg) * 60.0;
-    let min = min_f.floor();
-    let sec = (min_f - min) * 60.0;
+    let mut min = min_f.floor();
+    let mut sec = round_with((min_f - min) * 60.0, precision as u32, rounding);
+
+    // Carries.
+    if sec >= 60.0 {
+        sec = 0.0;
+        min += 1.0;
+    }
+    if min >= 60.0 {
+        min = 0.0;
+        deg += 1.0;
+    }
 
-    format!("{}°{}'{:.2}\"{}", deg as i32, min as i32, sec, dir)
+    let (min_mark, sec_mark) = symbols.marks();
+    format!("{}°{}{min_mark}{:.*}{sec_mark}{}", deg as i32, min as i32, precision, sec, dir)
+}
+
+// Converts decimal degrees to a DDM string with `precision` decimals on
+// the minutes (max 10), e.g. `48°51.4020'N`. Minutes are rounded first and
+// carried into degrees, like dd_to_dms.
+// This function does not perform validation.
+pub fn dd_to_ddm(
+    value: f64,
+    kind: CoordinateKind,
+    precision: usize,
+    locale: Locale,
+    rounding: RoundingMode,
+    symbols: DmsSymbols,
+) -> String {
+    let dir = direction(value, kind, locale).map(String::from).unwrap_or_default();
+    let precision = precision.min(10);
+    let abs = value.abs();
+    let mut deg = abs.floor();
+    let mut min = round_with((abs - deg) * 60.0, precision as u32, rounding);
+
+    if min >= 60.0 {
+        min = 0.0;
+        deg += 1.0;
+    }
+
+    format!("{}°{:.*}{}{}", deg as i32, precision, min, symbols.marks().0, dir)
+}
+
+// Direction letter of a signed coordinate, in the letters of `locale`.
+// None for an exact zero (0.0 or -0.0): the equator and the prime meridian
+// are in neither hemisphere. A value rounded to zero keeps its sign.
+fn direction(value: f64, kind: CoordinateKind, locale: Locale) -> Option<char> {
+    let west = match locale {
+        Locale::En => 'W',
+        Locale::Fr => 'O',
+    };
+    if value == 0.0 {
+        None
+    } else if kind == CoordinateKind::Latitude {
+        Some(if value > 0.0 { 'N' } else { 'S' })
+    } else {
+        Some(if value > 0.0 { 'E' } else { west })
+    }
+}
+
+/* ---------------- PROJECTION ---------------- */
+
+// Sphere radius of Web Mercator (m): the WGS84 semi-major axis.
+const WEB_MERCATOR_RADIUS_M: f64 = 6_378_137.0;
+
+// Latitude limit of Web Mercator, atan(sinh(π)): the map is then a square
+// (the poles are at infinity).
+pub const WEB_MERCATOR_MAX_LAT: f64 = 85.051_128_779_806_59;
+
+// Web Mercator (EPSG:3857) coordinates in meters: x east and y north of
+// (0°, 0°), up to ±20037508.34 m. The latitude is clamped to
+// ±WEB_MERCATOR_MAX_LAT. Inputs in decimal degrees.
+// This function does not perform validation.
+pub fn to_web_mercator(lat: f64, lon: f64) -> (f64, f64) {
+    let lat = lat.clamp(-WEB_MERCATOR_MAX_LAT, WEB_MERCATOR_MAX_LAT).to_radians();
+    let x = WEB_MERCATOR_RADIUS_M * lon.to_radians();
+    // asinh(tan φ) = ln(tan(π/4 + φ/2)), exactly 0 and odd around the equator.
+    let y = WEB_MERCATOR_RADIUS_M * lat.tan().asinh();
+    (x + 0.0, y + 0.0)
 }
@@ -0,0 +1,84 @@
+// src/geo/datum.rs
+
+/* ---------------- DATUMS ---------------- */
+
+// Geodetic datum of the input coordinates (--datum).
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Datum {
+    Wgs84,
+    Nad27,
+}
+
+impl Datum {
+    // Name written in the `datum` column.
+    pub fn label(self) -> &'static str {
+        match self {
+            Datum::Wgs84 => "WGS84",
+            Datum::Nad27 => "NAD27",
+        }
+    }
+
+    // Moves a point of this datum to WGS84.
+    pub fn to_wgs84(self, lat: f64, lon: f64) -> (f64, f64) {
+        match self {
+            Datum::Wgs84 => (lat, lon),
+            Datum::Nad27 => nad27_to_wgs84(lat, lon),
+        }
+    }
+}
+
+/* ---------------- MOLODENSKY ---------------- */
+
+// Clarke 1866 ellipsoid (NAD27).
+const CLARKE_1866_A: f64 = 6_378_206.4;
+const CLARKE_1866_F: f64 = 1.0 / 294.978_698_2;
+
+// WGS84 ellipsoid.
+const WGS84_A: f64 = 6_378_137.0;
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+// NAD27 -> WGS84 shift of the ellipsoid centre (m), mean for the
+// conterminous United States (NIMA TR8350.2).
+const NAD27_SHIFT: [f64; 3] = [-8.0, 160.0, 176.0];
+
+// Converts a NAD27 point (decimal degrees, on the ellipsoid) to WGS84 with
+// the standard Molodensky transform and the CONUS mean shift.
+// Accuracy is about 5 m inside the conterminous United States and degrades
+// elsewhere: NAD27 is distorted locally, which a 3-parameter shift cannot
+// model (NADCON grids can). Not meant for other regions.
+// This function does not perform validation.
+pub fn nad27_to_wgs84(lat: f64, lon: f64) -> (f64, f64) {
+    let [dx, dy, dz] = NAD27_SHIFT;
+    let (a, f) = (CLARKE_1866_A, CLARKE_1866_F);
+    let (da, df) = (WGS84_A - a, WGS84_F - f);
+    let b = a * (1.0 - f);
+    let e2 = 2.0 * f - f * f;
+
+    let (phi, lambda) = (lat.to_radians(), lon.to_radians());
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let (sin_lambda, cos_lambda) = lambda.sin_cos();
+
+    let w = 1.0 - e2 * sin_phi * sin_phi;
+    let rn = a / w.sqrt();                   // prime vertical radius of curvature
+    let rm = a * (1.0 - e2) / w.powf(1.5);   // meridian radius of curvature
+
+    let d_phi = (-dx * sin_phi * cos_lambda - dy * sin_phi * sin_lambda + dz * cos_phi
+        + da * rn * e2 * sin_phi * cos_phi / a
+        + df * (rm * a / b + rn * b / a) * sin_phi * cos_phi)
+        / rm;
+    // The longitude is undefined at the poles: left unchanged.
+    let d_lambda = if cos_phi.abs() < 1e-12 {
+        0.0
+    } else {
+        (-dx * sin_lambda + dy * cos_lambda) / (rn * cos_phi)
+    };
+
+    let mut lon = lon + d_lambda.to_degrees();
+    if lon > 180.0 {
+        lon -= 360.0;
+    } else if lon < -180.0 {
+        lon += 360.0;
+    }
+    (lat + d_phi.to_degrees(), lon)
+}
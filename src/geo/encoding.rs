@@ -0,0 +1,33 @@
+// src/geo/encoding.rs
+
+/* ---------------- ENCODED POLYLINE ---------------- */
+
+// Precision of the encoded polyline format: 5 decimals (about 1 m).
+const POLYLINE_FACTOR: f64 = 1e5;
+
+// Encodes (lat, lon) points in decimal degrees with the Google encoded
+// polyline algorithm: each value is the difference with the previous point,
+// in 1e-5 degrees, written as 5-bit chunks shifted into printable ASCII
+// (`?` to `~`, never a comma or a quote).
+pub fn encode_polyline(points: &[(f64, f64)]) -> String {
+    let mut out = String::new();
+    let mut previous = (0, 0);
+    for &(lat, lon) in points {
+        let current = ((lat * POLYLINE_FACTOR).round() as i64, (lon * POLYLINE_FACTOR).round() as i64);
+        encode_value(current.0 - previous.0, &mut out);
+        encode_value(current.1 - previous.1, &mut out);
+        previous = current;
+    }
+    out
+}
+
+// Appends one signed delta: zigzag sign bit, then 5-bit chunks from the
+// lowest, 0x20 marking that another chunk follows, offset by 63.
+fn encode_value(value: i64, out: &mut String) {
+    let mut v = if value < 0 { !(value << 1) } else { value << 1 } as u64;
+    while v >= 0x20 {
+        out.push(char::from((0x20 | (v & 0x1f)) as u8 + 63));
+        v >>= 5;
+    }
+    out.push(char::from(v as u8 + 63));
+}
@@ -0,0 +1,241 @@
+// src/input.rs
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::mpsc::SyncSender;
+
+use csv::{ReaderBuilder, StringRecord};
+use serde::de::{Deserializer, SeqAccess, Visitor};
+use serde_json::{Map, Value};
+
+/* ---------------- DOMAIN TYPES ---------------- */
+
+// Supported input file formats.
+#[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceFormat {
+    Csv,
+    Json,
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+}
+
+// Format-specific reading options.
+#[derive(Debug)]
+pub struct ReadOptions {
+    // CSV input as tab-separated values, without quoting.
+    pub tsv: bool,
+    // Headerless CSV input: names of the columns, in file order.
+    pub columns: Option<&'static [&'static str]>,
+    // Worksheet of an XLSX input (default: the first one).
+    #[cfg(feature = "xlsx")]
+    pub sheet: Option<String>,
+}
+
+// Errors specific to reading input rows.
+#[derive(Debug, thiserror::Error)]
+pub enum InputError {
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid header (missing or unreadable)")]
+    InvalidHeader,
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("JSON input must be an array of objects")]
+    NotAnArray,
+    #[error("record is not a JSON object")]
+    NotAnObject,
+    #[error("missing field `{0}`")]
+    MissingField(String),
+    #[error("field `{0}` must be a string or a number")]
+    InvalidValue(String),
+    #[cfg(feature = "xlsx")]
+    #[error("XLSX error: {0}")]
+    Xlsx(#[from] calamine::Error),
+    #[cfg(feature = "xlsx")]
+    #[error("no worksheet named `{0}`")]
+    UnknownSheet(String),
+}
+
+impl InputError {
+    // Field counts (expected, found) of a CSV row whose length differs
+    // from the header (or from the first row of a headerless file).
+    pub fn unequal_lengths(&self) -> Option<(u64, u64)> {
+        match self {
+            InputError::Csv(e) => match e.kind() {
+                csv::ErrorKind::UnequalLengths { expected_len, len, .. } => Some((*expected_len, *len)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Errors after which the rest of the file cannot be read (a JSON file
+    // broken or cut in the middle), as opposed to one invalid row.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, InputError::Json(_) | InputError::Io(_))
+    }
+}
+
+/* ---------------- ROW SOURCE ---------------- */
+
+type Rows = Box<dyn Iterator<Item = Result<StringRecord, InputError>>>;
+
+// Raw input rows, whatever the file format.
+// Every row is exposed as a `StringRecord` aligned on `headers`, so that the
+// coordinate parsing does not depend on the file format.
+pub struct RowSource {
+    pub headers: StringRecord,
+    // Line number reported for the row preceding the first one
+    // (the CSV header is line 1, JSON records are numbered from 1).
+    pub first_line: usize,
+    rows: Rows,
+}
+
+impl RowSource {
+    pub fn open(path: &Path, format: SourceFormat, options: &ReadOptions) -> Result<Self, InputError> {
+        match format {
+            SourceFormat::Csv => Self::open_csv(path, options.tsv, options.columns),
+            SourceFormat::Json => Self::open_json(path),
+            #[cfg(feature = "xlsx")]
+            SourceFormat::Xlsx => Self::open_xlsx(path, options.sheet.as_deref()),
+        }
+    }
+
+    // CSV file with a header line, or headerless with the given `columns`
+    // (data then starts on line 1).
+    // TSV: tab delimiter, quotes are read literally.
+    fn open_csv(path: &Path, tsv: bool, columns: Option<&[&str]>) -> Result<Self, InputError> {
+        let mut builder = ReaderBuilder::new();
+        if tsv {
+            builder.delimiter(b'\t').quoting(false);
+        }
+        let mut reader = builder
+            .has_headers(columns.is_none())
+            .from_path(path)?;
+        let (headers, first_line) = match columns {
+            Some(columns) => (StringRecord::from(columns.to_vec()), 0),
+            None => (reader.headers().map_err(|_| InputError::InvalidHeader)?.clone(), 1),
+        };
+        let rows = reader.into_records().map(|r| r.map_err(InputError::from));
+
+        Ok(RowSource { headers, first_line, rows: Box::new(rows) })
+    }
+
+    // JSON array of objects, streamed: a reader thread decodes the objects
+    // one at a time and hands them over through a bounded channel, so only a
+    // few of them are in memory. The keys of the first object act as headers.
+    fn open_json(path: &Path) -> Result<Self, InputError> {
+        let file = BufReader::new(File::open(path)?);
+        let (sender, receiver) = std::sync::mpsc::sync_channel(JSON_QUEUE);
+        std::thread::spawn(move || stream_json(file, sender));
+
+        let first = receiver.recv().ok().transpose()?;
+        let headers: StringRecord = first.iter().flat_map(Map::keys).collect();
+
+        let columns = headers.clone();
+        let rows = first.map(Ok).into_iter().chain(receiver).map(move |item| json_record(&columns, &item?));
+
+        Ok(RowSource { headers, first_line: 0, rows: Box::new(rows) })
+    }
+
+    // Excel worksheet (XLSX, XLS, ODS...): the first row acts as headers,
+    // rows are numbered like spreadsheet rows (header is row 1).
+    // The sheet is loaded at once.
+    #[cfg(feature = "xlsx")]
+    fn open_xlsx(path: &Path, sheet: Option<&str>) -> Result<Self, InputError> {
+        use calamine::Reader;
+
+        let mut workbook = calamine::open_workbook_auto(path)?;
+        let names = workbook.sheet_names();
+        let name = match sheet {
+            Some(name) if names.iter().any(|n| n == name) => name.to_string(),
+            Some(name) => return Err(InputError::UnknownSheet(name.to_string())),
+            None => names.first().cloned().ok_or(InputError::InvalidHeader)?,
+        };
+
+        let range = workbook.worksheet_range(&name)?;
+        let mut records = range.rows().map(|row| row.iter().map(|cell| cell.to_string()).collect::<StringRecord>());
+        let headers = records.next().ok_or(InputError::InvalidHeader)?;
+        let rows: Vec<_> = records.map(Ok).collect();
+
+        Ok(RowSource { headers, first_line: 1, rows: Box::new(rows.into_iter()) })
+    }
+}
+
+impl Iterator for RowSource {
+    type Item = Result<StringRecord, InputError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next()
+    }
+}
+
+/* ---------------- JSON STREAM ---------------- */
+
+// Objects decoded ahead of the rows being processed.
+const JSON_QUEUE: usize = 64;
+
+type JsonItem = Result<Map<String, Value>, InputError>;
+
+// Reader thread of a JSON input: sends every element of the top-level array,
+// then the error that stopped the decoding, if any. A document that is not
+// an array fails with `NotAnArray`.
+fn stream_json(file: BufReader<File>, sender: SyncSender<JsonItem>) {
+    let mut de = serde_json::Deserializer::from_reader(file);
+    let result = (&mut de).deserialize_any(JsonElements { sender: &sender }).and_then(|()| de.end());
+    if let Err(e) = result {
+        let error = match e.classify() {
+            serde_json::error::Category::Data => InputError::NotAnArray,
+            _ => InputError::Json(e),
+        };
+        let _ = sender.send(Err(error));
+    }
+}
+
+// Visitor of the top-level array. Elements are decoded as values, so that a
+// non-object element is one invalid row.
+struct JsonElements<'a> {
+    sender: &'a SyncSender<JsonItem>,
+}
+
+impl<'de> Visitor<'de> for JsonElements<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(element) = seq.next_element::<Value>()? {
+            let item = match element {
+                Value::Object(object) => Ok(object),
+                _ => Err(InputError::NotAnObject),
+            };
+            // The rows are no longer read (--limit, error): stop decoding.
+            if self.sender.send(item).is_err() {
+                return Err(serde::de::Error::custom("input closed"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Flattens a JSON object into a record following the header order.
+// Numbers keep their JSON text representation (e.g. `48.858056`).
+fn json_record(headers: &StringRecord, object: &Map<String, Value>) -> Result<StringRecord, InputError> {
+    headers
+        .iter()
+        .map(|h| match object.get(h) {
+            Some(Value::String(s)) => Ok(s.clone()),
+            Some(Value::Number(n)) => Ok(n.to_string()),
+            Some(Value::Null) => Ok(String::new()),
+            Some(_) => Err(InputError::InvalidValue(h.to_string())),
+            None => Err(InputError::MissingField(h.to_string())),
+        })
+        .collect()
+}
@@ -0,0 +1,240 @@
+// src/lib.rs
+
+/*
+ARCHITECTURE OVERVIEW
+
+This crate is a CSV-to-CSV geographic distance processor. The `ektaon`
+binary (src/main.rs) only calls `cli_main`; as a library, `run_with_hook`
+runs the same processing with a callback on every output record.
+
+High-level flow:
+1. Parse CLI arguments (input/output paths, coordinate format, strict mode).
+2. Validate CSV headers.
+3. Read input rows and parse coordinates according to the selected format:
+   - DD  → direct numeric values
+   - DMS → parsed and validated strings
+   - DDM → parsed and validated strings
+4. Normalize all coordinates to:
+   - decimal degrees (DD)
+   - formatted DMS strings
+5. Compute:
+   - Haversine distance (km / miles)
+   - near-equality flags (lat / lon / both)
+6. Write enriched rows to the output CSV.
+
+Key design choices:
+- Coordinate format is global (no mixed formats per file).
+- All computations use normalized decimal degrees.
+- Errors are handled per-line in permissive mode, or fail-fast in strict mode.
+- Parsing, geometry, and math logic are isolated in `geo` and `util` modules.
+
+Modules:
+- `cli`    → command line (`Cli`, subcommands, --config, logger)
+- `error`  → `AppError`
+- `parse`  → input rows and coordinates (headers, DD / DMS / DDM values)
+- `modes`  → processing of the rows (pairs, cross-group, anchor, matrix,
+             destination, track) and the end-of-run reports
+- `output` → output records and writer (CSV / JSON / GeoJSON, manifest)
+
+This module only wires them together: the entry points and the mode dispatch.
+*/
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::CommandFactory;
+use csv::StringRecord;
+
+pub mod util;
+use crate::util::KM_TO_MILES;
+use crate::util::round;
+
+pub mod geo;
+
+mod audit;
+mod fields;
+mod fixup;
+
+mod input;
+use crate::input::ReadOptions;
+use crate::input::RowSource;
+
+mod cli;
+pub use crate::cli::Cli;
+pub use crate::cli::InputFormat;
+use crate::cli::App;
+use crate::cli::Command;
+use crate::cli::init_logger;
+use crate::cli::parse_app;
+
+mod error;
+pub use crate::error::AppError;
+
+mod parse;
+use crate::parse::REQUIRED_HEADERS;
+use crate::parse::POINT_HEADERS;
+use crate::parse::DESTINATION_HEADERS;
+use crate::parse::apply_header_map;
+use crate::parse::swap_lonlat_headers;
+
+mod output;
+pub use crate::output::OutputRecord;
+pub use crate::output::RecordHook;
+use crate::output::OutputWriter;
+use crate::output::first_id;
+use crate::output::write_sorted;
+
+mod modes;
+use crate::modes::RunState;
+use crate::modes::RowPrep;
+use crate::modes::point_distance;
+use crate::modes::pair_report;
+use crate::modes::finish;
+use crate::modes::process_pairs;
+use crate::modes::process_cross_group;
+use crate::modes::process_matrix;
+use crate::modes::anchor_point;
+use crate::modes::process_anchor;
+use crate::modes::process_destination;
+use crate::modes::process_track;
+
+/* ---------------- MAIN ---------------- */
+
+// Set on Ctrl-C: the input loops stop at the next row and the run ends as
+// usual (output flushed, manifest, summary). The handler only sets this flag,
+// so it never waits on the writer.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+// Entry point of the `ektaon` binary.
+pub fn cli_main() -> Result<(), AppError> {
+
+    // Parse CLI arguments.
+    let app = parse_app(std::env::args_os());
+
+    // A second Ctrl-C does not wait for the end of the run.
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+    })?;
+
+    match (app.command, app.convert) {
+        (Some(Command::Convert(cli) | Command::Validate(cli)), _) | (None, Some(cli)) => {
+            init_logger(&cli);
+            run(&cli)
+        }
+        (Some(Command::Distance(args)), _) => {
+            let km = point_distance(&args)?;
+            println!("{} km ({} mi)", round(km, 2), round(km * KM_TO_MILES, 2));
+            Ok(())
+        }
+        (Some(Command::Pair(args)), _) => {
+            print!("{}", pair_report(&args)?);
+            Ok(())
+        }
+        (None, None) => Ok(App::command().print_help()?),
+    }
+}
+
+// Process the input file into the output file according to the CLI options.
+pub fn run(cli: &Cli) -> Result<(), AppError> {
+    run_with_hook(cli, None)
+}
+
+// Same as `run`, calling `hook` on every output record before it is written.
+pub fn run_with_hook(cli: &Cli, hook: Option<&mut RecordHook>) -> Result<(), AppError> {
+
+    // Reader / writer setup.
+    // Headerless input: the columns of the mode, in their documented order.
+    let columns = cli.no_header.then_some(if cli.destination {
+        DESTINATION_HEADERS
+    } else if cli.track || cli.anchor.is_some() {
+        POINT_HEADERS
+    } else {
+        REQUIRED_HEADERS
+    });
+    let options = ReadOptions {
+        tsv: cli.tsv,
+        columns,
+        #[cfg(feature = "xlsx")]
+        sheet: cli.sheet.clone(),
+    };
+    // Opens an input file, with its header aliases applied.
+    let open = |path: &PathBuf| -> Result<(RowSource, StringRecord), AppError> {
+        let reader = RowSource::open(path, cli.input_format_file, &options)?;
+        let mut headers = reader.headers.clone();
+        apply_header_map(&mut headers, &cli.header_map)?;
+        if cli.lonlat {
+            swap_lonlat_headers(&mut headers);
+        }
+        Ok((reader, headers))
+    };
+    let (reader, headers) = open(&cli.input[0])?;
+    let first_id = first_id(cli)?;
+    let mut writer = OutputWriter::create(cli)?;
+
+    // Only the pair mode concatenates several input files.
+    if cli.input.len() > 1 {
+        let mode = [
+            ("cross-group", cli.cross_group.is_some()),
+            ("destination", cli.destination),
+            ("track", cli.track),
+            ("anchor", cli.anchor.is_some()),
+            ("matrix", cli.matrix.is_some()),
+        ];
+        if let Some((name, _)) = mode.into_iter().find(|(_, on)| *on) {
+            return Err(AppError::SingleInputMode(name));
+        }
+    }
+
+    // Processing counters, with the optional manual corrections and
+    // statistics of the raw rows.
+    let mut state = RunState::new(cli, first_id);
+    state.prep = RowPrep::new(cli, &headers)?;
+
+    // Cross-group mode: single-point input, pairs are built from the groups.
+    if let Some(column) = &cli.cross_group {
+        process_cross_group(reader, &headers, column, &mut writer, cli, &mut state, hook)?;
+        return finish(writer, cli, &state);
+    }
+
+    // Anchor mode: single-point input, each point paired with a fixed B.
+    if let Some(anchor) = &cli.anchor {
+        let anchor = anchor_point(anchor, cli)?;
+        process_anchor(reader, &headers, &anchor, &mut writer, cli, &mut state, hook)?;
+        write_sorted(&mut writer, cli, &mut state)?;
+        return finish(writer, cli, &state);
+    }
+
+    // Matrix mode: single-point input, every pair of points.
+    if let Some(form) = cli.matrix {
+        process_matrix(reader, &headers, form, &mut writer, cli, &mut state, hook)?;
+        return finish(writer, cli, &state);
+    }
+
+    // Destination mode: start point, bearing and distance instead of pairs.
+    if cli.destination {
+        process_destination(reader, &headers, &mut writer, cli, &mut state)?;
+        return finish(writer, cli, &state);
+    }
+
+    // Track mode: single-point input, consecutive points form a path.
+    if cli.track {
+        process_track(reader, &headers, &mut writer, cli, &mut state)?;
+        return finish(writer, cli, &state);
+    }
+
+    // Pair mode: every row is a pair of points.
+    process_pairs(reader, &headers, open, &mut writer, cli, &mut state, hook)?;
+    write_sorted(&mut writer, cli, &mut state)?;
+    finish(writer, cli, &state)
+}
+
+/* ---------------- TEST ---------------- */
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1282 @@
+// src/modes.rs
+
+use std::path::PathBuf;
+use std::collections::HashSet;
+
+use csv::StringRecord;
+
+use crate::util::KM_TO_MILES;
+use crate::util::GeoTolerance;
+use crate::util::Nearly;
+use crate::util::round;
+use crate::util::round_with;
+use crate::util::haversine_with_radius;
+use crate::util::check_earth_distance;
+use crate::util::compute_nearly;
+use crate::util::is_antipodal;
+use crate::util::longitude_separation;
+use crate::util::crosses_antimeridian;
+use crate::util::nautical_tz;
+use crate::util::distance_3d;
+use crate::util::long_arc_km;
+use crate::util::initial_bearing;
+use crate::util::compass_point;
+use crate::util::arc_is_degenerate;
+use crate::util::intermediate_point;
+use crate::util::destination_point_with_radius;
+use crate::util::to_pole;
+use crate::util::PoleVector;
+use crate::util::BoundingBox;
+use crate::util::bbox_contains;
+use crate::util::grid_cell;
+use crate::audit::FormatReport;
+use crate::audit::PrecisionAudit;
+use crate::fixup::Fixups;
+use crate::input::RowSource;
+use crate::geo::CoordinateKind;
+use crate::geo::dd_to_dms_with;
+use crate::geo::dd_to_ddm;
+use crate::geo::to_wkt_linestring;
+use crate::geo::to_wkt_point;
+use crate::geo::to_web_mercator;
+use crate::geo::datum::Datum;
+use crate::geo::encoding::encode_polyline;
+use crate::geo::dms_to_dd;
+use crate::geo::dms_seconds_decimals;
+use crate::geo::parse_number;
+use crate::geo::normalize_coordinate;
+
+use crate::interrupted;
+use crate::cli::DistanceArgs;
+use crate::cli::PairArgs;
+use crate::cli::Cli;
+use crate::cli::InputFormat;
+use crate::cli::OutputFormat;
+use crate::cli::EmptyCells;
+use crate::cli::EmitFormat;
+use crate::cli::MatrixForm;
+use crate::cli::IGNORED_LINES;
+use crate::error::AppError;
+use crate::parse::REQUIRED_HEADERS;
+use crate::parse::ELEVATION_HEADERS;
+use crate::parse::KNOWN_HEADERS;
+use crate::parse::InputString;
+use crate::parse::POINT_HEADERS;
+use crate::parse::DESTINATION_HEADERS;
+use crate::parse::InputDestination;
+use crate::parse::parse_point_arg;
+use crate::parse::read_error;
+use crate::parse::parse_point;
+use crate::parse::check_extra_headers;
+use crate::parse::parse_elevations;
+use crate::parse::parse_row_radius;
+use crate::parse::COORD_FIELDS;
+use crate::parse::parse_coordinates;
+use crate::parse::ParseCache;
+use crate::parse::parse_coordinate;
+use crate::parse::ParsedPoint;
+use crate::output::OutputRecord;
+use crate::output::WaypointRecord;
+use crate::output::MatrixRow;
+use crate::output::TrackRecord;
+use crate::output::DestinationRecord;
+use crate::output::RecordHook;
+use crate::output::OutputWriter;
+use crate::output::localize_number;
+use crate::output::Passthrough;
+use crate::output::write_manifest;
+use crate::output::DDM_OUTPUT_DECIMALS;
+use crate::output::format_computed;
+use crate::output::output_record;
+use crate::output::write_output;
+
+/* ---------------- NORMALIZED ---------------- */
+
+// Normalized coordinate representation.
+#[derive(Debug, Clone)]
+pub(crate) struct NormalizedCoord {
+    pub(crate) input: String,  // original input string
+    pub(crate) dd: f64,        // decimal degrees
+    pub(crate) dms: String,    // formatted DMS output
+}
+
+// Normalized geographic point.
+#[derive(Debug, Clone)]
+pub(crate) struct NormalizedPoint {
+    pub(crate) name: String,
+    pub(crate) lat: NormalizedCoord,
+    pub(crate) lon: NormalizedCoord,
+}
+
+// Normalized geographic point.
+#[derive(Debug, Clone)]
+pub(crate) struct NormalizedGeo {
+    line: usize,            // input line number
+    pub(crate) a: NormalizedPoint,
+    pub(crate) b: NormalizedPoint,
+    elev_a: Option<f64>,    // elevation of A (meters)
+    elev_b: Option<f64>,    // elevation of B (meters)
+    radius_km: Option<f64>, // sphere radius of this row (km), else --earth-radius
+    suspect: bool,          // out-of-range value found by the sanity check
+    seconds_rounded: bool,  // DMS input seconds finer than the output precision
+    repaired: bool,         // swapped degree / minute marks fixed (--repair)
+    passthrough: Vec<String>, // extra input column values (--passthrough)
+    pub(crate) source_file: Option<String>, // input file of the row, with several --input
+}
+
+impl NormalizedGeo {
+    // Swaps the roles of A and B (--reverse), with their elevations.
+    fn reverse(&mut self) {
+        std::mem::swap(&mut self.a, &mut self.b);
+        std::mem::swap(&mut self.elev_a, &mut self.elev_b);
+    }
+}
+
+// Distance and comparison metrics.
+#[derive(Debug)]
+pub(crate) struct DistanceMetrics {
+    pub(crate) km: f64,
+    pub(crate) miles: f64,
+    pub(crate) km_3d: Option<Option<f64>>,             // None without elevation columns
+    pub(crate) km_long: Option<f64>,
+    pub(crate) nearly: Nearly,
+    pub(crate) deltas: Option<(f64, f64)>,             // |lat_a - lat_b|, |lon_a - lon_b|
+    pub(crate) colocated: Option<bool>,
+    pub(crate) antipodal: Option<bool>,
+    pub(crate) lon_sep_deg: Option<f64>,
+    pub(crate) crosses_antimeridian: Option<bool>,
+    pub(crate) bearing: Option<(f64, &'static str)>,   // degrees, compass point
+    pub(crate) dms: bool,                              // write the DMS columns
+    pub(crate) ddm: Option<[String; 4]>,               // DDM lat / lon of A, then of B
+    pub(crate) iso6709: bool,                          // write the ISO 6709 columns
+    pub(crate) wkt: Option<[String; 3]>,               // WKT of A, B and the A-B segment
+    pub(crate) polyline: Option<String>,               // encoded polyline of the A-B arc
+    pub(crate) mercator: Option<([f64; 2], [f64; 2])>, // Web Mercator x / y of A and B
+    pub(crate) cells: Option<([i64; 2], [i64; 2])>,    // grid cells of A and B
+    pub(crate) tz: Option<(i32, i32)>,                 // nautical time zones of A and B
+    pub(crate) suspect: Option<bool>,
+    pub(crate) in_expected_bbox: Option<bool>,
+    pub(crate) seconds_rounded: Option<bool>,
+    pub(crate) roundtrip_ok: Option<bool>,
+    pub(crate) repaired: Option<bool>,
+    pub(crate) to_pole_a: Option<PoleVector>,
+    pub(crate) to_pole_b: Option<PoleVector>,
+    pub(crate) datum: Option<Datum>,                   // datum of the written coordinates
+    pub(crate) format_used: Option<InputFormat>,       // parser of the row coordinates
+}
+
+// Canonical key of an unordered pair of points.
+// Built from the 6-decimal rounded DD values (in micro-degrees) so that
+// floating noise does not defeat deduplication.
+type PairKey = [(i64, i64); 2];
+
+// Mutable state shared by the processing loop.
+#[derive(Debug)]
+pub(crate) struct RunState {
+    pub(crate) prep: RowPrep,        // fixups and audits of the raw rows
+    pub(crate) read: u64,            // data rows read
+    first_id: u64,        // id of the first written row (--start-id, --append)
+    id: u64,              // id of the next written row
+    pub(crate) invalid: u64,         // ignored lines
+    pub(crate) duplicates: u64,      // pairs skipped by --skip-duplicate-pairs
+    seconds_rounded: u64, // written rows with rounded DMS seconds
+    repaired: u64,        // written rows with swapped marks fixed (--repair)
+    empty: u64,           // rows skipped by --empty skip
+    ragged: u64,          // invalid lines with a wrong number of fields
+    pub(crate) passthrough: Passthrough,
+    seen_pairs: HashSet<PairKey>,
+    has_elevation: bool,  // input provides elev_a / elev_b
+    bbox: Option<BoundingBox>, // extent of the written points (--bbox)
+    track_km: Option<f64>,     // total length of the --track
+    pub(crate) sorted: Vec<SortedRow>,    // rows held until the end of the input (--sort-by)
+    parse_cache: ParseCache,   // DMS / DDM values already parsed
+}
+
+// Preparation of the raw input rows, in every mode: manual corrections
+// (--fixups), then the precision audit and format report of the patched
+// values.
+#[derive(Debug, Default)]
+pub(crate) struct RowPrep {
+    fixups: Fixups,
+    pub(crate) audit: Option<PrecisionAudit>,
+    pub(crate) format_report: Option<FormatReport>,
+}
+
+impl RowPrep {
+    pub(crate) fn new(cli: &Cli, headers: &StringRecord) -> Result<Self, AppError> {
+        Ok(RowPrep {
+            fixups: match &cli.fixups {
+                Some(path) => Fixups::load(path)?,
+                None => Fixups::default(),
+            },
+            audit: cli.precision_audit.then(|| PrecisionAudit::new(cli.input_format)),
+            format_report: cli.format_report.then(|| FormatReport::new(headers)),
+        })
+    }
+
+    // Prepares one raw record before deserialization.
+    pub(crate) fn apply(&mut self, line: usize, headers: &StringRecord, record: &mut StringRecord) {
+        self.fixups.apply(line, headers, record);
+        if let Some(audit) = self.audit.as_mut() {
+            audit.record_row(headers, record);
+        }
+        if let Some(report) = self.format_report.as_mut() {
+            report.record_row(headers, record);
+        }
+    }
+}
+
+// An output row held for --sort-by, with its input line and extra columns.
+#[derive(Debug)]
+pub(crate) struct SortedRow {
+    pub(crate) line: usize,
+    pub(crate) record: OutputRecord,
+    pub(crate) passthrough: Vec<String>,
+}
+
+impl RunState {
+    pub(crate) fn new(cli: &Cli, first_id: u64) -> Self {
+        RunState {
+            prep: RowPrep::default(),
+            read: 0,
+            first_id,
+            id: first_id,
+            invalid: 0,
+            duplicates: 0,
+            seconds_rounded: 0,
+            repaired: 0,
+            empty: 0,
+            ragged: 0,
+            passthrough: Passthrough::default(),
+            seen_pairs: HashSet::new(),
+            has_elevation: false,
+            bbox: cli.bbox.then(BoundingBox::default),
+            track_km: cli.track.then_some(0.0),
+            sorted: Vec::new(),
+            parse_cache: ParseCache::default(),
+        }
+    }
+
+    // Number of rows written so far.
+    pub(crate) fn written(&self) -> u64 {
+        self.id - self.first_id
+    }
+
+    // Extends the --bbox extent with a point.
+    fn add_to_bbox(&mut self, lat: f64, lon: f64) {
+        if let Some(bbox) = self.bbox.as_mut() {
+            bbox.add(lat, lon);
+        }
+    }
+
+    // --max-memory: fails once the buffered entries (seen pairs, bbox
+    // longitudes, sorted rows, plus `pending` entries held by the caller)
+    // exceed the limit.
+    fn check_memory(&self, cli: &Cli, line: usize, pending: usize) -> Result<(), AppError> {
+        let Some(limit) = cli.max_memory else {
+            return Ok(());
+        };
+        let held = self.seen_pairs.len() + self.bbox.as_ref().map_or(0, BoundingBox::len) + self.sorted.len() + pending;
+        if held > limit {
+            return Err(AppError::MemoryLimit { line, limit });
+        }
+        Ok(())
+    }
+
+    // Handles an invalid line: fatal with --strict, otherwise counted and
+    // skipped (its reason printed with --verbose), until --max-errors.
+    pub(crate) fn reject(&mut self, cli: &Cli, line: usize, error: AppError) -> Result<(), AppError> {
+        // --empty skip: an expected gap, neither invalid nor fatal.
+        if cli.empty == EmptyCells::Skip && matches!(error, AppError::EmptyCoordinate { .. }) {
+            self.empty += 1;
+            return Ok(());
+        }
+        if cli.strict {
+            return Err(error);
+        }
+        self.invalid += 1;
+        if let AppError::RaggedRow { .. } = error {
+            self.ragged += 1;
+        }
+        match error.line() {
+            Some(_) => log::warn!(target: IGNORED_LINES, "Ignored: {error}"),
+            None => log::warn!(target: IGNORED_LINES, "Ignored line {line}: {error}"),
+        }
+        if let Some(limit) = cli.max_errors
+            && self.invalid > limit
+        {
+            return Err(AppError::TooManyErrors { invalid: self.invalid, read: self.read, limit });
+        }
+        Ok(())
+    }
+}
+
+/* ---------------- SUBCOMMANDS ---------------- */
+
+// Great-circle distance (km) of the `distance` subcommand.
+pub(crate) fn point_distance(args: &DistanceArgs) -> Result<f64, AppError> {
+    let (lat_a, lon_a) = parse_point_arg(&args.from, "--from", args.input_format)?;
+    let (lat_b, lon_b) = parse_point_arg(&args.to, "--to", args.input_format)?;
+
+    Ok(haversine_with_radius(lat_a, lon_a, lat_b, lon_b, args.earth_radius)?)
+}
+
+// Output of the `pair` subcommand, one `name: value` line per result.
+pub(crate) fn pair_report(args: &PairArgs) -> Result<String, AppError> {
+    let (lat_a, lon_a) = parse_point_arg(&args.a, "--a", args.input_format)?;
+    let (lat_b, lon_b) = parse_point_arg(&args.b, "--b", args.input_format)?;
+
+    let km = haversine_with_radius(lat_a, lon_a, lat_b, lon_b, args.earth_radius)?;
+    let bearing = initial_bearing(lat_a, lon_a, lat_b, lon_b);
+    Ok(format!(
+        "distance_km: {}\ndistance_miles: {}\nbearing_deg: {} ({})\n",
+        round(km, 2),
+        round(km * KM_TO_MILES, 2),
+        round(bearing, 2),
+        compass_point(bearing),
+    ))
+}
+
+/* ---------------- END OF RUN ---------------- */
+
+// End of run: flush the output, write the manifest and print the summaries.
+pub(crate) fn finish(
+    mut writer: OutputWriter,
+    cli: &Cli,
+    state: &RunState,
+) -> Result<(), AppError> {
+    writer.flush()?;
+
+    if let Some(path) = &cli.manifest {
+        write_manifest(path, cli, state)?;
+    }
+
+    log_reports(cli, state);
+    log_summary(cli, state);
+
+    if interrupted() {
+        return Err(AppError::Interrupted(state.written()));
+    }
+
+    // Nonzero exit code for data checks (--validate).
+    if cli.validate && state.invalid > 0 {
+        return Err(AppError::ValidationFailed(state.invalid));
+    }
+
+    Ok(())
+}
+
+// Reports asked for on the command line (--precision-audit, --format-report,
+// --bbox, --track), logged at the info level like the summary.
+fn log_reports(cli: &Cli, state: &RunState) {
+    if let Some(audit) = &state.prep.audit {
+        log::info!("{}", audit.report().trim_end());
+    }
+    if let Some(report) = &state.prep.format_report {
+        log::info!("{}", report.report().trim_end());
+    }
+    if let Some([min_lat, min_lon, max_lat, max_lon]) = state.bbox.as_ref().and_then(|b| b.bounds(cli.wrap_longitude)) {
+        log::info!("bbox: {min_lat},{min_lon},{max_lat},{max_lon}");
+    }
+    if let Some(km) = state.track_km {
+        log::info!("track length: {} km", round_with(km, cli.km_precision.into(), cli.rounding));
+    }
+}
+
+// End-of-run summary, logged at the info level (warnings at the warn one).
+fn log_summary(cli: &Cli, state: &RunState) {
+    if cli.validate {
+        log::info!("{} valid row(s), {} invalid row(s)", state.written(), state.invalid);
+    } else if state.invalid > 0 {
+        log::info!("{} ignored line(s)", state.invalid);
+    }
+    // Structural problems apart from invalid values.
+    if state.ragged > 0 {
+        log::info!(
+            "  {} with a wrong number of fields, {} with invalid values",
+            state.ragged,
+            state.invalid - state.ragged,
+        );
+    }
+    if state.duplicates > 0 {
+        log::info!("{} duplicate pair(s) skipped", state.duplicates);
+    }
+    if state.empty > 0 {
+        log::info!("{} row(s) with an empty coordinate skipped", state.empty);
+    }
+    if state.seconds_rounded > 0 {
+        log::warn!(
+            "Warning: {} row(s) with DMS seconds rounded to {} decimal(s)",
+            state.seconds_rounded, cli.dms_precision,
+        );
+    }
+    if state.repaired > 0 {
+        log::info!("{} row(s) with swapped degree / minute marks repaired", state.repaired);
+    }
+}
+
+/* ---------------- MODES ---------------- */
+
+// Pair mode: the input files in order, into one output, every row a pair of
+// points (A and B). `open` opens the files after the first one, which must
+// have the header of the first one.
+pub(crate) fn process_pairs(
+    reader: RowSource,
+    headers: &StringRecord,
+    open: impl Fn(&PathBuf) -> Result<(RowSource, StringRecord), AppError>,
+    writer: &mut OutputWriter,
+    cli: &Cli,
+    state: &mut RunState,
+    mut hook: Option<&mut RecordHook>,
+) -> Result<(), AppError> {
+    let header_set: HashSet<_> = headers.iter().collect();
+    for &h in REQUIRED_HEADERS {
+        if !header_set.contains(h) {
+            return Err(AppError::MissingHeaderField(h.to_string()));
+        }
+    }
+    if cli.strict_headers {
+        check_extra_headers(headers, KNOWN_HEADERS, cli)?;
+    }
+
+    state.has_elevation = ELEVATION_HEADERS.iter().all(|h| header_set.contains(h));
+    if cli.passthrough {
+        state.passthrough = Passthrough::new(headers, KNOWN_HEADERS);
+    }
+
+    // Input files in order, into one output: every file must have the header
+    // of the first one. Line numbers, --skip and --limit are per file.
+    let limit = read_limit(cli);
+    let mut first = Some(reader);
+    for path in &cli.input {
+        let reader = match first.take() {
+            Some(reader) => reader,
+            None => match open(path)? {
+                (reader, h) if h == *headers => reader,
+                _ => return Err(AppError::InputHeaderMismatch(path.clone())),
+            },
+        };
+        let source_file = (cli.input.len() > 1).then(|| path.display().to_string());
+        let mut line_no = reader.first_line + cli.skip;
+
+        for row in reader.skip(cli.skip).take(limit).take_while(|_| !interrupted()) {
+            line_no += 1;
+            state.read += 1;
+            let row = row.and_then(|mut record| {
+                state.prep.apply(line_no, headers, &mut record);
+                let r = record.deserialize::<InputString>(Some(headers))?;
+                Ok((r, record))
+            });
+            let (r, record) = match row {
+                Ok(v) => v,
+                Err(e) if e.is_fatal() => return Err(e.into()),
+                Err(e) => {
+                    // Apart from a ragged row, a value that does not deserialize.
+                    let error = match read_error(e, line_no) {
+                        ragged @ AppError::RaggedRow { .. } => ragged,
+                        _ => AppError::MixedCoordinateFormat {
+                            line: line_no,
+                            expected: cli.input_format.label(),
+                        },
+                    };
+                    state.reject(cli, line_no, error)?;
+                    continue;
+                }
+            };
+
+            // Parse coordinates according to the input format.
+            let ([lat_a_dd, lon_a_dd, lat_b_dd, lon_b_dd], repaired) =
+                match parse_coordinates(&r, cli.input_format, cli.empty, cli.repair, cli.sanity_check, &mut state.parse_cache, line_no) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        state.reject(cli, line_no, e)?;
+                        continue;
+                    }
+                };
+
+            // With --sanity-check, DD values out of bounds are kept: flagged, or
+            // an error in strict mode.
+            let out_of_range = if cli.sanity_check {
+                dd_out_of_range(&[lat_a_dd, lon_a_dd, lat_b_dd, lon_b_dd])
+            } else {
+                None
+            };
+            if let Some((field, value)) = out_of_range
+                && cli.strict
+            {
+                return Err(AppError::OutOfRange { line: line_no, field, value });
+            }
+            let (lat_a_dd, lon_a_dd) = shift_datum(cli, lat_a_dd, lon_a_dd);
+            let (lat_b_dd, lon_b_dd) = shift_datum(cli, lat_b_dd, lon_b_dd);
+            let numbers = parse_elevations(&r, line_no)
+                .and_then(|elevations| Ok((elevations, parse_row_radius(&r, line_no)?)));
+            let (elevations, radius_km) = match numbers {
+                Ok(v) => v,
+                Err(e) => {
+                    state.reject(cli, line_no, e)?;
+                    continue;
+                }
+            };
+
+            // Both points of the row, with their raw input strings.
+            let a = ParsedPoint { line: line_no, name: r.name_a, lat_in: r.lat_a, lon_in: r.lon_a, lat: lat_a_dd, lon: lon_a_dd };
+            let b = ParsedPoint { line: line_no, name: r.name_b, lat_in: r.lat_b, lon_in: r.lon_b, lat: lat_b_dd, lon: lon_b_dd };
+            let mut geo = build_normalized_geo(&a, &b, cli);
+            (geo.elev_a, geo.elev_b) = elevations;
+            geo.radius_km = radius_km;
+            geo.suspect = out_of_range.is_some();
+            geo.passthrough = state.passthrough.values(&record);
+            geo.source_file = source_file.clone();
+            geo.repaired = repaired;
+            if cli.reverse {
+                geo.reverse();
+            }
+
+            process_geo(writer, &geo, cli, state, hook.as_deref_mut())?;
+        }
+    }
+
+    Ok(())
+}
+
+// Builds every pair made of one point of each group (bipartite set) from a
+// single-point input: |A| x |B| pairs, A being the first group seen.
+// Pairs within a group are not computed.
+pub(crate) fn process_cross_group(
+    reader: RowSource,
+    headers: &StringRecord,
+    column: &str,
+    writer: &mut OutputWriter,
+    cli: &Cli,
+    state: &mut RunState,
+    mut hook: Option<&mut RecordHook>,
+) -> Result<(), AppError> {
+    let group_index = headers.iter().position(|h| h == column).unwrap_or_default();
+
+    // Points of each group, in input order.
+    let mut groups: Vec<(String, Vec<ParsedPoint>)> = Vec::new();
+    read_points(reader, headers, &[column], cli, state, |point, record, state| {
+        let line = point.line;
+        let group = record.get(group_index).unwrap_or_default().trim().to_string();
+        match groups.iter().position(|(g, _)| *g == group) {
+            Some(i) => groups[i].1.push(point),
+            None if groups.len() < 2 => groups.push((group, vec![point])),
+            None => {
+                let error = AppError::TooManyGroups { line, column: column.to_string(), value: group };
+                state.reject(cli, line, error)?;
+            }
+        }
+        state.check_memory(cli, line, groups.iter().map(|(_, points)| points.len()).sum())
+    })?;
+
+    let [(_, side_a), (_, side_b)] = groups.as_slice() else {
+        return Ok(());
+    };
+    for a in side_a {
+        for b in side_b {
+            if interrupted() {
+                return Ok(());
+            }
+            let mut geo = build_normalized_geo(a, b, cli);
+            if cli.reverse {
+                geo.reverse();
+            }
+            process_geo(writer, &geo, cli, state, hook.as_deref_mut())?;
+        }
+    }
+
+    Ok(())
+}
+
+// Builds every pair of points of a single-point input (--matrix): N(N-1)
+// ordered pairs, N(N-1)/2 with --upper-triangular. A point is not paired
+// with itself. All the points are held in memory.
+pub(crate) fn process_matrix(
+    reader: RowSource,
+    headers: &StringRecord,
+    form: MatrixForm,
+    writer: &mut OutputWriter,
+    cli: &Cli,
+    state: &mut RunState,
+    mut hook: Option<&mut RecordHook>,
+) -> Result<(), AppError> {
+    if form == MatrixForm::Square {
+        if let OutputFormat::Ndjson = cli.output_format {
+            return Err(AppError::SquareMatrix("--output-format ndjson"));
+        }
+        if !cli.columns.is_empty() {
+            return Err(AppError::SquareMatrix("--columns"));
+        }
+    }
+
+    let mut points: Vec<ParsedPoint> = Vec::new();
+    read_points(reader, headers, &[], cli, state, |point, _, state| {
+        let line = point.line;
+        points.push(point);
+        state.check_memory(cli, line, points.len())
+    })?;
+
+    if form == MatrixForm::Square {
+        return write_square_matrix(&points, writer, cli, state);
+    }
+    for (i, a) in points.iter().enumerate() {
+        for (j, b) in points.iter().enumerate() {
+            if i == j || (cli.upper_triangular && j < i) {
+                continue;
+            }
+            if interrupted() {
+                return Ok(());
+            }
+            let mut geo = build_normalized_geo(a, b, cli);
+            if cli.reverse {
+                geo.reverse();
+            }
+            process_geo(writer, &geo, cli, state, hook.as_deref_mut())?;
+        }
+    }
+
+    Ok(())
+}
+
+// --matrix square: one row per point, its name then one distance (km,
+// rounded as distance_km) column per point, named after it. The matrix is
+// symmetric: each pair is computed once. With --upper-triangular, the cells
+// below the diagonal are left empty.
+fn write_square_matrix(
+    points: &[ParsedPoint],
+    writer: &mut OutputWriter,
+    cli: &Cli,
+    state: &mut RunState,
+) -> Result<(), AppError> {
+    let n = points.len();
+    let dd = |value: f64| round_with(value, 6, cli.rounding);
+    let mut km = vec![0.0; n * n];
+    for (i, a) in points.iter().enumerate() {
+        for (j, b) in points.iter().enumerate().skip(i + 1) {
+            let d = haversine_with_radius(dd(a.lat), dd(a.lon), dd(b.lat), dd(b.lon), cli.earth_radius)?;
+            km[i * n + j] = round_with(d, cli.km_precision.into(), cli.rounding);
+            km[j * n + i] = km[i * n + j];
+        }
+    }
+
+    // The distance columns go through the extra columns of the writer.
+    let mut columns = Passthrough {
+        columns: points.iter().enumerate().map(|(i, p)| (i, p.name.clone())).collect(),
+        resolved: false,
+    };
+    for (i, point) in points.iter().enumerate() {
+        if interrupted() {
+            return Ok(());
+        }
+        let cells: Vec<String> = (0..n)
+            .map(|j| {
+                if cli.upper_triangular && j < i {
+                    String::new()
+                } else {
+                    localize_number(&km[i * n + j].to_string(), cli.number_locale)
+                }
+            })
+            .collect();
+        if !cli.validate {
+            writer.write_record_with(&MatrixRow { name: point.name.clone() }, &mut columns, &cells)?;
+        }
+        state.id += 1;
+    }
+    Ok(())
+}
+
+// The --anchor point, as the B side of every pair (`anchor` as its name).
+pub(crate) fn anchor_point(value: &str, cli: &Cli) -> Result<ParsedPoint, AppError> {
+    let (lat, lon) = parse_point_arg(value, "--anchor", cli.input_format)?;
+    let (lat, lon) = shift_datum(cli, lat, lon);
+    let (lat_in, lon_in) = value.split_once(',').unwrap_or_default();
+    Ok(ParsedPoint {
+        line: 0,
+        name: "anchor".to_string(),
+        lat_in: lat_in.trim().to_string(),
+        lon_in: lon_in.trim().to_string(),
+        lat,
+        lon,
+    })
+}
+
+// Pairs every point of a single-point input (A) with the --anchor point (B).
+pub(crate) fn process_anchor(
+    reader: RowSource,
+    headers: &StringRecord,
+    anchor: &ParsedPoint,
+    writer: &mut OutputWriter,
+    cli: &Cli,
+    state: &mut RunState,
+    mut hook: Option<&mut RecordHook>,
+) -> Result<(), AppError> {
+    if cli.passthrough {
+        state.passthrough = Passthrough::new(headers, &[POINT_HEADERS]);
+    }
+
+    read_points(reader, headers, &[], cli, state, |a, record, state| {
+        let mut geo = build_normalized_geo(&a, anchor, cli);
+        geo.passthrough = state.passthrough.values(record);
+        if cli.reverse {
+            geo.reverse();
+        }
+        process_geo(writer, &geo, cli, state, hook.as_deref_mut())
+    })
+}
+
+// Writes the destination point of every start point / bearing / distance row
+// (forward problem, the inverse of the pairwise distance).
+pub(crate) fn process_destination(
+    reader: RowSource,
+    headers: &StringRecord,
+    writer: &mut OutputWriter,
+    cli: &Cli,
+    state: &mut RunState,
+) -> Result<(), AppError> {
+    read_rows(reader, headers, DESTINATION_HEADERS, &["name_a"], cli, state, |line, row, state| {
+        state.check_memory(cli, line, 0)?;
+        let record = match destination_record(&row, headers, cli, state.id, line) {
+            Ok(v) => v,
+            Err(e) => return state.reject(cli, line, e),
+        };
+
+        if !cli.validate
+            && let Err(e) = writer.write_record(&record)
+        {
+            return state.reject(cli, line, e);
+        }
+        state.add_to_bbox(record.lat_a_dd, record.lon_a_dd);
+        state.add_to_bbox(record.lat_b_dd, record.lon_b_dd);
+        state.id += 1;
+        Ok(())
+    })
+}
+
+// Writes every point of an ordered track with the length of the segment from
+// the previous valid point and the running total. Invalid rows are skipped:
+// the next segment starts from the last valid point.
+pub(crate) fn process_track(
+    reader: RowSource,
+    headers: &StringRecord,
+    writer: &mut OutputWriter,
+    cli: &Cli,
+    state: &mut RunState,
+) -> Result<(), AppError> {
+    let round = |value: f64, decimals: u32| round_with(value, decimals, cli.rounding);
+    let km_decimals = u32::from(cli.km_precision);
+    let mut previous: Option<(f64, f64)> = None;
+    let mut cumulative_km = 0.0;
+
+    read_points(reader, headers, &[], cli, state, |point, _, state| {
+        let line = point.line;
+        state.check_memory(cli, line, 0)?;
+        let (lat, lon) = (round(point.lat, 6), round(point.lon, 6));
+
+        let segment_km = match previous {
+            Some((prev_lat, prev_lon)) => haversine_with_radius(prev_lat, prev_lon, lat, lon, cli.earth_radius)?,
+            None => 0.0,
+        };
+        let record = TrackRecord {
+            id: state.id,
+            name: point.name,
+            lat_in: point.lat_in,
+            lon_in: point.lon_in,
+            lat_dd: lat,
+            lon_dd: lon,
+            segment_km: round(segment_km, km_decimals),
+            cumulative_km: round(cumulative_km + segment_km, km_decimals),
+        };
+
+        if !cli.validate
+            && let Err(e) = writer.write_record(&record)
+        {
+            return state.reject(cli, line, e);
+        }
+        previous = Some((lat, lon));
+        cumulative_km += segment_km;
+        state.track_km = Some(cumulative_km);
+        state.add_to_bbox(lat, lon);
+        state.id += 1;
+        Ok(())
+    })
+}
+
+/* ---------------- ROWS ---------------- */
+
+// Rows of a single-input mode (--cross-group, --anchor, --matrix,
+// --destination, --track). The header must have every `required` column,
+// and nothing else than them and the `optional` ones with --strict-headers.
+// Rows are read after --skip, up to --limit, until Ctrl-C: an unreadable one
+// is rejected, `each` gets the others (fixups applied, audited) with their
+// line number.
+fn read_rows(
+    reader: RowSource,
+    headers: &StringRecord,
+    required: &[&str],
+    optional: &[&str],
+    cli: &Cli,
+    state: &mut RunState,
+    mut each: impl FnMut(usize, StringRecord, &mut RunState) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    for &h in required {
+        if !headers.iter().any(|c| c == h) {
+            return Err(AppError::MissingHeaderField(h.to_string()));
+        }
+    }
+    if cli.strict_headers {
+        check_extra_headers(headers, &[required, optional], cli)?;
+    }
+
+    let first_line = reader.first_line + cli.skip + 1;
+    let rows = reader.skip(cli.skip).take(read_limit(cli)).take_while(|_| !interrupted());
+    for (line_no, row) in (first_line..).zip(rows) {
+        state.read += 1;
+        match row {
+            Ok(mut record) => {
+                state.prep.apply(line_no, headers, &mut record);
+                each(line_no, record, state)?;
+            }
+            Err(e) if e.is_fatal() => return Err(e.into()),
+            Err(e) => state.reject(cli, line_no, read_error(e, line_no))?,
+        }
+    }
+    Ok(())
+}
+
+// Points of a single-point input (name,lat,lon, and the `extra` columns).
+// An invalid row is rejected, `each` gets the others (datum shifted) with
+// their raw record.
+fn read_points(
+    reader: RowSource,
+    headers: &StringRecord,
+    extra: &[&str],
+    cli: &Cli,
+    state: &mut RunState,
+    mut each: impl FnMut(ParsedPoint, &StringRecord, &mut RunState) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    let required: Vec<&str> = POINT_HEADERS.iter().chain(extra).copied().collect();
+    read_rows(reader, headers, &required, &[], cli, state, |line, record, state| {
+        match parse_point(&record, headers, cli.input_format, cli.empty, line) {
+            Ok(mut point) => {
+                (point.lat, point.lon) = shift_datum(cli, point.lat, point.lon);
+                each(point, &record, state)
+            }
+            Err(e) => state.reject(cli, line, e),
+        }
+    })
+}
+
+// Parses one row of the --destination mode and computes its end point.
+fn destination_record(
+    record: &StringRecord,
+    headers: &StringRecord,
+    cli: &Cli,
+    id: u64,
+    line: usize,
+) -> Result<DestinationRecord, AppError> {
+    let format = cli.input_format;
+    let d: InputDestination = record.deserialize(Some(headers))
+        .map_err(|_| AppError::MixedCoordinateFormat { line, expected: format.label() })?;
+
+    let round = |value: f64, decimals: u32| round_with(value, decimals, cli.rounding);
+    let lat_a = round(parse_coordinate(&d.lat_a, "lat_a", CoordinateKind::Latitude, format, cli.empty, line)?, 6);
+    let lon_a = round(parse_coordinate(&d.lon_a, "lon_a", CoordinateKind::Longitude, format, cli.empty, line)?, 6);
+    // Bearing: any finite angle. Distance: finite and non-negative.
+    let number = |value: &str, field: &'static str, min: f64| {
+        parse_number(value.trim())
+            .ok()
+            .filter(|v| v.is_finite() && *v >= min)
+            .ok_or_else(|| AppError::InvalidDestination { line, field, value: value.to_string() })
+    };
+    let bearing = number(&d.bearing, "bearing", f64::NEG_INFINITY)?;
+    let distance_km = number(&d.distance_km, "distance_km", 0.0)?;
+
+    let (lat_b, lon_b) = destination_point_with_radius(lat_a, lon_a, bearing, distance_km, cli.earth_radius);
+    let (lat_b, lon_b) = (round(lat_b, 6), round(lon_b, 6));
+    let dms = |value: f64, kind| {
+        dd_to_dms_with(value, kind, cli.dms_precision.into(), cli.locale, cli.rounding, cli.dms_symbols)
+    };
+
+    Ok(DestinationRecord {
+        id,
+        name_a: d.name_a,
+        lat_a_in: d.lat_a,
+        lon_a_in: d.lon_a,
+        lat_a_dd: lat_a,
+        lon_a_dd: lon_a,
+        bearing_deg: bearing,
+        distance_km,
+        lat_b_dd: lat_b,
+        lon_b_dd: lon_b,
+        lat_b_dms: dms(lat_b, CoordinateKind::Latitude),
+        lon_b_dms: dms(lon_b, CoordinateKind::Longitude),
+        lat_b: format_computed(lat_b, CoordinateKind::Latitude, cli),
+        lon_b: format_computed(lon_b, CoordinateKind::Longitude, cli),
+    })
+}
+
+/* ---------------- PAIRS ---------------- */
+
+// True when a DMS input value has more seconds decimals than the DMS output
+// precision, i.e. the output columns lose part of the input.
+fn seconds_rounded(geo: &NormalizedGeo, cli: &Cli) -> bool {
+    let inputs = [&geo.a.lat.input, &geo.a.lon.input, &geo.b.lat.input, &geo.b.lon.input];
+    matches!(cli.input_format, InputFormat::Dms)
+        && inputs.iter().any(|v| dms_seconds_decimals(v).is_some_and(|d| d > cli.dms_precision.into()))
+}
+
+// Tolerance of the nearly_* flags: 0 with --exact-match, else --tolerance-lat
+// / --tolerance-lon, then --tolerance, then the 1e-6° default.
+fn nearly_tolerance(cli: &Cli) -> GeoTolerance {
+    if cli.exact_match {
+        return GeoTolerance::EXACT;
+    }
+    let both = cli.tolerance.map_or(GeoTolerance::DEFAULT, GeoTolerance::uniform);
+    GeoTolerance {
+        lat: cli.tolerance_lat.unwrap_or(both.lat),
+        lon: cli.tolerance_lon.unwrap_or(both.lon),
+    }
+}
+
+// True when every DD value reads back from its DMS output column (dd_to_dms,
+// then dms_to_dd) within the tolerance. The seconds rounding of
+// --dms-precision alone can exceed 1e-6° (0.005" is about 1.4e-6°).
+fn roundtrip_ok(geo: &NormalizedGeo, tolerance: GeoTolerance) -> bool {
+    let coords = [
+        (&geo.a.lat, CoordinateKind::Latitude),
+        (&geo.a.lon, CoordinateKind::Longitude),
+        (&geo.b.lat, CoordinateKind::Latitude),
+        (&geo.b.lon, CoordinateKind::Longitude),
+    ];
+    coords.iter().all(|(coord, kind)| {
+        let tolerance = match kind {
+            CoordinateKind::Latitude => tolerance.lat,
+            CoordinateKind::Longitude => tolerance.lon,
+        };
+        dms_to_dd(&coord.dms, *kind).is_ok_and(|dd| (dd - coord.dd).abs() <= tolerance)
+    })
+}
+
+// Returns the first field outside latitude [-90, 90] / longitude [-180, 180].
+fn dd_out_of_range(dd: &[f64; 4]) -> Option<(&'static str, f64)> {
+    dd.iter()
+        .zip(COORD_FIELDS)
+        .find(|&(&value, (_, kind))| normalize_coordinate(value, kind).is_err())
+        .map(|(&value, (field, _))| (field, value))
+}
+
+// Build a fully normalized geo structure from its two points: DD rounded to
+// 6 decimals, DMS in the output notation. The line is the one of A.
+pub(crate) fn build_normalized_geo(a: &ParsedPoint, b: &ParsedPoint, cli: &Cli) -> NormalizedGeo {
+    let point = |p: &ParsedPoint| {
+        let coord = |input: &str, dd: f64, kind| {
+            let dd = round_with(dd, 6, cli.rounding);
+            NormalizedCoord {
+                input: input.to_string(),
+                dd,
+                dms: dd_to_dms_with(dd, kind, cli.dms_precision.into(), cli.locale, cli.rounding, cli.dms_symbols),
+            }
+        };
+        NormalizedPoint {
+            name: p.name.clone(),
+            lat: coord(&p.lat_in, p.lat, CoordinateKind::Latitude),
+            lon: coord(&p.lon_in, p.lon, CoordinateKind::Longitude),
+        }
+    };
+
+    let mut geo = NormalizedGeo {
+        a: point(a),
+        b: point(b),
+        line: a.line,
+        elev_a: None,
+        elev_b: None,
+        radius_km: None,
+        suspect: false,
+        seconds_rounded: false,
+        repaired: false,
+        passthrough: Vec::new(),
+        source_file: None,
+    };
+    geo.seconds_rounded = cli.flag_seconds_rounded && seconds_rounded(&geo, cli);
+    geo
+}
+
+// Process one normalized geo entry.
+fn process_geo(
+    writer: &mut OutputWriter,
+    geo: &NormalizedGeo,
+    cli: &Cli,
+    state: &mut RunState,
+    hook: Option<&mut RecordHook>,
+) -> Result<(), AppError> {
+
+    // Skip pairs already processed, in either order.
+    if cli.skip_duplicate_pairs && !state.seen_pairs.insert(pair_key(geo)) {
+        state.duplicates += 1;
+        return Ok(());
+    }
+    state.check_memory(cli, geo.line, 0)?;
+
+    // Points outside --expect-bbox: flagged, or fatal in strict mode.
+    let in_expected_bbox = cli.expect_bbox.map(|bounds| {
+        [("A", &geo.a), ("B", &geo.b)]
+            .into_iter()
+            .find(|(_, p)| !bbox_contains(bounds, p.lat.dd, p.lon.dd))
+    });
+    if let Some(Some((point, p))) = in_expected_bbox
+        && cli.strict
+    {
+        return Err(AppError::OutsideExpectedBbox { line: geo.line, point, lat: p.lat.dd, lon: p.lon.dd });
+    }
+
+    // Waypoints mode replaces the distance output.
+    if let Some(n) = cli.waypoints {
+        write_waypoints(writer, geo, n, state.id, cli, &mut state.passthrough)?;
+        state.add_to_bbox(geo.a.lat.dd, geo.a.lon.dd);
+        state.add_to_bbox(geo.b.lat.dd, geo.b.lon.dd);
+        state.id += 1;
+        return Ok(());
+    }
+
+    // Compute proximity comparison.
+    let tolerance = nearly_tolerance(cli);
+    let nearly = compute_nearly(
+        geo.a.lat.dd,
+        geo.a.lon.dd,
+        geo.b.lat.dd,
+        geo.b.lon.dd,
+        tolerance,
+        cli.nearly_mode,
+    );
+
+    // Sphere radius: the row's `radius_km` when given, else --earth-radius
+    // (both validated when read).
+    let radius_km = geo.radius_km.unwrap_or(cli.earth_radius);
+
+    // Compute distance.
+    // Same point (--snap-zero): exactly 0 instead of floating-point noise.
+    let surface_km = if cli.snap_zero && nearly.same_point() {
+        0.0
+    } else {
+        haversine_with_radius(
+            geo.a.lat.dd,
+            geo.a.lon.dd,
+            geo.b.lat.dd,
+            geo.b.lon.dd,
+            radius_km,
+        )?
+    };
+    if cli.check_distance {
+        check_earth_distance(surface_km)?;
+    }
+    let round = |value: f64, decimals: u32| round_with(value, decimals, cli.rounding);
+    let km_decimals = u32::from(cli.km_precision);
+    let miles_decimals = u32::from(cli.miles_precision);
+    let dist_km = round(surface_km, km_decimals);
+    if let Some(warning) = distance_warning(geo, dist_km, cli.warn_above) {
+        log::warn!("{warning}");
+    }
+
+    // Compute distance including elevations, when the input provides them.
+    // A row without one of its elevations gets an empty distance_3d_km.
+    let km_3d = match (state.has_elevation, geo.elev_a, geo.elev_b) {
+        (false, ..) => None,
+        (true, Some(elev_a), Some(elev_b)) => Some(Some(round(distance_3d(surface_km, elev_a, elev_b)?, km_decimals))),
+        (true, ..) => Some(None),
+    };
+
+    // Optional distance / bearing to a pole.
+    let pole_vector = |lat: f64| {
+        cli.to_pole.map(|pole| {
+            let v = to_pole(lat, pole, radius_km);
+            PoleVector { km: round(v.km, km_decimals), bearing_deg: v.bearing_deg }
+        })
+    };
+
+    let distance_metrics = DistanceMetrics {
+        km: dist_km,
+        // From the km rounded to the finer of both precisions, as with the
+        // historical 2 / 2 decimals, without losing any with --miles-precision.
+        miles: round(round(surface_km, km_decimals.max(miles_decimals)) * KM_TO_MILES, miles_decimals),
+        km_3d,
+        km_long: cli.emit_long_arc.then(|| round(long_arc_km(surface_km, radius_km), km_decimals)),
+        nearly,
+        deltas: cli.emit_deltas.then(|| {
+            // Same precision as the DD columns (--coord-precision, else 6 decimals).
+            let decimals = cli.coord_precision.map_or(6, u32::from);
+            let delta = |a: f64, b: f64| round_with((a - b).abs(), decimals, cli.rounding);
+            (delta(geo.a.lat.dd, geo.b.lat.dd), delta(geo.a.lon.dd, geo.b.lon.dd))
+        }),
+        colocated: cli.colocated_km.map(|r| dist_km <= r),
+        antipodal: cli.flag_antipodal.then(|| is_antipodal(
+            geo.a.lat.dd,
+            geo.a.lon.dd,
+            geo.b.lat.dd,
+            geo.b.lon.dd,
+            GeoTolerance::DEFAULT,
+        )),
+        lon_sep_deg: cli.emit_lon_sep
+            .then(|| round(longitude_separation(geo.a.lon.dd, geo.b.lon.dd), 6)),
+        crosses_antimeridian: cli.flag_antimeridian.then(|| crosses_antimeridian(geo.a.lon.dd, geo.b.lon.dd)),
+        bearing: cli.emit_bearing.then(|| {
+            let bearing = initial_bearing(geo.a.lat.dd, geo.a.lon.dd, geo.b.lat.dd, geo.b.lon.dd);
+            (round(bearing, 2), compass_point(bearing))
+        }),
+        dms: cli.emit_formats.contains(&EmitFormat::Dms),
+        ddm: cli.emit_formats.contains(&EmitFormat::Ddm).then(|| {
+            let ddm = |value, kind| dd_to_ddm(value, kind, DDM_OUTPUT_DECIMALS, cli.locale, cli.rounding, cli.dms_symbols);
+            [
+                ddm(geo.a.lat.dd, CoordinateKind::Latitude),
+                ddm(geo.a.lon.dd, CoordinateKind::Longitude),
+                ddm(geo.b.lat.dd, CoordinateKind::Latitude),
+                ddm(geo.b.lon.dd, CoordinateKind::Longitude),
+            ]
+        }),
+        iso6709: cli.iso6709 || cli.emit_formats.contains(&EmitFormat::Iso),
+        wkt: cli.wkt.then(|| {
+            // Same decimals as the DD columns (--coord-precision).
+            let decimals = cli.coord_precision.map(usize::from);
+            let (a, b) = ((geo.a.lat.dd, geo.a.lon.dd), (geo.b.lat.dd, geo.b.lon.dd));
+            [
+                to_wkt_point(a.0, a.1, decimals, cli.rounding),
+                to_wkt_point(b.0, b.1, decimals, cli.rounding),
+                to_wkt_linestring(a, b, decimals, cli.rounding),
+            ]
+        }),
+        polyline: cli.emit_polyline.map(|n| encode_polyline(&arc_points(geo, n))),
+        mercator: cli.emit_mercator.then(|| {
+            let xy = |lat, lon| {
+                let (x, y) = to_web_mercator(lat, lon);
+                [round_with(x, 2, cli.rounding), round_with(y, 2, cli.rounding)]
+            };
+            (xy(geo.a.lat.dd, geo.a.lon.dd), xy(geo.b.lat.dd, geo.b.lon.dd))
+        }),
+        cells: cli.grid_size.map(|size| {
+            let cell = |lat, lon| <[i64; 2]>::from(grid_cell(lat, lon, size));
+            (cell(geo.a.lat.dd, geo.a.lon.dd), cell(geo.b.lat.dd, geo.b.lon.dd))
+        }),
+        tz: cli.emit_tz.then(|| (nautical_tz(geo.a.lon.dd), nautical_tz(geo.b.lon.dd))),
+        suspect: cli.sanity_check.then_some(geo.suspect),
+        in_expected_bbox: in_expected_bbox.map(|outside| outside.is_none()),
+        seconds_rounded: cli.flag_seconds_rounded.then_some(geo.seconds_rounded),
+        roundtrip_ok: cli.roundtrip_check.then(|| roundtrip_ok(geo, tolerance)),
+        repaired: cli.repair.then_some(geo.repaired),
+        to_pole_a: pole_vector(geo.a.lat.dd),
+        to_pole_b: pole_vector(geo.b.lat.dd),
+        datum: cli.datum.map(|datum| if cli.datum_shift { Datum::Wgs84 } else { datum }),
+        // A single notation per run for now: --input-format.
+        format_used: cli.emit_format_used.then_some(cli.input_format),
+    };
+
+    // Write output row (nothing is written with --validate), or hold it
+    // until the end of the input with --sort-by.
+    if !cli.validate {
+        let mut record = output_record(geo, &distance_metrics, state.id);
+        if let Some(hook) = hook {
+            hook(&mut record);
+        }
+        if cli.sort_by.is_some() {
+            state.sorted.push(SortedRow { line: geo.line, record, passthrough: geo.passthrough.clone() });
+            state.check_memory(cli, geo.line, 0)?;
+        } else if let Err(e) = write_output(writer, &record, &mut state.passthrough, &geo.passthrough, &cli.columns) {
+            // A missing --columns column fails every row: stop at the first one.
+            if let AppError::ColumnNotWritten(_) = e {
+                return Err(e);
+            }
+            return state.reject(cli, geo.line, e);
+        }
+    }
+
+    if geo.seconds_rounded {
+        state.seconds_rounded += 1;
+    }
+    if geo.repaired {
+        state.repaired += 1;
+    }
+    state.add_to_bbox(geo.a.lat.dd, geo.a.lon.dd);
+    state.add_to_bbox(geo.b.lat.dd, geo.b.lon.dd);
+    state.id += 1;
+    Ok(())
+}
+
+// Write `n` evenly spaced points along the A-B great circle.
+fn write_waypoints(
+    writer: &mut OutputWriter,
+    geo: &NormalizedGeo,
+    n: u32,
+    pair_id: u64,
+    cli: &Cli,
+    passthrough: &mut Passthrough,
+) -> Result<(), AppError> {
+    let round = |value: f64| round_with(value, 6, cli.rounding);
+    let (a, b) = (&geo.a, &geo.b);
+
+    if arc_is_degenerate(a.lat.dd, a.lon.dd, b.lat.dd, b.lon.dd) {
+        log::warn!(
+            "Line {}: no single great circle between {} and {} (coincident or antipodal points), using linear interpolation",
+            geo.line, a.name, b.name,
+        );
+    }
+
+    for index in 0..n {
+        let fraction = f64::from(index) / f64::from(n - 1);
+        let (lat, lon) = intermediate_point(a.lat.dd, a.lon.dd, b.lat.dd, b.lon.dd, fraction);
+
+        writer.write_record_with(&WaypointRecord {
+            pair_id,
+            index,
+            fraction: round(fraction),
+            name_a: a.name.clone(),
+            name_b: b.name.clone(),
+            lat_dd: round(lat),
+            lon_dd: round(lon),
+            lat: format_computed(lat, CoordinateKind::Latitude, cli),
+            lon: format_computed(lon, CoordinateKind::Longitude, cli),
+        }, passthrough, &geo.passthrough)?;
+    }
+
+    Ok(())
+}
+
+// N evenly spaced points (lat, lon) along the A-B great circle, A and B
+// included, as written by --waypoints.
+fn arc_points(geo: &NormalizedGeo, n: u32) -> Vec<(f64, f64)> {
+    let (a, b) = (&geo.a, &geo.b);
+    (0..n)
+        .map(|index| {
+            let fraction = f64::from(index) / f64::from(n - 1);
+            intermediate_point(a.lat.dd, a.lon.dd, b.lat.dd, b.lon.dd, fraction)
+        })
+        .collect()
+}
+
+// --warn-above: message for an implausibly large distance, if above the threshold.
+pub(crate) fn distance_warning(geo: &NormalizedGeo, km: f64, threshold: Option<f64>) -> Option<String> {
+    let threshold = threshold.filter(|&t| km > t)?;
+    Some(format!(
+        "Warning: line {}: {} km between {} and {} (above --warn-above {} km)",
+        geo.line, km, geo.a.name, geo.b.name, threshold,
+    ))
+}
+
+// With --datum-shift, a point moved from the --datum to WGS84.
+fn shift_datum(cli: &Cli, lat: f64, lon: f64) -> (f64, f64) {
+    match cli.datum {
+        Some(datum) if cli.datum_shift => datum.to_wgs84(lat, lon),
+        _ => (lat, lon),
+    }
+}
+
+// Canonical (order-independent) key of the pair A-B.
+fn pair_key(geo: &NormalizedGeo) -> PairKey {
+    let micro = |deg: f64| (deg * 1e6).round() as i64;
+    let a = (micro(geo.a.lat.dd), micro(geo.a.lon.dd));
+    let b = (micro(geo.b.lat.dd), micro(geo.b.lon.dd));
+    if a <= b { [a, b] } else { [b, a] }
+}
+
+// Number of data rows to read after --skip: --limit, unless it applies to
+// the sorted output (--sort-by).
+fn read_limit(cli: &Cli) -> usize {
+    cli.limit.filter(|_| cli.sort_by.is_none()).unwrap_or(usize::MAX)
+}
@@ -0,0 +1,935 @@
+// src/output.rs
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use csv::{ReaderBuilder, StringRecord, Writer, WriterBuilder};
+use serde::Serialize;
+
+use crate::util::round_with;
+use crate::util::RoundingMode;
+use crate::fields::CsvFields;
+use crate::geo::CoordinateKind;
+use crate::geo::dd_to_dms_with;
+use crate::geo::dd_to_ddm;
+use crate::geo::dd_to_iso6709;
+use crate::geo::Locale;
+use crate::geo::datum::Datum;
+
+use crate::cli::Cli;
+use crate::cli::InputFormat;
+use crate::cli::OutputFormat;
+use crate::cli::SortKey;
+use crate::cli::QuoteStyle;
+use crate::error::AppError;
+use crate::modes::NormalizedGeo;
+use crate::modes::DistanceMetrics;
+use crate::modes::RunState;
+
+/* ---------------- OUTPUT CSV STRUCTS ---------------- */
+
+// Output CSV record (fully normalized).
+#[derive(Debug, Serialize)]
+pub struct OutputRecord {
+    pub id: u64,
+
+    pub name_a: String,
+    pub lat_a_in: String,
+    pub lon_a_in: String,
+    pub lat_a_dd: f64,
+    pub lon_a_dd: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat_a_dms: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon_a_dms: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat_a_ddm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon_a_ddm: Option<String>,
+
+    pub name_b: String,
+    pub lat_b_in: String,
+    pub lon_b_in: String,
+    pub lat_b_dd: f64,
+    pub lon_b_dd: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat_b_dms: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon_b_dms: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat_b_ddm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon_b_ddm: Option<String>,
+
+    pub distance_km: f64,
+    pub distance_miles: f64,
+    // Some(None): elevation columns in the input, but missing on this row.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance_3d_km: Option<Option<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance_long_km: Option<f64>,
+    pub nearly_lat: bool,
+    pub nearly_lon: bool,
+    pub nearly_both: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta_lat_deg: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta_lon_deg: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colocated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub antipodal: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon_sep_deg: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crosses_antimeridian: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bearing_deg: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bearing_compass: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub point_a_iso: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub point_b_iso: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geom_a: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geom_b: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geom_line: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub polyline: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x_a: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y_a: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x_b: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y_b: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cell_a: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cell_b: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tz_a: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tz_b: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspect: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_expected_bbox: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seconds_rounded: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roundtrip_ok: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repaired: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dist_to_pole_km_a: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bearing_to_pole_deg_a: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dist_to_pole_km_b: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bearing_to_pole_deg_b: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datum: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format_used: Option<InputFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_file: Option<String>,
+}
+
+// Every column of OutputRecord, in output order (--columns).
+pub(crate) const OUTPUT_COLUMNS: &[&str] = &[
+    "id",
+    "name_a", "lat_a_in", "lon_a_in", "lat_a_dd", "lon_a_dd", "lat_a_dms", "lon_a_dms", "lat_a_ddm", "lon_a_ddm",
+    "name_b", "lat_b_in", "lon_b_in", "lat_b_dd", "lon_b_dd", "lat_b_dms", "lon_b_dms", "lat_b_ddm", "lon_b_ddm",
+    "distance_km", "distance_miles", "distance_3d_km", "distance_long_km",
+    "nearly_lat", "nearly_lon", "nearly_both", "delta_lat_deg", "delta_lon_deg",
+    "colocated", "antipodal", "lon_sep_deg", "crosses_antimeridian", "bearing_deg", "bearing_compass",
+    "point_a_iso", "point_b_iso", "geom_a", "geom_b", "geom_line", "polyline",
+    "x_a", "y_a", "x_b", "y_b", "cell_a", "cell_b",
+    "tz_a", "tz_b", "suspect", "in_expected_bbox", "seconds_rounded", "roundtrip_ok",
+    "repaired",
+    "dist_to_pole_km_a", "bearing_to_pole_deg_a", "dist_to_pole_km_b", "bearing_to_pole_deg_b",
+    "datum", "format_used", "source_file",
+];
+
+// Output columns echoing the input, never prefixed by --column-prefix
+// (pair and --track records). The --passthrough columns are not either.
+const INPUT_COLUMNS: &[&str] = &[
+    "name_a", "lat_a_in", "lon_a_in", "name_b", "lat_b_in", "lon_b_in",
+    "name", "lat_in", "lon_in",
+];
+
+// Computed integer columns (row ids, waypoint index), never localized by
+// --number-locale.
+const COUNTER_COLUMNS: &[&str] = &["id", "pair_id", "index"];
+
+// Coordinate columns written as text (DMS, DDM or --output-coord-format).
+fn is_coord_text(name: &str) -> bool {
+    name.ends_with("_dms") || name.ends_with("_ddm") || matches!(name, "lat" | "lon" | "lat_b" | "lon_b")
+}
+
+// Name of an output column with the --column-prefix.
+fn prefixed_column(prefix: &str, name: &str) -> String {
+    if INPUT_COLUMNS.contains(&name) {
+        name.to_string()
+    } else {
+        format!("{prefix}{name}")
+    }
+}
+
+// Subset of the columns of an output record, in the --columns order.
+struct Selected {
+    fields: Vec<(&'static str, serde_json::Value)>,
+}
+
+impl Selected {
+    // Fails if a column is not part of the record (option not enabled).
+    fn new(record: &OutputRecord, columns: &[&'static str]) -> Result<Self, AppError> {
+        let serde_json::Value::Object(mut values) = serde_json::to_value(record)? else {
+            unreachable!("OutputRecord serializes as an object");
+        };
+        let fields = columns
+            .iter()
+            .map(|&name| values.remove(name).map(|v| (name, v)).ok_or(AppError::ColumnNotWritten(name)))
+            .collect::<Result<_, _>>()?;
+        Ok(Selected { fields })
+    }
+}
+
+impl Serialize for Selected {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut record = serializer.serialize_struct("Selected", self.fields.len())?;
+        for (name, value) in &self.fields {
+            record.serialize_field(name, value)?;
+        }
+        record.end()
+    }
+}
+
+// JSON object with its keys in a given order (serde_json maps are sorted).
+struct JsonObject {
+    fields: Vec<(String, serde_json::Value)>,
+}
+
+impl JsonObject {
+    // Fields of a record, in its order, with the computed keys prefixed
+    // (--column-prefix).
+    fn prefixed<T: Serialize>(record: &T, prefix: &str) -> Result<Self, AppError> {
+        let serde_json::Value::Object(mut values) = serde_json::to_value(record)? else {
+            unreachable!("output records serialize as objects");
+        };
+        let fields = CsvFields::header_of(record)?
+            .iter()
+            .map(|name| (prefixed_column(prefix, name), values.remove(name).unwrap_or_default()))
+            .collect();
+        Ok(JsonObject { fields })
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+impl Serialize for JsonObject {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.fields.iter().map(|(name, value)| (name, value)))
+    }
+}
+
+// Output record of the --waypoints mode (one row per point).
+#[derive(Debug, Serialize)]
+pub(crate) struct WaypointRecord {
+    pub(crate) pair_id: u64,
+    pub(crate) index: u32,
+    pub(crate) fraction: f64,
+    pub(crate) name_a: String,
+    pub(crate) name_b: String,
+    pub(crate) lat_dd: f64,
+    pub(crate) lon_dd: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) lat: Option<String>,   // --output-coord-format
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) lon: Option<String>,
+}
+
+// First column of a --matrix square row, before the distance columns.
+#[derive(Debug, Serialize)]
+pub(crate) struct MatrixRow {
+    pub(crate) name: String,
+}
+
+// Output record of the --track mode (one row per point).
+#[derive(Debug, Serialize)]
+pub(crate) struct TrackRecord {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    pub(crate) lat_in: String,
+    pub(crate) lon_in: String,
+    pub(crate) lat_dd: f64,
+    pub(crate) lon_dd: f64,
+    pub(crate) segment_km: f64,        // from the previous valid point (0 for the first one)
+    pub(crate) cumulative_km: f64,
+}
+
+// Output record of the --destination mode (one row per start point).
+#[derive(Debug, Serialize)]
+pub(crate) struct DestinationRecord {
+    pub(crate) id: u64,
+    pub(crate) name_a: String,
+    pub(crate) lat_a_in: String,
+    pub(crate) lon_a_in: String,
+    pub(crate) lat_a_dd: f64,
+    pub(crate) lon_a_dd: f64,
+    pub(crate) bearing_deg: f64,
+    pub(crate) distance_km: f64,
+    pub(crate) lat_b_dd: f64,
+    pub(crate) lon_b_dd: f64,
+    pub(crate) lat_b_dms: String,
+    pub(crate) lon_b_dms: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) lat_b: Option<String>, // --output-coord-format
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) lon_b: Option<String>,
+}
+
+// Callback invoked on every output record before it is written, to
+// transform values without forking (not called for --waypoints rows).
+pub type RecordHook<'a> = dyn FnMut(&mut OutputRecord) + 'a;
+
+/* ---------------- OUTPUT WRITER ---------------- */
+
+// Output sink: one row per record, either CSV or JSON Lines.
+// `UnquotedCsv` (--quote-style never, --tsv) refuses the fields that would
+// need quotes. `FormattedCsv` (--coord-precision, --number-locale) rewrites
+// the numeric columns, with the quoting check of `UnquotedCsv` when
+// needed. `Discard` (--validate) has
+// no file and writes nothing.
+pub(crate) enum OutputWriter {
+    Csv(Box<Writer<OutputFile>>, CsvFields),
+    UnquotedCsv(Box<Writer<OutputFile>>, Unquoted, CsvFields),
+    FormattedCsv(Box<Writer<OutputFile>>, Option<Unquoted>, RowFormat, CsvFields),
+    Ndjson(BufWriter<File>, Option<String>),  // --column-prefix
+    Discard,
+}
+
+// Rewriting of the CSV rows: number format of the numeric columns
+// (--coord-precision decimals, --number-locale separator) and header names
+// (--column-prefix).
+pub(crate) struct RowFormat {
+    dd_decimals: Option<usize>,
+    rounding: RoundingMode,
+    locale: Locale,
+    prefix: Option<String>,
+    header_written: bool,
+}
+
+impl RowFormat {
+    // Header with the computed column names prefixed.
+    fn header(&self, header: &StringRecord) -> StringRecord {
+        match &self.prefix {
+            Some(prefix) => header.iter().map(|name| prefixed_column(prefix, name)).collect(),
+            None => header.clone(),
+        }
+    }
+
+    // Rewrites the numbers of a row: every computed numeric field, and the
+    // decimal separator of the DMS / DDM / --output-coord-format text. The
+    // input text and the row counters are kept.
+    fn apply(&self, header: &StringRecord, row: &StringRecord) -> StringRecord {
+        row.iter()
+            .zip(header)
+            .map(|(field, name)| {
+                if INPUT_COLUMNS.contains(&name) || COUNTER_COLUMNS.contains(&name) {
+                    return field.to_string();
+                }
+                let is_dd = name.ends_with("_dd");
+                match field.parse::<f64>() {
+                    Ok(value) => {
+                        let text = match self.dd_decimals {
+                            Some(decimals) if is_dd => {
+                                let value = round_with(value, decimals as u32, self.rounding) + 0.0;
+                                format!("{value:.decimals$}")
+                            }
+                            _ => field.to_string(),
+                        };
+                        localize_number(&text, self.locale)
+                    }
+                    Err(_) if is_coord_text(name) => localize_number(field, self.locale),
+                    Err(_) => field.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+// A plain decimal number (`-1234.56`) in the --number-locale: unchanged in
+// English, `-1 234,56` in French (decimal comma, digits grouped by three with
+// a no-break space). An exponent form only gets the decimal comma.
+pub(crate) fn localize_number(text: &str, locale: Locale) -> String {
+    if locale == Locale::En {
+        return text.to_string();
+    }
+    let (int, frac) = text.split_once('.').map_or((text, None), |(int, frac)| (int, Some(frac)));
+    let (sign, digits) = int.strip_prefix('-').map_or(("", int), |digits| ("-", digits));
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return text.replace('.', ",");
+    }
+
+    let mut out = String::from(sign);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            out.push('\u{a0}');
+        }
+        out.push(digit);
+    }
+    if let Some(frac) = frac {
+        out.push(',');
+        out.push_str(frac);
+    }
+    out
+}
+
+// The parent directory of an output file must exist: created with --mkdir,
+// otherwise a clear error instead of the bare "No such file or directory".
+fn ensure_parent_dir(path: &std::path::Path, mkdir: bool) -> Result<(), AppError> {
+    let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) else {
+        return Ok(());
+    };
+    if parent.is_dir() {
+        return Ok(());
+    }
+    if mkdir {
+        std::fs::create_dir_all(parent)?;
+        return Ok(());
+    }
+    Err(AppError::MissingOutputDir(path.to_path_buf()))
+}
+
+// Separators of an unquoted CSV output. TSV has no quote character.
+#[derive(Copy, Clone)]
+pub(crate) struct Unquoted {
+    delimiter: u8,
+    quote: Option<u8>,
+}
+
+impl OutputWriter {
+    pub(crate) fn create(cli: &Cli) -> Result<Self, AppError> {
+        let Some(path) = &cli.output else {
+            return Ok(OutputWriter::Discard);
+        };
+        ensure_parent_dir(path, cli.mkdir)?;
+        // --append to a non-empty file: its header is kept.
+        let appended = appended_output(cli).is_some();
+        let mut file = if cli.append {
+            OpenOptions::new().append(true).create(true).open(path)?
+        } else {
+            File::create(path)?
+        };
+        if appended && !ends_with_newline(path)? {
+            file.write_all(b"\n")?;
+        }
+        if let OutputFormat::Ndjson = cli.output_format {
+            return Ok(OutputWriter::Ndjson(BufWriter::new(file), cli.column_prefix.clone()));
+        }
+        let file = OutputFile { file, skip_header: appended };
+
+        let (writer, unquoted) = if cli.tsv {
+            let writer = WriterBuilder::new()
+                .delimiter(b'\t')
+                .quote_style(csv::QuoteStyle::Never)
+                .from_writer(file);
+            (writer, Some(Unquoted { delimiter: b'\t', quote: None }))
+        } else {
+            let writer = WriterBuilder::new()
+                .quote_style(cli.quote_style.into())
+                .quote(cli.quote_char)
+                .from_writer(file);
+            let unquoted = matches!(cli.quote_style, QuoteStyle::Never)
+                .then_some(Unquoted { delimiter: b',', quote: Some(cli.quote_char) });
+            (writer, unquoted)
+        };
+
+        let writer = Box::new(writer);
+        let formatted = cli.coord_precision.is_some()
+            || cli.number_locale != Locale::En
+            || cli.column_prefix.is_some();
+        Ok(match (formatted, unquoted) {
+            (true, unquoted) => {
+                let format = RowFormat {
+                    dd_decimals: cli.coord_precision.map(usize::from),
+                    rounding: cli.rounding,
+                    locale: cli.number_locale,
+                    prefix: cli.column_prefix.clone(),
+                    header_written: false,
+                };
+                OutputWriter::FormattedCsv(writer, unquoted, format, CsvFields::default())
+            }
+            (false, Some(unquoted)) => OutputWriter::UnquotedCsv(writer, unquoted, CsvFields::default()),
+            (false, None) => OutputWriter::Csv(writer, CsvFields::default()),
+        })
+    }
+
+    // Serialize one record (one CSV row or one JSON object per line).
+    pub(crate) fn write_record<T: Serialize>(&mut self, record: &T) -> Result<(), AppError> {
+        match self {
+            OutputWriter::Csv(w, _) => w.serialize(record)?,
+            OutputWriter::UnquotedCsv(w, unquoted, fields) => {
+                let first = fields.header.is_empty();
+                fields.fill(record)?;
+                check_unquoted(fields.row.iter(), *unquoted)?;
+                if first {
+                    w.write_record(&fields.header)?;
+                }
+                w.write_record(&fields.row)?;
+            }
+            OutputWriter::FormattedCsv(w, unquoted, format, fields) => {
+                fields.fill(record)?;
+                let row = format.apply(&fields.header, &fields.row);
+                if let Some(unquoted) = unquoted {
+                    check_unquoted(row.iter(), *unquoted)?;
+                }
+                if !format.header_written {
+                    w.write_record(&format.header(&fields.header))?;
+                    format.header_written = true;
+                }
+                w.write_record(&row)?;
+            }
+            OutputWriter::Ndjson(w, prefix) => {
+                match prefix {
+                    Some(prefix) => serde_json::to_writer(&mut *w, &JsonObject::prefixed(record, prefix)?)?,
+                    None => serde_json::to_writer(&mut *w, record)?,
+                }
+                w.write_all(b"\n")?;
+            }
+            OutputWriter::Discard => {}
+        }
+        Ok(())
+    }
+
+    // Serialize one record followed by the extra input columns (--passthrough).
+    // The CSV header is written with the first record, as for `write_record`.
+    pub(crate) fn write_record_with<T: Serialize>(
+        &mut self,
+        record: &T,
+        passthrough: &mut Passthrough,
+        values: &[String],
+    ) -> Result<(), AppError> {
+        if passthrough.columns.is_empty() {
+            return self.write_record(record);
+        }
+
+        let (w, unquoted, format, fields) = match self {
+            OutputWriter::Csv(w, fields) => (w, None, None, fields),
+            OutputWriter::UnquotedCsv(w, unquoted, fields) => (w, Some(*unquoted), None, fields),
+            OutputWriter::FormattedCsv(w, unquoted, format, fields) => (w, *unquoted, Some(&*format), fields),
+            OutputWriter::Ndjson(w, prefix) => {
+                if let Some(prefix) = prefix {
+                    let mut object = JsonObject::prefixed(record, prefix)?;
+                    if !passthrough.resolved {
+                        passthrough.resolve(&object.names().collect());
+                    }
+                    let extra = passthrough.names().zip(values);
+                    object.fields.extend(extra.map(|(name, value)| (name.to_string(), value.as_str().into())));
+                    serde_json::to_writer(&mut *w, &object)?;
+                } else {
+                    if !passthrough.resolved {
+                        passthrough.resolve(&CsvFields::header_of(record)?);
+                    }
+                    let names: Vec<&str> = passthrough.names().collect();
+                    let extended = Extended { record, extra: ExtraColumns { names: &names, values } };
+                    serde_json::to_writer(&mut *w, &extended)?;
+                }
+                w.write_all(b"\n")?;
+                return Ok(());
+            }
+            OutputWriter::Discard => return Ok(()),
+        };
+
+        fields.fill(record)?;
+        let formatted = format.map(|format| format.apply(&fields.header, &fields.row));
+        let row = formatted.as_ref().unwrap_or(&fields.row);
+        if let Some(unquoted) = unquoted {
+            check_unquoted(row.iter().chain(values.iter().map(String::as_str)), unquoted)?;
+        }
+        if !passthrough.resolved {
+            let header = format.map_or_else(|| fields.header.clone(), |format| format.header(&fields.header));
+            passthrough.resolve(&header);
+            w.write_record(header.iter().chain(passthrough.names()))?;
+        }
+        w.write_record(row.iter().chain(values.iter().map(String::as_str)))?;
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), AppError> {
+        match self {
+            OutputWriter::Csv(w, _) | OutputWriter::UnquotedCsv(w, ..) | OutputWriter::FormattedCsv(w, ..) => w.flush()?,
+            OutputWriter::Ndjson(w, _) => w.flush()?,
+            OutputWriter::Discard => {}
+        }
+        Ok(())
+    }
+}
+
+// Output file of the CSV writers. With --append to a non-empty file, the
+// header line they write first is dropped: the file already has one.
+pub(crate) struct OutputFile {
+    file: File,
+    skip_header: bool,
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.skip_header {
+            return self.file.write(buf);
+        }
+        if let Some(end) = buf.iter().position(|&b| b == b'\n') {
+            self.skip_header = false;
+            self.file.write_all(&buf[end + 1..])?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// The output file when --append continues a non-empty one.
+fn appended_output(cli: &Cli) -> Option<&PathBuf> {
+    cli.output.as_ref().filter(|path| cli.append && std::fs::metadata(path).is_ok_and(|m| m.len() > 0))
+}
+
+// True when the last byte of a non-empty file is a line feed.
+fn ends_with_newline(path: &PathBuf) -> Result<bool, AppError> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::End(-1))?;
+    let mut last = [0u8];
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+// Id of the first written row: --start-id, else the last id of the
+// --append output file + 1, else 1.
+pub(crate) fn first_id(cli: &Cli) -> Result<u64, AppError> {
+    if let Some(id) = cli.start_id {
+        return Ok(id);
+    }
+    let Some(path) = appended_output(cli) else {
+        return Ok(1);
+    };
+    let id_column = prefixed_column(cli.column_prefix.as_deref().unwrap_or_default(), "id");
+    let last_id = match cli.output_format {
+        OutputFormat::Ndjson => {
+            let mut last = None;
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    last = Some(line);
+                }
+            }
+            match last {
+                Some(line) => serde_json::from_str::<serde_json::Value>(&line).ok().and_then(|row| row[&id_column].as_u64()),
+                None => Some(0),
+            }
+        }
+        OutputFormat::Csv => {
+            let mut reader = ReaderBuilder::new()
+                .delimiter(if cli.tsv { b'\t' } else { b',' })
+                .quote(cli.quote_char)
+                .from_path(path)?;
+            let index = reader.headers()?.iter().position(|h| h == id_column);
+            let mut last = None;
+            for record in reader.records() {
+                last = Some(record?);
+            }
+            // A header without any row: numbering starts at 1.
+            match (index, last) {
+                (_, None) => Some(0),
+                (Some(i), Some(record)) => record.get(i).and_then(|id| id.parse().ok()),
+                (None, Some(_)) => None,
+            }
+        }
+    };
+    last_id.map(|id| id + 1).ok_or_else(|| AppError::AppendId(path.clone()))
+}
+
+// Fails on the first field that cannot be written without quotes (delimiter,
+// line break, or leading quote character), instead of writing a corrupt row.
+// A quote inside a field is read literally (e.g. the seconds of a DMS value).
+fn check_unquoted<'a>(mut fields: impl Iterator<Item = &'a str>, unquoted: Unquoted) -> Result<(), AppError> {
+    let needs_quotes = |f: &str| {
+        f.contains([char::from(unquoted.delimiter), '\n', '\r'])
+            || unquoted.quote.is_some_and(|q| f.as_bytes().first() == Some(&q))
+    };
+    match fields.find(|f| needs_quotes(f)) {
+        Some(field) => Err(AppError::UnquotableField(field.to_string())),
+        None => Ok(()),
+    }
+}
+
+/* ---------------- PASSTHROUGH ---------------- */
+
+// Extra input columns copied after the computed columns (--passthrough).
+#[derive(Debug, Default)]
+pub(crate) struct Passthrough {
+    pub(crate) columns: Vec<(usize, String)>,  // input index, output name
+    pub(crate) resolved: bool,                 // output names checked against the record fields
+}
+
+impl Passthrough {
+    // Every input column that is not read as a coordinate, name or elevation.
+    pub(crate) fn new(headers: &StringRecord, known: &[&[&str]]) -> Self {
+        let columns = headers
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| !known.iter().any(|set| set.contains(h)))
+            .map(|(i, h)| (i, h.to_string()))
+            .collect();
+        Passthrough { columns, resolved: false }
+    }
+
+    // Renames the columns clashing with a computed one (`id` becomes `id_in`).
+    fn resolve(&mut self, header: &StringRecord) {
+        for (_, name) in &mut self.columns {
+            if header.iter().any(|h| h == name) {
+                name.push_str("_in");
+            }
+        }
+        self.resolved = true;
+    }
+
+    // Output names of the extra columns.
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.columns.iter().map(|(_, name)| name.as_str())
+    }
+
+    // Values of the extra columns of a raw record.
+    pub(crate) fn values(&self, record: &StringRecord) -> Vec<String> {
+        self.columns
+            .iter()
+            .map(|&(i, _)| record.get(i).unwrap_or_default().to_string())
+            .collect()
+    }
+}
+
+// Record followed by extra columns, for the JSON output.
+#[derive(Serialize)]
+struct Extended<'a, T> {
+    #[serde(flatten)]
+    record: &'a T,
+    #[serde(flatten)]
+    extra: ExtraColumns<'a>,
+}
+
+// Extra columns serialized as string fields, in input order.
+struct ExtraColumns<'a> {
+    names: &'a [&'a str],
+    values: &'a [String],
+}
+
+impl Serialize for ExtraColumns<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.names.iter().zip(self.values))
+    }
+}
+
+/* ---------------- MANIFEST ---------------- */
+
+// File reference with its BLAKE3 hash.
+#[derive(Debug, Serialize)]
+struct ManifestFile {
+    path: PathBuf,
+    blake3: String,
+}
+
+// Row counters of a run.
+#[derive(Debug, Serialize)]
+struct ManifestRows {
+    read: u64,
+    written: u64,
+    invalid: u64,
+    duplicates: u64,
+}
+
+// Provenance manifest: ties an output to its input and configuration.
+#[derive(Debug, Serialize)]
+struct Manifest<'a> {
+    tool: &'static str,
+    version: &'static str,
+    input: ManifestFile,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    other_inputs: Vec<ManifestFile>,  // second and next --input files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<ManifestFile>,
+    rows: ManifestRows,
+    options: &'a Cli,
+}
+
+// Streams a file through BLAKE3 and returns the hex digest.
+fn hash_file(path: &PathBuf) -> Result<ManifestFile, AppError> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?)?;
+    Ok(ManifestFile {
+        path: path.clone(),
+        blake3: hasher.finalize().to_hex().to_string(),
+    })
+}
+
+// Writes the provenance manifest of a finished run.
+pub(crate) fn write_manifest(path: &PathBuf, cli: &Cli, state: &RunState) -> Result<(), AppError> {
+    let manifest = Manifest {
+        tool: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        input: hash_file(&cli.input[0])?,
+        other_inputs: cli.input[1..].iter().map(hash_file).collect::<Result<_, _>>()?,
+        output: cli.output.as_ref().map(hash_file).transpose()?,
+        rows: ManifestRows {
+            read: state.read,
+            written: state.written(),
+            invalid: state.invalid,
+            duplicates: state.duplicates,
+        },
+        options: cli,
+    };
+
+    ensure_parent_dir(path, cli.mkdir)?;
+    let mut file = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut file, &manifest)?;
+    file.write_all(b"\n")?;
+    file.flush()?;
+    Ok(())
+}
+
+/* ---------------- RECORDS ---------------- */
+
+// Decimals of the DDM minutes of the computed coordinates (0.0001' is about 0.2 m).
+pub(crate) const DDM_OUTPUT_DECIMALS: usize = 4;
+
+// A computed coordinate in the --output-coord-format notation, if set.
+// DMS follows --dms-precision; both DMS and DDM follow --locale, --rounding
+// and --dms-symbols.
+pub(crate) fn format_computed(value: f64, kind: CoordinateKind, cli: &Cli) -> Option<String> {
+    Some(match cli.output_coord_format? {
+        InputFormat::Dd => (round_with(value, 6, cli.rounding) + 0.0).to_string(),
+        InputFormat::Dms => {
+            dd_to_dms_with(value, kind, cli.dms_precision.into(), cli.locale, cli.rounding, cli.dms_symbols)
+        }
+        InputFormat::Ddm => {
+            dd_to_ddm(value, kind, DDM_OUTPUT_DECIMALS, cli.locale, cli.rounding, cli.dms_symbols)
+        }
+    })
+}
+
+// Output row of a pair.
+pub(crate) fn output_record(geo: &NormalizedGeo, distance_metrics: &DistanceMetrics, id: u64) -> OutputRecord {
+    OutputRecord {
+        id,
+        name_a: geo.a.name.clone(),
+        lat_a_in: geo.a.lat.input.clone(),
+        lon_a_in: geo.a.lon.input.clone(),
+        lat_a_dd: geo.a.lat.dd,
+        lon_a_dd: geo.a.lon.dd,
+        lat_a_dms: distance_metrics.dms.then(|| geo.a.lat.dms.clone()),
+        lon_a_dms: distance_metrics.dms.then(|| geo.a.lon.dms.clone()),
+        lat_a_ddm: distance_metrics.ddm.as_ref().map(|ddm| ddm[0].clone()),
+        lon_a_ddm: distance_metrics.ddm.as_ref().map(|ddm| ddm[1].clone()),
+        name_b: geo.b.name.clone(),
+        lat_b_in: geo.b.lat.input.clone(),
+        lon_b_in: geo.b.lon.input.clone(),
+        lat_b_dd: geo.b.lat.dd,
+        lon_b_dd: geo.b.lon.dd,
+        lat_b_dms: distance_metrics.dms.then(|| geo.b.lat.dms.clone()),
+        lon_b_dms: distance_metrics.dms.then(|| geo.b.lon.dms.clone()),
+        lat_b_ddm: distance_metrics.ddm.as_ref().map(|ddm| ddm[2].clone()),
+        lon_b_ddm: distance_metrics.ddm.as_ref().map(|ddm| ddm[3].clone()),
+        distance_km: distance_metrics.km,
+        distance_miles: distance_metrics.miles,
+        distance_3d_km: distance_metrics.km_3d,
+        distance_long_km: distance_metrics.km_long,
+        nearly_lat: distance_metrics.nearly.lat,
+        nearly_lon: distance_metrics.nearly.lon,
+        nearly_both: distance_metrics.nearly.combined,
+        delta_lat_deg: distance_metrics.deltas.map(|(lat, _)| lat),
+        delta_lon_deg: distance_metrics.deltas.map(|(_, lon)| lon),
+        colocated: distance_metrics.colocated,
+        antipodal: distance_metrics.antipodal,
+        lon_sep_deg: distance_metrics.lon_sep_deg,
+        crosses_antimeridian: distance_metrics.crosses_antimeridian,
+        bearing_deg: distance_metrics.bearing.map(|(deg, _)| deg),
+        bearing_compass: distance_metrics.bearing.map(|(_, point)| point),
+        point_a_iso: distance_metrics.iso6709.then(|| dd_to_iso6709(geo.a.lat.dd, geo.a.lon.dd)),
+        point_b_iso: distance_metrics.iso6709.then(|| dd_to_iso6709(geo.b.lat.dd, geo.b.lon.dd)),
+        geom_a: distance_metrics.wkt.as_ref().map(|wkt| wkt[0].clone()),
+        geom_b: distance_metrics.wkt.as_ref().map(|wkt| wkt[1].clone()),
+        geom_line: distance_metrics.wkt.as_ref().map(|wkt| wkt[2].clone()),
+        polyline: distance_metrics.polyline.clone(),
+        x_a: distance_metrics.mercator.map(|([x, _], _)| x),
+        y_a: distance_metrics.mercator.map(|([_, y], _)| y),
+        x_b: distance_metrics.mercator.map(|(_, [x, _])| x),
+        y_b: distance_metrics.mercator.map(|(_, [_, y])| y),
+        cell_a: distance_metrics.cells.map(|([row, col], _)| format!("{row}_{col}")),
+        cell_b: distance_metrics.cells.map(|(_, [row, col])| format!("{row}_{col}")),
+        tz_a: distance_metrics.tz.map(|(a, _)| a),
+        tz_b: distance_metrics.tz.map(|(_, b)| b),
+        suspect: distance_metrics.suspect,
+        in_expected_bbox: distance_metrics.in_expected_bbox,
+        seconds_rounded: distance_metrics.seconds_rounded,
+        roundtrip_ok: distance_metrics.roundtrip_ok,
+        repaired: distance_metrics.repaired,
+        dist_to_pole_km_a: distance_metrics.to_pole_a.map(|v| v.km),
+        bearing_to_pole_deg_a: distance_metrics.to_pole_a.map(|v| v.bearing_deg),
+        dist_to_pole_km_b: distance_metrics.to_pole_b.map(|v| v.km),
+        bearing_to_pole_deg_b: distance_metrics.to_pole_b.map(|v| v.bearing_deg),
+        datum: distance_metrics.datum.map(Datum::label),
+        format_used: distance_metrics.format_used,
+        source_file: geo.source_file.clone(),
+    }
+}
+
+// Serialize one output row, with its extra columns.
+pub(crate) fn write_output(
+    writer: &mut OutputWriter,
+    record: &OutputRecord,
+    passthrough: &mut Passthrough,
+    values: &[String],
+    columns: &[&'static str],
+) -> Result<(), AppError> {
+    if columns.is_empty() {
+        writer.write_record_with(record, passthrough, values)
+    } else {
+        writer.write_record_with(&Selected::new(record, columns)?, passthrough, values)
+    }
+}
+
+// --sort-by: writes the held rows in order (stable: ties keep the input
+// order), the first --limit ones only. `id` keeps the input order.
+pub(crate) fn write_sorted(writer: &mut OutputWriter, cli: &Cli, state: &mut RunState) -> Result<(), AppError> {
+    let Some(key) = cli.sort_by else {
+        return Ok(());
+    };
+    let mut rows = std::mem::take(&mut state.sorted);
+    match key {
+        SortKey::Distance => rows.sort_by(|a, b| a.record.distance_km.total_cmp(&b.record.distance_km)),
+        SortKey::NameA => rows.sort_by(|a, b| a.record.name_a.cmp(&b.record.name_a)),
+    }
+    rows.truncate(cli.limit.unwrap_or(usize::MAX));
+
+    for row in rows {
+        if let Err(e) = write_output(writer, &row.record, &mut state.passthrough, &row.passthrough, &cli.columns) {
+            if let AppError::ColumnNotWritten(_) = e {
+                return Err(e);
+            }
+            state.reject(cli, row.line, e)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,362 @@
+// src/parse.rs
+
+use std::collections::HashMap;
+
+use csv::StringRecord;
+use serde::Deserialize;
+
+use crate::util::validate_radius;
+use crate::input::InputError;
+use crate::geo::CoordinateKind;
+use crate::geo::dms_to_dd;
+use crate::geo::ddm_to_dd;
+use crate::geo::repair_swapped_marks;
+use crate::geo::parse_number;
+use crate::geo::normalize_coordinate;
+
+use crate::cli::Cli;
+use crate::cli::InputFormat;
+use crate::cli::EmptyCells;
+use crate::error::AppError;
+
+/* ---------------- CONSTANTES ---------------- */
+
+// Required CSV headers (order-independent).
+pub(crate) const REQUIRED_HEADERS: &[&str] = &[
+    "name_a",
+    "lat_a",
+    "lon_a",
+    "name_b",
+    "lat_b",
+    "lon_b",
+];
+
+/* ---------------- INPUT CSV STRUCTS ---------------- */
+
+// Optional elevation columns (`elev_a` / `elev_b`, meters).
+pub(crate) const ELEVATION_HEADERS: &[&str] = &["elev_a", "elev_b"];
+
+// Optional per-row sphere radius column (km), overriding --earth-radius.
+const RADIUS_HEADERS: &[&str] = &["radius_km"];
+
+// Every column read from a pair input.
+pub(crate) const KNOWN_HEADERS: &[&[&str]] = &[REQUIRED_HEADERS, ELEVATION_HEADERS, RADIUS_HEADERS];
+
+// Raw input row: coordinates are parsed according to the input format.
+#[derive(Debug, Deserialize)]
+pub(crate) struct InputString {
+    pub(crate) name_a: String,
+    pub(crate) lat_a: String,
+    pub(crate) lon_a: String,
+    pub(crate) name_b: String,
+    pub(crate) lat_b: String,
+    pub(crate) lon_b: String,
+    #[serde(default)]
+    elev_a: Option<String>,
+    #[serde(default)]
+    elev_b: Option<String>,
+    #[serde(default)]
+    radius_km: Option<String>,
+}
+
+// Single-point input row (--cross-group); the group column is read by name.
+pub(crate) const POINT_HEADERS: &[&str] = &["name", "lat", "lon"];
+
+#[derive(Debug, Deserialize)]
+struct InputPoint {
+    name: String,
+    lat: String,
+    lon: String,
+}
+
+// Start point, bearing and distance of the --destination mode.
+pub(crate) const DESTINATION_HEADERS: &[&str] = &["lat_a", "lon_a", "bearing", "distance_km"];
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct InputDestination {
+    #[serde(default)]
+    pub(crate) name_a: String,
+    pub(crate) lat_a: String,
+    pub(crate) lon_a: String,
+    pub(crate) bearing: String,
+    pub(crate) distance_km: String,
+}
+
+// Parsed point of a single-point input (--cross-group).
+#[derive(Debug, Clone)]
+pub(crate) struct ParsedPoint {
+    pub(crate) line: usize,        // input line number
+    pub(crate) name: String,
+    pub(crate) lat_in: String,     // original input strings
+    pub(crate) lon_in: String,
+    pub(crate) lat: f64,           // decimal degrees
+    pub(crate) lon: f64,
+}
+
+/* ---------------- PARSING ---------------- */
+
+// Parses a LAT,LON command-line point in the given notation.
+// Both values are checked against the bounds, DD included.
+pub(crate) fn parse_point_arg(value: &str, field: &'static str, format: InputFormat) -> Result<(f64, f64), AppError> {
+    let invalid = || AppError::InvalidPointArg { field, value: value.to_string() };
+    let coordinate = |raw: &str, kind| {
+        parse_coordinate(raw, field, kind, format, EmptyCells::Error, 0).map_err(|_| invalid())
+    };
+    let (lat, lon) = value.split_once(',').ok_or_else(invalid)?;
+    Ok((coordinate(lat, CoordinateKind::Latitude)?, coordinate(lon, CoordinateKind::Longitude)?))
+}
+
+// Error of an unreadable input row: a row with a wrong number of fields is
+// reported as such, anything else as an input error.
+pub(crate) fn read_error(error: InputError, line: usize) -> AppError {
+    match error.unequal_lengths() {
+        Some((expected, found)) => AppError::RaggedRow { line, expected, found },
+        None => AppError::from(error),
+    }
+}
+
+// Parses one row of a single-point input.
+pub(crate) fn parse_point(
+    record: &StringRecord,
+    headers: &StringRecord,
+    format: InputFormat,
+    empty: EmptyCells,
+    line: usize,
+) -> Result<ParsedPoint, AppError> {
+    let p: InputPoint = record.deserialize(Some(headers))
+        .map_err(|_| AppError::MixedCoordinateFormat { line, expected: format.label() })?;
+
+    let lat = parse_coordinate(&p.lat, "lat", CoordinateKind::Latitude, format, empty, line)?;
+    let lon = parse_coordinate(&p.lon, "lon", CoordinateKind::Longitude, format, empty, line)?;
+
+    Ok(ParsedPoint { line, name: p.name, lat_in: p.lat, lon_in: p.lon, lat, lon })
+}
+
+// Rejects every header that is neither expected nor allowed by --allow-column.
+// All offending columns are reported at once.
+pub(crate) fn check_extra_headers(headers: &StringRecord, expected: &[&[&str]], cli: &Cli) -> Result<(), AppError> {
+    let unexpected: Vec<String> = headers
+        .iter()
+        .filter(|h| !expected.iter().any(|set| set.contains(h)))
+        .filter(|h| !cli.allow_column.iter().any(|a| a == h))
+        .map(str::to_string)
+        .collect();
+
+    if unexpected.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::UnexpectedHeaderFields(unexpected))
+    }
+}
+
+// Renames the SOURCE columns of `--map TARGET=SOURCE` to their TARGET name,
+// so that rows deserialize as if the file used the expected headers.
+pub(crate) fn apply_header_map(headers: &mut StringRecord, map: &[(String, String)]) -> Result<(), AppError> {
+    for (target, source) in map {
+        if headers.iter().any(|h| h == target) {
+            return Err(AppError::HeaderMapConflict {
+                target: target.clone(),
+                column: source.clone(),
+            });
+        }
+        if !headers.iter().any(|h| h == source) {
+            return Err(AppError::MissingHeaderField(format!("{source}' (mapped to '{target}")));
+        }
+        *headers = headers
+            .iter()
+            .map(|h| if h == source { target.as_str() } else { h })
+            .collect();
+    }
+    Ok(())
+}
+
+// --lonlat: each lat_*/lon_* column is read under the other name.
+pub(crate) fn swap_lonlat_headers(headers: &mut StringRecord) {
+    const PAIRS: [(&str, &str); 3] = [("lat_a", "lon_a"), ("lat_b", "lon_b"), ("lat", "lon")];
+    *headers = headers
+        .iter()
+        .map(|h| {
+            PAIRS
+                .iter()
+                .find_map(|&(lat, lon)| match h {
+                    _ if h == lat => Some(lon),
+                    _ if h == lon => Some(lat),
+                    _ => None,
+                })
+                .unwrap_or(h)
+        })
+        .collect();
+}
+
+// Elevations of A and B (meters) of a row: an empty cell is None, otherwise
+// a finite number (Unicode minus accepted, as for the DD values).
+pub(crate) fn parse_elevations(r: &InputString, line: usize) -> Result<(Option<f64>, Option<f64>), AppError> {
+    let parse = |raw: &Option<String>, field: &'static str| {
+        let Some(raw) = raw.as_deref().map(str::trim).filter(|v| !v.is_empty()) else {
+            return Ok(None);
+        };
+        match parse_number(raw) {
+            Ok(elev) if elev.is_finite() => Ok(Some(elev)),
+            _ => Err(AppError::InvalidElevation { line, field, value: raw.to_string() }),
+        }
+    };
+    Ok((parse(&r.elev_a, "elev_a")?, parse(&r.elev_b, "elev_b")?))
+}
+
+// Sphere radius (km) of a row: an empty cell is None (--earth-radius),
+// otherwise a positive, finite number.
+pub(crate) fn parse_row_radius(r: &InputString, line: usize) -> Result<Option<f64>, AppError> {
+    let Some(raw) = r.radius_km.as_deref().map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    match parse_number(raw).map(validate_radius) {
+        Ok(Ok(radius)) => Ok(Some(radius)),
+        _ => Err(AppError::InvalidRowRadius { line, value: raw.to_string() }),
+    }
+}
+
+// Coordinate fields of a row, in input order.
+pub(crate) const COORD_FIELDS: [(&str, CoordinateKind); 4] = [
+    ("lat_a", CoordinateKind::Latitude),
+    ("lon_a", CoordinateKind::Longitude),
+    ("lat_b", CoordinateKind::Latitude),
+    ("lon_b", CoordinateKind::Longitude),
+];
+
+// Parses the four coordinates of a row (lat_a, lon_a, lat_b, lon_b)
+// to decimal degrees, according to the input format.
+// The first invalid field is reported with the line number.
+// With `repair`, an invalid DMS / DDM value with swapped degree and minute
+// marks is read again with the marks in order; true when it happened.
+// Valid DMS / DDM values go through the cache (DD values are cheaper to
+// parse than to look up). DD values out of bounds are errors unless
+// `keep_out_of_range` (--sanity-check).
+pub(crate) fn parse_coordinates(
+    r: &InputString,
+    format: InputFormat,
+    empty: EmptyCells,
+    repair: bool,
+    keep_out_of_range: bool,
+    cache: &mut ParseCache,
+    line: usize,
+) -> Result<([f64; 4], bool), AppError> {
+    let parse = if keep_out_of_range { parse_unchecked_coordinate } else { parse_coordinate };
+    let values = [&r.lat_a, &r.lon_a, &r.lat_b, &r.lon_b];
+    let mut dd = [0.0; 4];
+    let mut repaired = false;
+    let cached = !matches!(format, InputFormat::Dd);
+
+    for ((value, (field, kind)), out) in values.into_iter().zip(COORD_FIELDS).zip(&mut dd) {
+        if cached && let Some(hit) = cache.get(value, kind) {
+            *out = hit;
+            continue;
+        }
+        *out = match parse(value, field, kind, format, empty, line) {
+            Err(e) if repair && cached => {
+                let fixed = repair_swapped_marks(value).ok_or(e)?;
+                repaired = true;
+                parse(&fixed, field, kind, format, empty, line)?
+            }
+            result => {
+                let parsed = result?;
+                if cached {
+                    cache.insert(value.clone(), kind, parsed);
+                }
+                parsed
+            }
+        };
+    }
+
+    Ok((dd, repaired))
+}
+
+// Number of values kept per axis and generation by the parse cache.
+pub(crate) const PARSE_CACHE_SIZE: usize = 4096;
+
+// Decimal degrees of the DMS / DDM values already parsed, keyed on the raw
+// string and the axis: catalogs repeat the same coordinates (same city)
+// thousands of times. Bounded and about LRU: when the current generation is
+// full it becomes the previous one and the older values are dropped; a value
+// found in the previous generation moves back to the current one.
+#[derive(Debug, Default)]
+pub(crate) struct ParseCache {
+    current: [HashMap<String, f64>; 2],   // latitudes, longitudes
+    previous: [HashMap<String, f64>; 2],
+}
+
+impl ParseCache {
+    fn slot(kind: CoordinateKind) -> usize {
+        match kind {
+            CoordinateKind::Latitude => 0,
+            CoordinateKind::Longitude => 1,
+        }
+    }
+
+    pub(crate) fn get(&mut self, raw: &str, kind: CoordinateKind) -> Option<f64> {
+        let slot = Self::slot(kind);
+        if let Some(&dd) = self.current[slot].get(raw) {
+            return Some(dd);
+        }
+        let (raw, dd) = self.previous[slot].remove_entry(raw)?;
+        self.insert(raw, kind, dd);
+        Some(dd)
+    }
+
+    pub(crate) fn insert(&mut self, raw: String, kind: CoordinateKind, dd: f64) {
+        let slot = Self::slot(kind);
+        if self.current[slot].len() >= PARSE_CACHE_SIZE {
+            self.previous[slot] = std::mem::take(&mut self.current[slot]);
+        }
+        self.current[slot].insert(raw, dd);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.current.iter().chain(&self.previous).map(HashMap::len).sum()
+    }
+}
+
+// Parses one coordinate field to decimal degrees and checks it against the
+// latitude / longitude bounds with `normalize_coordinate`, whatever the
+// format: a DD value out of range is an invalid value like a DMS one.
+pub(crate) fn parse_coordinate(
+    value: &str,
+    field: &'static str,
+    kind: CoordinateKind,
+    format: InputFormat,
+    empty: EmptyCells,
+    line: usize,
+) -> Result<f64, AppError> {
+    let dd = parse_unchecked_coordinate(value, field, kind, format, empty, line)?;
+    normalize_coordinate(dd, kind).map_err(|_| AppError::OutOfRange { line, field, value: dd })
+}
+
+// Parses one coordinate field to decimal degrees, according to the input
+// format, without the bounds check of DD values (--sanity-check flags them).
+// An empty (or blank) cell is handled by the --empty policy before any parsing.
+fn parse_unchecked_coordinate(
+    value: &str,
+    field: &'static str,
+    kind: CoordinateKind,
+    format: InputFormat,
+    empty: EmptyCells,
+    line: usize,
+) -> Result<f64, AppError> {
+    if value.trim().is_empty() {
+        return match empty {
+            EmptyCells::Zero => Ok(0.0),
+            EmptyCells::Error | EmptyCells::Skip => Err(AppError::EmptyCoordinate { line, field }),
+        };
+    }
+    Ok(match format {
+        // `inf` / `NaN` parse as f64: rejected like in dms_to_dd / ddm_to_dd.
+        InputFormat::Dd => parse_number(value.trim())
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| AppError::InvalidDd { line, field, value: value.to_string() })?,
+        InputFormat::Dms => dms_to_dd(value, kind)
+            .map_err(|source| AppError::InvalidDms { line, source })?,
+        InputFormat::Ddm => ddm_to_dd(value, kind)
+            .map_err(|source| AppError::InvalidDdm { line, source })?,
+    })
+}
@@ -0,0 +1,2876 @@
+// src/tests.rs
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use csv::{StringRecord, Writer};
+use serde::Serialize;
+
+use crate::util::HaversineError;
+use crate::util::GeoTolerance;
+use crate::util::NearlyMode;
+use crate::util::round;
+use crate::util::round_with;
+use crate::util::RoundingMode;
+use crate::util::haversine_with_radius;
+use crate::util::check_earth_distance;
+use crate::util::EARTH_RADIUS_KM;
+use crate::util::compute_nearly;
+use crate::util::is_antipodal;
+use crate::util::longitude_separation;
+use crate::util::crosses_antimeridian;
+use crate::util::nautical_tz;
+use crate::util::distance_3d;
+use crate::util::long_arc_km;
+use crate::util::initial_bearing;
+use crate::util::compass_point;
+use crate::util::arc_is_degenerate;
+use crate::util::intermediate_point;
+use crate::util::to_pole;
+use crate::util::Pole;
+use crate::util::BoundingBox;
+use crate::util::bbox_contains;
+use crate::util::grid_cell;
+use crate::util::haversine;
+use crate::util::haversine_batch;
+use crate::util::haversine_rad;
+use crate::util::haversine_3d;
+use crate::util::destination_point;
+use crate::util::checked_distance;
+use crate::util::GEO_PRECISION;
+
+use crate::audit::FormatReport;
+use crate::audit::PrecisionAudit;
+
+use crate::fields::CsvFields;
+
+use crate::fixup::Fixups;
+use crate::fixup::FixupError;
+
+use crate::input::InputError;
+
+use crate::geo::CoordinateKind;
+use crate::geo::dd_to_dms_with;
+use crate::geo::DmsSymbols;
+use crate::geo::dd_to_ddm;
+use crate::geo::dd_to_iso6709;
+use crate::geo::to_wkt_linestring;
+use crate::geo::to_wkt_point;
+use crate::geo::to_web_mercator;
+use crate::geo::Locale;
+use crate::geo::encoding::encode_polyline;
+use crate::geo::dms_to_dd;
+use crate::geo::ddm_to_dd;
+use crate::geo::dms_seconds_decimals;
+use crate::geo::repair_swapped_marks;
+use crate::geo::parse_number;
+use crate::geo::normalize_coordinate;
+use crate::geo::DmsError;
+use crate::geo::DdmError;
+use crate::geo::CoordField;
+use crate::geo::CoordError;
+use crate::geo::ParsedCoord;
+use crate::geo::parse_dms;
+use crate::geo::parse_ddm;
+use crate::geo::dd_to_dms;
+use crate::geo::datum::nad27_to_wgs84;
+use crate::geo::WEB_MERCATOR_MAX_LAT;
+
+use super::*;
+use crate::cli::*;
+use crate::parse::*;
+use crate::output::*;
+use crate::modes::*;
+
+/* --- Helpers --------------------*/
+
+// Creates an empty scratch directory for one test.
+fn fixture_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("ektaon_test_{}_{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("cannot create fixture dir");
+    dir
+}
+
+// Runs the whole pipeline on an in-memory input and returns the output file content.
+fn run_fixture(name: &str, input: &str, args: &[&str]) -> Result<String, AppError> {
+    run_in(&fixture_dir(name), input, args)
+}
+
+// Same as `run_fixture`, in a directory prepared by the caller.
+fn run_in(dir: &std::path::Path, input: &str, args: &[&str]) -> Result<String, AppError> {
+    let input_path = dir.join("input.csv");
+    let output_path = dir.join("output");
+    std::fs::write(&input_path, input)?;
+
+    let mut argv = vec![
+        "ektaon".to_string(),
+        "-i".to_string(),
+        input_path.display().to_string(),
+        "-o".to_string(),
+        output_path.display().to_string(),
+    ];
+    argv.extend(args.iter().map(|a| a.to_string()));
+
+    run(&Cli::parse_from(argv))?;
+    Ok(std::fs::read_to_string(&output_path)?)
+}
+
+const DD_FIXTURE: &str = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+    Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+    Eiffel,48.858056,2.2945,Copy,48.858056,2.2945\n";
+
+/* --- round() --------------------*/
+#[test]
+fn test_round_basic() {
+    assert_eq!(round(1.23456, 2), 1.23);
+    assert_eq!(round(1.23556, 2), 1.24);
+    assert_eq!(round(-1.23456, 3), -1.235);
+}
+
+#[test]
+fn test_round_half_even() -> Result<(), AppError> {
+    let even = RoundingMode::HalfEven;
+    assert_eq!(round_with(2.5, 0, even), 2.0);
+    assert_eq!(round_with(3.5, 0, even), 4.0);
+    assert_eq!(round_with(-2.5, 0, even), -2.0);
+    assert_eq!(round_with(0.125, 2, even), 0.12);
+    assert_eq!(round_with(0.375, 2, even), 0.38);
+    assert_eq!(round_with(1.23556, 2, even), 1.24);
+
+    assert_eq!(round_with(2.5, 0, RoundingMode::HalfUp), 3.0);
+    assert_eq!(round_with(0.125, 2, RoundingMode::HalfUp), 0.13);
+
+    let out = run_fixture("half_even", DD_FIXTURE, &["-f", "dd", "--rounding", "half-even"])?;
+    assert!(out.contains(",392.93,244.16,"));
+    Ok(())
+}
+
+/* --- DMS --------------------*/
+#[test]
+fn test_dms_latitude() {
+    let v = dms_to_dd("48°51'29\"N", CoordinateKind::Latitude).unwrap();
+    let v = round(v, 6);
+    assert_eq!(v, 48.858056);
+}
+
+#[test]
+fn test_dms_longitude_west() {
+    let v = dms_to_dd("2°17'40\"W", CoordinateKind::Longitude).unwrap();
+    let v = round(v, 6);
+    assert_eq!(v, -2.294444);
+}
+
+#[test]
+fn test_dms_longitude_ouest_fr() {
+    let v = dms_to_dd("2°17'40\"O", CoordinateKind::Longitude).unwrap();
+    let v = round(v, 6);
+    assert_eq!(v, -2.294444);
+}
+
+#[test]
+fn test_dms_invalid_direction() {
+    assert!(dms_to_dd("48°51'29\"X", CoordinateKind::Latitude).is_err());
+}
+
+#[test]
+fn test_dms_direction_single_letter() {
+    let lat = CoordinateKind::Latitude;
+    let dir_error = |input| matches!(dms_to_dd(input, lat), Err(DmsError::InvalidField { field: CoordField::Dir }));
+    assert!(dir_error("48°51'29\"North"));
+    assert!(dir_error("48°51'29\"NE"));
+    assert!(dir_error("48°51'29\" N x"));
+    assert_eq!(round(dms_to_dd("48°51'29\" n ", lat).unwrap(), 6), 48.858056);
+}
+
+#[test]
+fn test_dms_leading_direction() {
+    let lat = CoordinateKind::Latitude;
+    let lon = CoordinateKind::Longitude;
+    assert_eq!(dms_to_dd("N48°51'29\"", lat).unwrap(), dms_to_dd("48°51'29\"N", lat).unwrap());
+    assert_eq!(round(dms_to_dd("W2°17'40\"", lon).unwrap(), 6), -2.294444);
+    assert_eq!(round(dms_to_dd("W002°17'40\"", lon).unwrap(), 6), -2.294444);
+    assert_eq!(round(dms_to_dd(" s 33° 51′ 24″", lat).unwrap(), 6), -33.856667);
+
+    // Both positions, or none.
+    let dir_error = |input, kind| matches!(dms_to_dd(input, kind), Err(DmsError::InvalidField { field: CoordField::Dir }));
+    assert!(dir_error("N48°51'29\"N", lat));
+    assert!(dir_error("W2°17'40\"E", lon));
+    assert!(dir_error("48°51'29\"", lat));
+}
+
+#[test]
+fn test_dms_invalid_format() {
+    assert!(dms_to_dd("48.858056", CoordinateKind::Latitude).is_err());
+}
+
+#[test]
+fn test_dms_without_seconds() {
+    let lat = CoordinateKind::Latitude;
+    assert_eq!(dms_to_dd("48°51'N", lat).unwrap(), 48.85);
+    assert_eq!(dms_to_dd("48°51'N", lat).unwrap(), dms_to_dd("48°51'0\"N", lat).unwrap());
+    assert_eq!(parse_dms("2°17'W", CoordinateKind::Longitude).unwrap().sec, 0.0);
+
+    // Decimal minutes without seconds: the DDM reading.
+    assert_eq!(dms_to_dd("48°51.5'N", lat).unwrap(), ddm_to_dd("48°51.5'N", lat).unwrap());
+    assert_eq!(round(dms_to_dd("2°17.6667'W", CoordinateKind::Longitude).unwrap(), 6), -2.294445);
+    assert!(matches!(dms_to_dd("48°51.5'", lat), Err(DmsError::InvalidFormat)));
+    // DDM itself still requires decimal minutes without seconds.
+    assert!(ddm_to_dd("48°51'29\"N", lat).is_err());
+    assert!(matches!(dms_to_dd("48°61'N", lat), Err(DmsError::InvalidCoord(CoordError::InvalidMinutes { .. }))));
+    assert!(matches!(dms_to_dd("48°x'N", lat), Err(DmsError::InvalidField { field: CoordField::Min })));
+}
+
+#[test]
+fn test_dms_fractional_deg_and_min() {
+    let lat = CoordinateKind::Latitude;
+    let field_error = |input, field| matches!(dms_to_dd(input, lat), Err(DmsError::InvalidField { field: f }) if f == field);
+    assert!(field_error("48.5°51'29\"N", CoordField::Deg));
+    assert!(field_error("48°51.5'29\"N", CoordField::Min));
+    assert!(field_error("48.5°51'N", CoordField::Deg));
+    // Integral values written with decimals are still integers.
+    assert_eq!(dms_to_dd("48.0°51'0\"N", lat).unwrap(), 48.85);
+
+    assert!(matches!(ddm_to_dd("48.5°51.4'N", lat), Err(DdmError::InvalidField { field: CoordField::Deg })));
+}
+
+#[test]
+fn test_dms_missing_deg_field() {
+    assert!(dms_to_dd("°0'0\"N", CoordinateKind::Latitude).is_err());
+}
+
+#[test]
+fn test_dms_invalid_minutes_field() {
+    assert!(dms_to_dd("48°V'0\"N", CoordinateKind::Latitude).is_err());
+}
+
+#[test]
+fn test_dms_invalid_seconds_field() {
+    assert!(dms_to_dd("48°0'O\"N", CoordinateKind::Latitude).is_err());
+}
+
+#[test]
+fn test_dms_invalid_latitude_value() {
+    assert!(matches!(
+        dms_to_dd("91°0'0\"N", CoordinateKind::Latitude),
+        Err(DmsError::InvalidCoord(_))
+    ));
+}
+
+#[test]
+fn test_dms_invalid_minutes_value() {
+    assert!(matches!(
+        dms_to_dd("48°61'57\"N", CoordinateKind::Latitude),
+        Err(DmsError::InvalidCoord(_))
+    ));
+}
+
+#[test]
+fn test_dms_invalid_seconds_value() {
+    assert!(matches!(
+        dms_to_dd("48°61'57\"N", CoordinateKind::Latitude),
+        Err(DmsError::InvalidCoord(_))
+    ));
+}
+
+#[test]
+fn test_dms_unicode_symbols() {
+    let v = dms_to_dd("48°51′29″N", CoordinateKind::Latitude).unwrap();
+    let v = round(v, 6);
+    assert_eq!(v, 48.858056);
+}
+
+#[test]
+fn test_dms_letter_separators() {
+    let lat = CoordinateKind::Latitude;
+    for input in ["48d51m29sN", "48D51M29sN", "48d 51m 29.0s N", "N48d51m29s"] {
+        assert_eq!(round(dms_to_dd(input, lat).unwrap(), 6), 48.858056, "{input}");
+    }
+    assert_eq!(round(dms_to_dd("33d51m25sS", lat).unwrap(), 6), -33.856944);
+    assert_eq!(round(dms_to_dd("2d20m55sW", CoordinateKind::Longitude).unwrap(), 6), -2.348611);
+
+    // An uppercase `S` is the south direction, never the seconds separator.
+    assert!(matches!(dms_to_dd("48°51'29S", lat), Err(DmsError::InvalidFormat)));
+    assert!(matches!(dms_to_dd("48D51M29SN", lat), Err(DmsError::InvalidFormat)));
+
+    // Still malformed: a letter in a field, a missing field, no direction.
+    assert!(matches!(dms_to_dd("4xd51m29sN", lat), Err(DmsError::InvalidField { field: CoordField::Deg })));
+    assert!(dms_to_dd("48d51m29N", lat).is_err());
+    assert!(dms_to_dd("48dm29sN", lat).is_err());
+    assert!(dms_to_dd("48d51m29s", lat).is_err());
+    assert!(dms_to_dd("d51m29sN", lat).is_err());
+    assert!(dms_to_dd("48d51m29sNE", lat).is_err());
+}
+
+#[test]
+fn test_dms_spaces() {
+    let v = dms_to_dd("48° 51 ' 29\" N", CoordinateKind::Latitude).unwrap();
+    let v = round(v, 6);
+    assert_eq!(v, 48.858056);
+}
+
+#[test]
+fn test_dms_latitude_90_is_valid() {
+    let v = dms_to_dd("90°0'0\"N", CoordinateKind::Latitude).unwrap();
+    assert_eq!(v, 90.0);
+}
+
+#[test]
+fn test_dms_longitude_180_is_valid() {
+    let v = dms_to_dd("180°0'0\"E", CoordinateKind::Longitude).unwrap();
+    assert_eq!(v, 180.0);
+}
+
+#[test]
+fn test_dms_invalid_format_vs_invalid_value() {
+    // Format invalid
+    assert!(matches!(
+        dms_to_dd("48.858056", CoordinateKind::Latitude),
+        Err(DmsError::InvalidFormat)
+    ));
+
+    // Value invalid (minutes > 60)
+    assert!(matches!(
+        dms_to_dd("48°61'0\"N", CoordinateKind::Latitude),
+        Err(DmsError::InvalidCoord(_))
+    ));
+
+    // Field invalid
+    assert!(matches!(
+        dms_to_dd("48c°57'0\"N", CoordinateKind::Latitude),
+        Err(DmsError::InvalidField { field: CoordField::Deg })
+    ));
+
+}
+
+/* --- DMS formatting --------------------*/
+
+#[test]
+fn test_dd_to_dms_default_precision() {
+    assert_eq!(dd_to_dms(48.858056, CoordinateKind::Latitude, 2, Locale::En, RoundingMode::HalfUp), "48°51'29.00\"N");
+    assert_eq!(dd_to_dms(-2.294444, CoordinateKind::Longitude, 2, Locale::En, RoundingMode::HalfUp), "2°17'40.00\"W");
+}
+
+#[test]
+fn test_dd_to_dms_locale_fr() {
+    assert_eq!(dd_to_dms(-2.294444, CoordinateKind::Longitude, 2, Locale::Fr, RoundingMode::HalfUp), "2°17'40.00\"O");
+    assert_eq!(dd_to_dms(2.294444, CoordinateKind::Longitude, 2, Locale::Fr, RoundingMode::HalfUp), "2°17'40.00\"E");
+    assert_eq!(dd_to_dms(-48.858056, CoordinateKind::Latitude, 2, Locale::Fr, RoundingMode::HalfUp), "48°51'29.00\"S");
+}
+
+#[test]
+fn test_dd_to_dms_unicode_symbols() -> Result<(), AppError> {
+    let lat = CoordinateKind::Latitude;
+    let dms = dd_to_dms_with(48.858056, lat, 2, Locale::En, RoundingMode::HalfUp, DmsSymbols::Unicode);
+    assert_eq!(dms, "48°51′29.00″N");
+    assert_eq!(dms_to_dd(&dms, lat).unwrap(), dms_to_dd("48°51'29.00\"N", lat).unwrap());
+    let ddm = dd_to_ddm(48.85, lat, 1, Locale::En, RoundingMode::HalfUp, DmsSymbols::Unicode);
+    assert_eq!(ddm, "48°51.0′N");
+
+    // Unicode output needs no quoting.
+    let out = run_fixture("dms_unicode", DD_FIXTURE, &["-f", "dd", "--dms-symbols", "unicode"])?;
+    assert!(out.lines().nth(1).unwrap().contains(",48°51′24.12″N,2°20′54.96″E,"));
+    Ok(())
+}
+
+#[test]
+fn test_locale_fr_round_trip() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Brest,48°23'24.00\"N,4°29'24.00\"O,Paris,48°51'24.00\"N,2°21'0.00\"E\n";
+    let out = run_fixture("locale_fr", input, &["-f", "dms", "--locale", "fr"])?;
+    assert!(out.contains(",\"4°29'24.00\"\"O\","));
+    assert!(!out.contains("W\""));
+    Ok(())
+}
+
+#[test]
+fn test_dd_to_dms_precision() {
+    assert_eq!(dd_to_dms(2.294481, CoordinateKind::Longitude, 3, Locale::En, RoundingMode::HalfUp), "2°17'40.132\"E");
+    assert_eq!(dd_to_dms(2.294481, CoordinateKind::Longitude, 0, Locale::En, RoundingMode::HalfUp), "2°17'40\"E");
+}
+
+#[test]
+fn test_dd_to_dms_seconds_carry() {
+    // 4.833333° is 4°49'59.9988", which used to print as 4°49'60.00".
+    assert_eq!(dd_to_dms(4.833333, CoordinateKind::Longitude, 2, Locale::En, RoundingMode::HalfUp), "4°50'0.00\"E");
+    // 59.999" carries into minutes, then minutes into degrees.
+    assert_eq!(dd_to_dms(10.0 + 59.0 / 60.0 + 59.999 / 3600.0, CoordinateKind::Latitude, 2, Locale::En, RoundingMode::HalfUp), "11°0'0.00\"N");
+}
+
+#[test]
+fn test_dd_to_dms_carry_at_bounds() {
+    // 89.9999999° is 89°59'59.99964": both carries end on the pole.
+    let lat = dd_to_dms(89.9999999, CoordinateKind::Latitude, 2, Locale::En, RoundingMode::HalfUp);
+    assert_eq!(lat, "90°0'0.00\"N");
+    let lon = dd_to_dms(-179.9999999, CoordinateKind::Longitude, 2, Locale::En, RoundingMode::HalfUp);
+    assert_eq!(lon, "180°0'0.00\"W");
+
+    for precision in 0..=4 {
+        let s = dd_to_dms(179.9999999, CoordinateKind::Longitude, precision, Locale::En, RoundingMode::HalfUp);
+        assert!(!s.contains("60"), "{s} at precision {precision}");
+    }
+    // Enough decimals to keep the value: no carry.
+    assert_eq!(dd_to_dms(179.9999999, CoordinateKind::Longitude, 4, Locale::En, RoundingMode::HalfUp), "179°59'59.9996\"E");
+}
+
+#[test]
+fn test_parse_cache_bounded() {
+    let lat = CoordinateKind::Latitude;
+    let mut cache = ParseCache::default();
+    cache.insert("48°N".to_string(), lat, 48.0);
+    assert_eq!(cache.get("48°N", lat), Some(48.0));
+    assert_eq!(cache.get("48°N", CoordinateKind::Longitude), None);
+
+    // Still found one generation later, and kept by that hit.
+    for i in 0..PARSE_CACHE_SIZE {
+        cache.insert(format!("{i}'"), lat, 0.0);
+    }
+    assert_eq!(cache.get("48°N", lat), Some(48.0));
+    // Dropped two generations after its last use.
+    for i in 0..2 * PARSE_CACHE_SIZE {
+        cache.insert(format!("{i}\""), lat, 0.0);
+    }
+    assert_eq!(cache.get("48°N", lat), None);
+    assert!(cache.len() <= 2 * PARSE_CACHE_SIZE);
+}
+
+#[test]
+fn test_parse_cache_repeated_values() -> Result<(), AppError> {
+    let row = "Paris,48°51'24\"N,2°20'55\"E,Lyon,45°45'0\"N,4°50'0\"E\n";
+    let input = format!("name_a,lat_a,lon_a,name_b,lat_b,lon_b\n{row}{row}");
+    let out = run_fixture("parse_cache", &input, &["-f", "dms"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows[1].split_once(',').map(|(_, r)| r), rows[2].split_once(',').map(|(_, r)| r));
+    Ok(())
+}
+
+#[test]
+fn test_dd_to_dms_zero() {
+    let (lat, lon) = (CoordinateKind::Latitude, CoordinateKind::Longitude);
+    // Equator and prime meridian: no hemisphere, whatever the zero sign.
+    for zero in [0.0, -0.0] {
+        assert_eq!(dd_to_dms(zero, lat, 2, Locale::En, RoundingMode::HalfUp), "0°0'0.00\"");
+        assert_eq!(dd_to_dms(zero, lon, 2, Locale::Fr, RoundingMode::HalfUp), "0°0'0.00\"");
+        assert_eq!(dd_to_ddm(zero, lon, 4, Locale::En, RoundingMode::HalfUp, DmsSymbols::Ascii), "0°0.0000'");
+    }
+    // A value rounded to zero keeps its sign.
+    assert_eq!(dd_to_dms(-1e-9, lat, 2, Locale::En, RoundingMode::HalfUp), "0°0'0.00\"S");
+
+    // Read back as 0, the direction being only optional for zero.
+    assert_eq!(dms_to_dd("0°0'0.00\"", lat).unwrap(), 0.0);
+    assert_eq!(ddm_to_dd("0°0.0000'", lon).unwrap(), 0.0);
+    assert!(matches!(dms_to_dd("0°0'1\"", lat), Err(DmsError::InvalidField { field: CoordField::Dir })));
+    assert!(matches!(ddm_to_dd("0°0.5'", lon), Err(DdmError::InvalidFormat)));
+}
+
+#[test]
+fn test_dms_precision_option()-> Result<(), AppError> {
+    let out = run_fixture("dms_precision", DD_FIXTURE, &["-f", "dd", "--dms-precision", "0"])?;
+    assert!(out.contains("48°51'29\"\"N"));
+    Ok(())
+}
+
+#[test]
+fn test_dms_seconds_decimals() {
+    assert_eq!(dms_seconds_decimals("48°51'29.1234\"N"), Some(4));
+    assert_eq!(dms_seconds_decimals("48° 51′ 29″ N"), Some(0));
+    assert_eq!(dms_seconds_decimals("48.858056"), None);
+}
+
+#[test]
+fn test_seconds_rounded_flag() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Fine,48°51'29.1234\"N,2°17'40\"E,Lyon,45°45'0\"N,4°50'0\"E\n\
+        Coarse,48°51'29.12\"N,2°17'40\"E,Lyon,45°45'0\"N,4°50'0\"E\n";
+
+    let out = run_fixture("seconds_rounded", input, &["-f", "dms", "--flag-seconds-rounded"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,seconds_rounded"));
+    assert!(rows[1].ends_with(",true"));
+    assert!(rows[2].ends_with(",false"));
+
+    // Enough output decimals: nothing is lost.
+    let out = run_fixture("seconds_kept", input, &["-f", "dms", "--flag-seconds-rounded", "--dms-precision", "4"])?;
+    assert!(out.lines().skip(1).all(|row| row.ends_with(",false")));
+    Ok(())
+}
+
+#[test]
+fn test_repair_swapped_marks() -> Result<(), AppError> {
+    assert_eq!(repair_swapped_marks("48'51°29\"N").as_deref(), Some("48°51'29\"N"));
+    assert_eq!(repair_swapped_marks("N 48′51.5°").as_deref(), Some("N48°51.5'"));
+    assert_eq!(repair_swapped_marks("48°51'29\"N"), None);
+
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Swapped,48'51°29\"N,2°17'40\"E,Lyon,45°45'0\"N,4°50'0\"E\n\
+        Fine,48°51'29\"N,2°17'40\"E,Lyon,45°45'0\"N,4°50'0\"E\n";
+
+    let out = run_fixture("repair", input, &["-f", "dms", "--repair"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,repaired"));
+    // The input column keeps the original text.
+    assert!(rows[1].starts_with("1,Swapped,\"48'51°29\"\"N\",\"2°17'40\"\"E\",48.858056,"), "{}", rows[1]);
+    assert!(rows[1].ends_with(",true"));
+    assert!(rows[2].ends_with(",false"));
+
+    // Without --repair the value stays invalid.
+    let err = run_fixture("repair_strict", input, &["-f", "dms", "--strict"]).unwrap_err();
+    assert!(matches!(err, AppError::InvalidDms { line: 2, .. }), "{err}");
+    Ok(())
+}
+
+#[test]
+fn test_roundtrip_check() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Near,45.0012345,2.3486,Lyon,45.75,4.833333\n\
+        Whole,48.85,2.35,Lyon,45.75,4.75\n";
+
+    // 2 seconds decimals: up to 1.4e-6° lost on the way.
+    let out = run_fixture("roundtrip", input, &["-f", "dd", "--roundtrip-check"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,roundtrip_ok"));
+    assert!(rows[1].ends_with(",false"));
+    assert!(rows[2].ends_with(",true"));
+
+    let out = run_fixture("roundtrip_fine", input, &["-f", "dd", "--roundtrip-check", "--dms-precision", "4"])?;
+    assert!(out.lines().skip(1).all(|row| row.ends_with(",true")));
+    Ok(())
+}
+
+#[test]
+fn test_parse_dms_components() {
+    let p = parse_dms("2°17'40.5\"w", CoordinateKind::Longitude).unwrap();
+    assert_eq!((p.deg, p.min, p.sec, p.dir), (2.0, 17.0, 40.5, 'W'));
+    assert_eq!(p.dd, dms_to_dd("2°17'40.5\"W", CoordinateKind::Longitude).unwrap());
+    assert!(p.dd < 0.0);
+    assert!(matches!(parse_dms("48°51'29\"E", CoordinateKind::Latitude), Err(DmsError::InvalidCoord(_))));
+}
+
+#[test]
+fn test_invalid_direction_message() {
+    // Swapped lat/lon columns: the message names the expected axis.
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,2°20'55\"E,48°51'24\"N,Lyon,45°45'0\"N,4°50'0\"E\n";
+    let err = run_fixture("direction_message", input, &["-f", "dms", "--strict"]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Line 2: invalid DMS (invalid coord (invalid direction `E` for latitude (expected N/S)))",
+    );
+    let err = ddm_to_dd("2°17.3'N", CoordinateKind::Longitude).unwrap_err();
+    assert_eq!(err.to_string(), "invalid coord (invalid direction `N` for longitude (expected E/W/O))");
+}
+
+#[test]
+fn test_degrees_only() {
+    let (lat, lon) = (CoordinateKind::Latitude, CoordinateKind::Longitude);
+    let p = parse_dms("48°N", lat).unwrap();
+    assert_eq!((p.deg, p.min, p.sec, p.dir, p.dd), (48.0, 0.0, 0.0, 'N', 48.0));
+    assert_eq!(dms_to_dd("2°W", lon).unwrap(), -2.0);
+    assert_eq!(ddm_to_dd("48 ° n", lat).unwrap(), 48.0);
+    assert_eq!(ddm_to_dd("2°W", lon).unwrap(), -2.0);
+
+    // Degrees and direction validated as usual.
+    assert!(matches!(dms_to_dd("48.5°N", lat), Err(DmsError::InvalidField { field: CoordField::Deg })));
+    assert!(matches!(dms_to_dd("91°N", lat), Err(DmsError::InvalidCoord(CoordError::OutOfRange { .. }))));
+    assert!(matches!(
+        ddm_to_dd("2°N", lon),
+        Err(DdmError::InvalidCoord(CoordError::InvalidDirection { dir: 'N', kind: CoordinateKind::Longitude }))
+    ));
+    assert!(matches!(dms_to_dd("48°", lat), Err(DmsError::InvalidFormat)));
+}
+
+#[test]
+fn test_normalize_coordinate() {
+    let (lat, lon) = (CoordinateKind::Latitude, CoordinateKind::Longitude);
+    assert_eq!(normalize_coordinate(-90.0, lat).unwrap(), -90.0);
+    assert_eq!(normalize_coordinate(180.0, lon).unwrap(), 180.0);
+    assert!(normalize_coordinate(-0.0, lat).unwrap().is_sign_positive());
+    assert!(matches!(normalize_coordinate(90.000001, lat), Err(CoordError::OutOfRange { .. })));
+    assert!(matches!(normalize_coordinate(-180.5, lon), Err(CoordError::OutOfRange { .. })));
+    assert!(matches!(normalize_coordinate(f64::NAN, lon), Err(CoordError::InvalidDegree { .. })));
+
+    // Same bounds on the DMS path.
+    assert!(matches!(
+        dms_to_dd("90°0'0.1\"N", lat),
+        Err(DmsError::InvalidCoord(CoordError::OutOfRange { .. }))
+    ));
+    assert!(dms_to_dd("0°0'0\"S", lat).unwrap().is_sign_positive());
+}
+
+#[test]
+fn test_dd_non_finite_rejected() -> Result<(), AppError> {
+    for value in ["inf", "-Infinity", "NaN"] {
+        let res = parse_coordinate(value, "lat_a", CoordinateKind::Latitude, InputFormat::Dd, EmptyCells::Error, 2);
+        assert!(matches!(res, Err(AppError::InvalidDd { line: 2, field: "lat_a", .. })), "{value}");
+    }
+
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Nowhere,nan,2.3486,Lyon,45.75,inf\n";
+    let out = run_fixture("dd_non_finite", input, &["-f", "dd"])?;
+    assert_eq!(out.lines().count(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_unicode_minus() -> Result<(), AppError> {
+    let lon = CoordinateKind::Longitude;
+    assert_eq!(parse_coordinate("\u{2212}2.294444", "lon_a", lon, InputFormat::Dd, EmptyCells::Error, 2)?, -2.294444);
+    assert_eq!(parse_coordinate("\u{2013}2.294444", "lon_a", lon, InputFormat::Dd, EmptyCells::Error, 2)?, -2.294444);
+    assert_eq!(parse_number("\u{FF0D}1e3"), Ok(-1000.0));
+
+    // Read as an ASCII `-`: a negative degree, not an unreadable field.
+    assert!(matches!(
+        dms_to_dd("\u{2212}2°17'40\"W", lon),
+        Err(DmsError::InvalidCoord(CoordError::InvalidDegree { deg })) if deg == -2.0
+    ));
+    assert!(matches!(
+        ddm_to_dd("2°\u{2212}17.5'W", lon),
+        Err(DdmError::InvalidCoord(CoordError::InvalidMinutes { .. }))
+    ));
+    Ok(())
+}
+
+/* --- DDM --------------------*/
+
+#[test]
+fn test_parse_ddm_components() {
+    let p = parse_ddm("48°51.5'N", CoordinateKind::Latitude).unwrap();
+    assert_eq!(p, ParsedCoord { deg: 48.0, min: 51.5, sec: 0.0, dir: 'N', dd: 48.0 + 51.5 / 60.0 });
+}
+
+#[test]
+fn test_ddm_invalid_deg_field() {
+    assert!(matches!(
+        ddm_to_dd("48c°57'N", CoordinateKind::Latitude),
+        Err(DdmError::InvalidField { field: CoordField::Deg })
+    ));
+}
+
+#[test]
+fn test_ddm_invalid_minutes_value() {
+    assert!(matches!(
+        ddm_to_dd("48°61'N", CoordinateKind::Latitude),
+        Err(DdmError::InvalidCoord(_))
+    ));
+}
+
+#[test]
+fn test_ddm_invalid_format_vs_invalid_value() {
+    // Format invalid
+    assert!(matches!(
+        ddm_to_dd("48.858056", CoordinateKind::Latitude),
+        Err(DdmError::InvalidFormat)
+    ));
+
+    // Value invalid (minutes > 60)
+    assert!(matches!(
+        ddm_to_dd("48°61'N", CoordinateKind::Latitude),
+        Err(DdmError::InvalidCoord(_))
+    ));
+
+    // Minutes negative
+    assert!(matches!(
+        ddm_to_dd("48°-1'N", CoordinateKind::Latitude),
+        Err(DdmError::InvalidCoord(_))
+    ));
+
+    // Latitude out of boundaries
+    assert!(matches!(
+        ddm_to_dd("91°0'N", CoordinateKind::Latitude),
+        Err(DdmError::InvalidCoord(_))
+    ));
+
+    // Direction invalid
+    assert!(matches!(
+        ddm_to_dd("48°30'X", CoordinateKind::Latitude),
+        Err(DdmError::InvalidCoord(_))
+    ));
+}
+
+#[test]
+fn test_ddm_to_distance_integration() -> Result<(), Box<dyn std::error::Error>> {
+
+    let turing_eiffel_lat = "48° 51.492' N";
+    let turing_eiffel_lon = "2° 17.652' E";
+
+    let statue_liberty_lat = "40° 41.358' N";
+    let statue_liberty_lon = "74° 2.646' W";
+
+    let lat1 = ddm_to_dd(turing_eiffel_lat, CoordinateKind::Latitude)?;
+    let lon1 = ddm_to_dd(turing_eiffel_lon, CoordinateKind::Longitude)?;
+
+    let lat2 = ddm_to_dd(statue_liberty_lat, CoordinateKind::Latitude)?;
+    let lon2 = ddm_to_dd(statue_liberty_lon, CoordinateKind::Longitude)?;
+
+    assert!(lat1 > 0.0);
+    assert!(lon1 > 0.0);
+    assert!(lat2 > 0.0);
+    assert!(lon2 < 0.0);
+
+    let distance = haversine(lat1, lon1, lat2, lon2)?;
+
+    let distance_rounded = round(distance, 2);
+
+    assert!((distance_rounded - 5837.0).abs() < 5.0);
+
+    Ok(())
+}
+
+/* --- Append --------------------*/
+
+#[test]
+fn test_append() -> Result<(), AppError> {
+    let dir = fixture_dir("append");
+    run_in(&dir, DD_FIXTURE, &["-f", "dd"])?;
+    // No new header, ids continue, whatever the writer (here fr number format).
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\nNice,43.7,7.27,Lyon,45.75,4.833333\n";
+    let out = run_in(&dir, input, &["-f", "dd", "--append"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows.len(), 1 + 3);
+    assert!(rows[0].starts_with("id,") && rows[3].starts_with("3,Nice,"));
+    let out = run_in(&dir, DD_FIXTURE, &["-f", "dd", "--append", "--number-locale", "fr"])?;
+    let ids: Vec<&str> = out.lines().skip(1).map(|row| &row[..row.find(',').unwrap()]).collect();
+    assert_eq!(ids, ["1", "2", "3", "4", "5"]);
+
+    // Missing file: created with its header; --start-id wins.
+    let dir = fixture_dir("append_new");
+    let out = run_in(&dir, DD_FIXTURE, &["-f", "dd", "--append", "--start-id", "10"])?;
+    assert!(out.starts_with("id,") && out.lines().nth(1).unwrap().starts_with("10,"));
+
+    // JSON Lines: last id of the last line.
+    let out = run_in(&dir, DD_FIXTURE, &["-f", "dd", "--output-format", "ndjson"])?;
+    assert_eq!(out.lines().count(), 2);
+    let out = run_in(&dir, DD_FIXTURE, &["-f", "dd", "--output-format", "ndjson", "--append"])?;
+    assert!(out.lines().last().unwrap().starts_with("{\"id\":4,"));
+
+    // No id column to continue from.
+    let dir = fixture_dir("append_no_id");
+    run_in(&dir, DD_FIXTURE, &["-f", "dd", "--columns", "name_a,distance_km"])?;
+    let res = run_in(&dir, DD_FIXTURE, &["-f", "dd", "--columns", "name_a,distance_km", "--append"]);
+    assert!(matches!(res, Err(AppError::AppendId(_))));
+    Ok(())
+}
+
+#[test]
+fn test_missing_output_dir() -> Result<(), AppError> {
+    let dir = fixture_dir("output_dir");
+    let input_path = dir.join("input.csv");
+    std::fs::write(&input_path, DD_FIXTURE)?;
+    let output_path = dir.join("out/2024/output.csv");
+    let args = |extra: &[&str]| {
+        let mut argv = vec!["ektaon", "-f", "dd", "-i", input_path.to_str().unwrap(), "-o", output_path.to_str().unwrap()];
+        argv.extend(extra);
+        Cli::parse_from(argv)
+    };
+
+    let res = run(&args(&[]));
+    assert!(matches!(res, Err(AppError::MissingOutputDir(ref p)) if *p == output_path));
+    assert!(res.unwrap_err().to_string().contains("--mkdir"));
+
+    run(&args(&["--mkdir"]))?;
+    assert_eq!(std::fs::read_to_string(&output_path)?.lines().count(), 3);
+    Ok(())
+}
+
+/* --- Skip / limit --------------------*/
+
+#[test]
+fn test_skip_and_limit() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Bad,x,0,Row,0,0\n\
+        One,0,0,B,0,1\n\
+        Two,0,0,B,0,2\n\
+        Three,0,0,B,0,3\n";
+
+    let dir = fixture_dir("skip_limit");
+    let manifest = dir.join("manifest.json");
+    let out = run_in(&dir, input, &[
+        "-f", "dd", "--skip", "1", "--limit", "2", "--manifest", manifest.to_str().unwrap(),
+    ])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[1].starts_with("1,One,"));
+    assert!(rows[2].starts_with("2,Two,"));
+
+    // The skipped bad row is neither read nor invalid.
+    let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&manifest)?)?;
+    assert_eq!(manifest["rows"]["read"], 2);
+    assert_eq!(manifest["rows"]["invalid"], 0);
+
+    // Line numbers still count the skipped rows.
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\nOne,0,0,B,0,1\nBad,x,0,Row,0,0\n";
+    let err = run_fixture("skip_line", input, &["-f", "dd", "--skip", "1", "--strict"]);
+    assert!(matches!(err, Err(AppError::InvalidDd { line: 3, .. })));
+    Ok(())
+}
+
+/* --- DD --------------------*/
+
+#[test]
+fn test_dd_invalid_field_reported() {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Lyon,45.75,4.833333,Tokyo,35.68,east\n";
+
+    let err = run_fixture("dd_invalid_field", input, &["-f", "dd", "--strict"]).unwrap_err();
+    assert!(matches!(
+        &err,
+        AppError::InvalidDd { line: 3, field: "lon_b", value } if value == "east"
+    ));
+    assert_eq!(err.to_string(), "Line 3: invalid DD value `east` for lon_b");
+}
+
+#[test]
+fn test_dd_input_echoed_verbatim() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\nZero,0.0, 2.50,One,1,2\n";
+    let out = run_fixture("dd_echo", input, &["-f", "dd"])?;
+    assert!(out.lines().nth(1).unwrap().starts_with("1,Zero,0.0, 2.50,0.0,2.5,"));
+    Ok(())
+}
+
+/* --- Radius --------------------*/
+
+#[test]
+fn test_haversine_default_radius_delegates() -> Result<(), Box<dyn std::error::Error>> {
+    let earth = haversine(0.0, 0.0, 0.0, 1.0)?;
+    let explicit = haversine_with_radius(0.0, 0.0, 0.0, 1.0, EARTH_RADIUS_KM)?;
+    assert_eq!(earth, explicit);
+    Ok(())
+}
+
+#[test]
+fn test_haversine_mars_radius() -> Result<(), Box<dyn std::error::Error>> {
+    // One degree along the equator of Mars (radius 3389.5 km).
+    let d = haversine_with_radius(0.0, 0.0, 0.0, 1.0, 3389.5)?;
+    assert_eq!(round(d, 2), 59.16);
+    Ok(())
+}
+
+#[test]
+fn test_haversine_invalid_radius() {
+    for radius in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        assert!(matches!(
+            haversine_with_radius(0.0, 0.0, 0.0, 1.0, radius),
+            Err(HaversineError::InvalidRadius { .. })
+        ));
+    }
+    assert!(parse_radius("-5").is_err());
+    assert!(parse_radius("abc").is_err());
+    assert_eq!(parse_radius("1737.4"), Ok(1737.4));
+}
+
+#[test]
+fn test_haversine_batch_and_radians() -> Result<(), Box<dyn std::error::Error>> {
+    let pairs = [(48.8567, 2.3486, 45.75, 4.833333), (0.0, 0.0, 0.0, 180.0), (10.0, 20.0, 10.0, 20.0)];
+    let batch = haversine_batch(&pairs);
+    assert_eq!(batch.len(), pairs.len());
+    for (&(lat1, lon1, lat2, lon2), d) in pairs.iter().zip(batch) {
+        assert_eq!(d?, haversine(lat1, lon1, lat2, lon2)?);
+    }
+
+    let r = f64::to_radians;
+    assert_eq!(round(haversine_rad(r(48.8567), r(2.3486), r(45.75), r(4.833333))?, 2), 392.93);
+    assert!(matches!(haversine_rad(f64::NAN, 0.0, 0.0, 0.0), Err(HaversineError::InvalidDistance)));
+    Ok(())
+}
+
+/* --- Record hook --------------------*/
+
+#[test]
+fn test_record_hook_transforms_rows() -> Result<(), AppError> {
+    let dir = fixture_dir("record_hook");
+    let input_path = dir.join("input.csv");
+    let output_path = dir.join("output.csv");
+    std::fs::write(&input_path, DD_FIXTURE)?;
+    let cli = Cli::new(&input_path, &output_path, InputFormat::Dd);
+
+    let mut seen = 0;
+    let mut hook = |record: &mut OutputRecord| {
+        seen += 1;
+        record.name_a = record.name_a.to_uppercase();
+        record.distance_km = round(record.distance_km, 0);
+    };
+    run_with_hook(&cli, Some(&mut hook))?;
+
+    assert_eq!(seen, 2);
+    let out = std::fs::read_to_string(&output_path)?;
+    let row = out.lines().nth(1).unwrap();
+    assert!(row.starts_with("1,PARIS,"));
+    assert!(row.contains(",393.0,244.16,"));
+    Ok(())
+}
+
+#[test]
+fn test_cli_builder_matches_command_line() {
+    let built = Cli::new("in.csv", "out.csv", InputFormat::Dms)
+        .add_input("more.csv")
+        .strict(true)
+        .tsv(true)
+        .passthrough(true)
+        .reverse(true);
+    let parsed = Cli::parse_from([
+        "ektaon", "-i", "in.csv", "-i", "more.csv", "-o", "out.csv", "-f", "dms",
+        "--strict", "--tsv", "--passthrough", "--reverse",
+    ]);
+    assert_eq!(serde_json::to_value(&built).unwrap(), serde_json::to_value(&parsed).unwrap());
+
+    // Everything else at its default.
+    let plain = Cli::parse_from(["ektaon", "-i", "in.csv", "-o", "out.csv", "-f", "dd"]);
+    let built = Cli::new("in.csv", "out.csv", InputFormat::Dd);
+    assert_eq!(serde_json::to_value(&built).unwrap(), serde_json::to_value(&plain).unwrap());
+}
+
+#[test]
+fn test_row_radius_column() -> Result<(), AppError> {
+    // One degree along the equator of Mars, of the Moon, then the default radius.
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b,radius_km\n\
+        Mars,0,0,Mars,0,1,3389.5\n\
+        Moon,0,0,Moon,0,1,1737.4\n\
+        Earth,0,0,Earth,0,1,\n\
+        Bad,0,0,Bad,0,1,-1\n";
+
+    let out = run_fixture("row_radius", input, &["-f", "dd"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows.len(), 4);
+    assert!(rows[1].contains(",59.16,"));
+    assert!(rows[2].contains(",30.32,"));
+    assert!(rows[3].contains(",111.19,"));
+
+    let strict = run_fixture("row_radius_strict", input, &["-f", "dd", "--strict"]);
+    assert!(matches!(strict, Err(AppError::InvalidRowRadius { line: 5, .. })));
+    // Text and negative values get the radius error, not a format error.
+    for value in ["3389.5 km", "\u{2212}1"] {
+        let input = format!("name_a,lat_a,lon_a,name_b,lat_b,lon_b,radius_km\nBad,0,0,Bad,0,1,{value}\n");
+        let err = run_fixture("row_radius_text", &input, &["-f", "dd", "--strict"]).unwrap_err();
+        assert_eq!(err.to_string(), format!("Line 2: invalid radius_km `{value}` (expected a positive number of km)"));
+    }
+    Ok(())
+}
+
+/* --- Output formats --------------------*/
+
+#[test]
+fn test_csv_output_default() -> Result<(), AppError> {
+    let out = run_fixture("csv_default", DD_FIXTURE, &["-f", "dd"])?;
+    let mut lines = out.lines();
+    assert!(lines.next().unwrap().starts_with("id,name_a,lat_a_in"));
+    assert_eq!(lines.count(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_quote_style() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        \"Paris, France\",48.8567,2.3486,Lyon,45.75,4.833333\n";
+
+    let out = run_fixture("quote_always", input, &["-f", "dd", "--quote-style", "always", "--quote-char", "'"])?;
+    assert!(out.lines().nth(1).unwrap().starts_with("'1','Paris, France','48.8567',"));
+
+    let out = run_fixture("quote_never", DD_FIXTURE, &["-f", "dd", "--quote-style", "never"])?;
+    assert!(out.contains(",48°51'24.12\"N,"));
+
+    let err = run_fixture("quote_never_comma", input, &["-f", "dd", "--quote-style", "never", "--strict"]);
+    assert!(matches!(err, Err(AppError::UnquotableField(f)) if f == "Paris, France"));
+    Ok(())
+}
+
+#[test]
+fn test_tsv() -> Result<(), AppError> {
+    let input = "name_a\tlat_a\tlon_a\tname_b\tlat_b\tlon_b\n\
+        Paris, France\t48.8567\t2.3486\t\"Lyon\" centre\t45.75\t4.833333\n";
+
+    let out = run_fixture("tsv", input, &["-f", "dd", "--tsv"])?;
+    let mut lines = out.lines();
+    assert!(lines.next().unwrap().starts_with("id\tname_a\tlat_a_in\t"));
+    let row: Vec<&str> = lines.next().unwrap().split('\t').collect();
+    assert_eq!(&row[..3], ["1", "Paris, France", "48.8567"]);
+    assert!(row.contains(&"\"Lyon\" centre"));
+
+    let err = run_fixture("tsv_missing", "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n", &["-f", "dd", "--tsv"]);
+    assert!(matches!(err, Err(AppError::MissingHeaderField(_))));
+    Ok(())
+}
+
+#[test]
+fn test_no_header_positional_columns() -> Result<(), AppError> {
+    let input = "Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Bad,abc,2.3486,Lyon,45.75,4.833333\n";
+    let out = run_fixture("no_header", input, &["-f", "dd", "--no-header"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows.len(), 2);
+    assert!(rows[1].starts_with("1,Paris,48.8567,2.3486,"));
+    assert!(rows[1].contains(",392.93,"));
+
+    // Data starts on line 1.
+    let res = run_fixture("no_header_strict", input, &["-f", "dd", "--no-header", "--strict"]);
+    assert!(matches!(res, Err(AppError::InvalidDd { line: 2, .. })));
+
+    let out = run_fixture("no_header_track", "A,0,0\nB,0,1\n", &["-f", "dd", "--no-header", "--track"])?;
+    assert!(out.lines().nth(2).unwrap().starts_with("2,B,0,1,"));
+    Ok(())
+}
+
+#[test]
+fn test_validate_writes_nothing() -> Result<(), AppError> {
+    let dir = fixture_dir("validate");
+    let input_path = dir.join("input.csv");
+    let argv = |extra: &[&str]| {
+        let mut argv = vec!["ektaon", "-i", input_path.to_str().unwrap(), "-f", "dd", "--validate"];
+        argv.extend(extra);
+        Cli::parse_from(argv)
+    };
+
+    std::fs::write(&input_path, DD_FIXTURE)?;
+    run(&argv(&[]))?;
+    assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Bad,abc,2.3486,Lyon,45.75,4.833333\n";
+    std::fs::write(&input_path, input)?;
+    assert!(matches!(run(&argv(&[])), Err(AppError::ValidationFailed(1))));
+    assert!(matches!(run(&argv(&["--strict"])), Err(AppError::InvalidDd { line: 3, .. })));
+    assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+
+    assert!(Cli::try_parse_from(["ektaon", "-i", "in.csv", "-f", "dd"]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_max_errors() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Bad,abc,2.3486,Lyon,45.75,4.833333\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Bad,48.8567,2.3486,Lyon,xyz,4.833333\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n";
+
+    // Two invalid lines: allowed up to 2, aborted at the second above 1.
+    assert_eq!(run_fixture("max_errors_ok", input, &["-f", "dd", "--max-errors", "2"])?.lines().count(), 3);
+    let res = run_fixture("max_errors", input, &["-f", "dd", "--max-errors", "1"]);
+    assert!(matches!(res, Err(AppError::TooManyErrors { invalid: 2, read: 3, limit: 1 })));
+
+    let res = run_fixture("max_errors_zero", input, &["-f", "dd", "--max-errors", "0"]);
+    assert!(matches!(res, Err(AppError::TooManyErrors { invalid: 1, read: 1, limit: 0 })));
+    Ok(())
+}
+
+#[test]
+fn test_ragged_rows() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Paris,48.8567,2.3486,Lyon,45.75\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333,extra\n";
+
+    let res = run_fixture("ragged_strict", input, &["-f", "dd", "--strict"]);
+    assert!(matches!(res, Err(AppError::RaggedRow { line: 3, expected: 6, found: 5 })));
+    let out = run_fixture("ragged", input, &["-f", "dd"])?;
+    assert_eq!(out.lines().count(), 2);
+
+    // Same error in the single-point modes.
+    let input = "name,lat,lon\nA,48.8567,2.3486\nB,45.75,4.833333,x\n";
+    let res = run_fixture("ragged_track", input, &["-f", "dd", "--track", "--strict"]);
+    assert!(matches!(res, Err(AppError::RaggedRow { line: 3, expected: 3, found: 4 })));
+    Ok(())
+}
+
+#[test]
+fn test_empty_cells() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Paris,48.8567,2.3486,Nowhere, ,4.833333\n";
+
+    let res = run_fixture("empty_error", input, &["-f", "dd", "--strict"]);
+    assert!(matches!(res, Err(AppError::EmptyCoordinate { line: 3, field: "lat_b" })));
+
+    // Skipped even in strict mode, and never counted against --max-errors.
+    let out = run_fixture("empty_skip", input, &["-f", "dd", "--strict", "--empty", "skip"])?;
+    assert_eq!(out.lines().count(), 2);
+    let out = run_fixture("empty_skip_max", input, &["-f", "dd", "--empty", "skip", "--max-errors", "0"])?;
+    assert_eq!(out.lines().count(), 2);
+
+    let args = ["-f", "dd", "--strict", "--empty", "zero", "--columns", "lat_b_in,lat_b_dd"];
+    let out = run_fixture("empty_zero", input, &args)?;
+    assert_eq!(out.lines().nth(2), Some(" ,0.0"));
+
+    // Detected before the notation is parsed.
+    assert_eq!(parse_coordinate("", "lat_a", CoordinateKind::Latitude, InputFormat::Dms, EmptyCells::Zero, 2)?, 0.0);
+    let res = parse_coordinate("  ", "lat_a", CoordinateKind::Latitude, InputFormat::Ddm, EmptyCells::Error, 2);
+    assert!(matches!(res, Err(AppError::EmptyCoordinate { line: 2, field: "lat_a" })));
+    Ok(())
+}
+
+#[test]
+fn test_quiet_and_verbose() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Bad,abc,2.3486,Lyon,45.75,4.833333\n";
+    for flags in [&["--quiet"][..], &["--verbose"], &["--log-level", "warn"]] {
+        let out = run_fixture("quiet_verbose", input, &[&["-f", "dd"], flags].concat())?;
+        assert_eq!(out.lines().count(), 2);
+    }
+    assert!(Cli::try_parse_from(["ektaon", "-i", "in", "-o", "out", "-f", "dd", "-q", "-v"]).is_err());
+    assert!(Cli::try_parse_from(["ektaon", "-i", "in", "-o", "out", "-f", "dd", "-q", "--log-level", "info"]).is_err());
+    assert_eq!(log::LevelFilter::from(LogLevel::Warn), log::LevelFilter::Warn);
+
+    // The reason printed with --verbose names the line once.
+    let invalid = AppError::InvalidDd { line: 3, field: "lat_a", value: "abc".into() };
+    assert_eq!(invalid.line(), Some(3));
+    assert_eq!(AppError::UnquotableField("a,b".into()).line(), None);
+    Ok(())
+}
+
+#[test]
+fn test_ndjson_output() -> Result<(), AppError> {
+    let out = run_fixture("ndjson", DD_FIXTURE, &["-f", "dd", "--output-format", "ndjson"])?;
+    let rows: Vec<serde_json::Value> = out
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0]["id"], 1);
+    assert_eq!(rows[0]["name_b"], "Lyon");
+    assert_eq!(rows[0]["lat_a_dd"], 48.8567);
+    assert_eq!(rows[0]["nearly_both"], false);
+    assert_eq!(rows[1]["nearly_both"], true);
+    assert!(rows[1]["distance_km"].is_f64());
+    Ok(())
+}
+
+#[test]
+fn test_column_prefix() -> Result<(), AppError> {
+    let out = run_fixture("prefix", DD_FIXTURE, &["-f", "dd", "--column-prefix", "geo_"])?;
+    let header: Vec<&str> = out.lines().next().unwrap().split(',').collect();
+    assert_eq!(&header[..6], ["geo_id", "name_a", "lat_a_in", "lon_a_in", "geo_lat_a_dd", "geo_lon_a_dd"]);
+    assert!(header.contains(&"geo_distance_km") && header.contains(&"geo_nearly_lat"));
+    assert!(out.lines().nth(1).unwrap().starts_with("1,Paris,48.8567,2.3486,48.8567,"));
+
+    let out = run_fixture(
+        "prefix_ndjson",
+        DD_FIXTURE,
+        &["-f", "dd", "--column-prefix", "geo_", "--output-format", "ndjson", "--columns", "id,name_b,distance_km"],
+    )?;
+    assert_eq!(out.lines().next(), Some(r#"{"geo_id":1,"name_b":"Lyon","geo_distance_km":392.93}"#));
+    Ok(())
+}
+
+/* --- Poles --------------------*/
+
+#[test]
+fn test_to_pole_from_equator() {
+    let north = to_pole(0.0, Pole::North, EARTH_RADIUS_KM);
+    assert_eq!(round(north.km, 2), 10007.54);
+    assert_eq!(north.bearing_deg, 0.0);
+
+    let south = to_pole(0.0, Pole::South, EARTH_RADIUS_KM);
+    assert_eq!(round(south.km, 2), 10007.54);
+    assert_eq!(south.bearing_deg, 180.0);
+}
+
+#[test]
+fn test_to_pole_near_pole() -> Result<(), Box<dyn std::error::Error>> {
+    let near = to_pole(89.9, Pole::North, EARTH_RADIUS_KM);
+    assert_eq!(round(near.km, 2), 11.12);
+    assert_eq!(near.bearing_deg, 0.0);
+
+    // Matches the haversine distance to the pole itself.
+    let expected = haversine(89.9, 45.0, 90.0, 0.0)?;
+    assert!((near.km - expected).abs() < 1e-6);
+
+    let at_pole = to_pole(90.0, Pole::North, EARTH_RADIUS_KM);
+    assert_eq!(at_pole.km, 0.0);
+    Ok(())
+}
+
+#[test]
+fn test_to_pole_columns() -> Result<(), AppError> {
+    let out = run_fixture("to_pole", DD_FIXTURE, &["-f", "dd", "--to-pole", "south"])?;
+    let mut lines = out.lines();
+    assert!(lines.next().unwrap().ends_with(
+        ",dist_to_pole_km_a,bearing_to_pole_deg_a,dist_to_pole_km_b,bearing_to_pole_deg_b"
+    ));
+    assert!(lines.next().unwrap().ends_with(",15440.16,180.0,15094.71,180.0"));
+
+    let args = ["-f", "dd", "--to-pole", "south", "--km-precision", "0"];
+    let out = run_fixture("to_pole_precision", DD_FIXTURE, &args)?;
+    assert!(out.lines().nth(1).unwrap().ends_with(",15440.0,180.0,15095.0,180.0"));
+    Ok(())
+}
+
+/* --- Reverse --------------------*/
+
+#[test]
+fn test_reverse_swaps_points() -> Result<(), AppError> {
+    let args = ["-f", "dd", "--to-pole", "north"];
+    let out = run_fixture("reverse_off", DD_FIXTURE, &args)?;
+    let reversed = run_fixture("reverse_on", DD_FIXTURE, &[&args[..], &["--reverse"]].concat())?;
+
+    let row: Vec<&str> = out.lines().nth(1).unwrap().split(',').collect();
+    let rev: Vec<&str> = reversed.lines().nth(1).unwrap().split(',').collect();
+
+    // Points swapped: name, inputs, DD and DMS columns.
+    assert_eq!(rev[1..8], row[8..15]);
+    assert_eq!(rev[8..15], row[1..8]);
+    // Same distance, per-point pole columns swapped.
+    assert_eq!(rev[15..17], row[15..17]);
+    assert_eq!((rev[20], rev[22]), (row[22], row[20]));
+    Ok(())
+}
+
+/* --- Sanity check --------------------*/
+
+const SWAPPED_FIXTURE: &str = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+    Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+    Tokyo,139.683333,35.683333,Osaka,34.683333,135.5\n";
+
+#[test]
+fn test_sanity_check_flags_suspect_rows() -> Result<(), AppError> {
+    let out = run_fixture("sanity_flag", SWAPPED_FIXTURE, &["-f", "dd", "--sanity-check"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,suspect"));
+    assert!(rows[1].ends_with(",false"));
+    assert!(rows[2].ends_with(",true"));
+    Ok(())
+}
+
+#[test]
+fn test_sanity_check_strict_error() {
+    let res = run_fixture("sanity_strict", SWAPPED_FIXTURE, &["-f", "dd", "--sanity-check", "--strict"]);
+    assert!(matches!(
+        res,
+        Err(AppError::OutOfRange { line: 3, field: "lat_a", .. })
+    ));
+}
+
+#[test]
+fn test_lonlat_reads_longitude_first() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,2.3522,48.8566,Lyon,4.8357,45.764\n";
+    let out = run_fixture("lonlat", input, &["-f", "dd", "--lonlat"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[1].starts_with("1,Paris,48.8566,2.3522,48.8566,2.3522,"));
+    assert!(rows[1].contains(",Lyon,45.764,4.8357,45.764,4.8357,"));
+    assert!(rows[1].contains(",391.5,"));
+    Ok(())
+}
+
+#[test]
+fn test_lonlat_sanity_check_flags_lat_first_rows() -> Result<(), AppError> {
+    // Tokyo is written lat-first: read as lon-first, its latitude is 139.68.
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,2.3522,48.8566,Lyon,4.8357,45.764\n\
+        Tokyo,35.683333,139.683333,Osaka,135.5,34.683333\n";
+    let out = run_fixture("lonlat_sanity", input, &["-f", "dd", "--lonlat", "--sanity-check"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[1].ends_with(",false"));
+    assert!(rows[2].ends_with(",true"));
+    Ok(())
+}
+
+#[test]
+fn test_swap_lonlat_headers() {
+    let mut headers = StringRecord::from(vec!["name", "lat", "lon", "lat_a", "lon_b", "group"]);
+    swap_lonlat_headers(&mut headers);
+    assert_eq!(headers, StringRecord::from(vec!["name", "lon", "lat", "lon_a", "lat_b", "group"]));
+}
+
+#[test]
+fn test_dd_out_of_range_rejected() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Nowhere,95,200,Lyon,45.75,4.833333\n";
+    let out = run_fixture("dd_out_of_range", input, &["-f", "dd"])?;
+    assert_eq!(out.lines().count(), 2);
+
+    let res = run_fixture("dd_out_of_range_strict", input, &["-f", "dd", "--strict"]);
+    assert!(matches!(res, Err(AppError::OutOfRange { line: 3, field: "lat_a", value: 95.0 })));
+
+    let res = parse_coordinate("-180.5", "lon", CoordinateKind::Longitude, InputFormat::Dd, EmptyCells::Error, 2);
+    assert!(matches!(res, Err(AppError::OutOfRange { line: 2, field: "lon", .. })));
+    Ok(())
+}
+
+#[test]
+fn test_without_sanity_check_no_column() -> Result<(), AppError> {
+    let out = run_fixture("sanity_off", SWAPPED_FIXTURE, &["-f", "dd"])?;
+    assert!(out.lines().next().unwrap().ends_with(",nearly_both"));
+    Ok(())
+}
+
+/* --- Header map --------------------*/
+
+const ALIASED_FIXTURE: &str = "origin,origin_lat,origin_lon,dest,dest_lat,dest_lon\n\
+    Paris,48.8567,2.3486,Lyon,45.75,4.833333\n";
+
+#[test]
+fn test_header_map() -> Result<(), AppError> {
+    let out = run_fixture("header_map", ALIASED_FIXTURE, &[
+        "-f", "dd",
+        "--map", "name_a=origin", "--map", "lat_a=origin_lat", "--map", "lon_a=origin_lon",
+        "--map", "name_b=dest", "--map", "lat_b=dest_lat", "--map", "lon_b=dest_lon",
+    ])?;
+    assert!(out.lines().nth(1).unwrap().starts_with("1,Paris,48.8567,2.3486,"));
+    Ok(())
+}
+
+#[test]
+fn test_header_map_errors() {
+    assert!(parse_header_map("lat_a").is_err());
+    assert!(parse_header_map("latitude=origin_lat").is_err());
+    assert_eq!(
+        parse_header_map("lat_a = origin_lat"),
+        Ok(("lat_a".to_string(), "origin_lat".to_string()))
+    );
+
+    let missing = run_fixture("header_map_missing", ALIASED_FIXTURE, &["-f", "dd", "--map", "lat_a=start_lat"]);
+    assert!(matches!(missing, Err(AppError::MissingHeaderField(f)) if f.starts_with("start_lat")));
+
+    let conflict = run_fixture("header_map_conflict", DD_FIXTURE, &["-f", "dd", "--map", "lat_a=lat_b"]);
+    assert!(matches!(conflict, Err(AppError::HeaderMapConflict { .. })));
+}
+
+/* --- Strict headers --------------------*/
+
+const EXTRA_COLUMNS_FIXTURE: &str = "id,name_a,lat_a,lon_a,name_b,lat_b,lon_b,notes\n\
+    7,Paris,48.8567,2.3486,Lyon,45.75,4.833333,hq\n";
+
+#[test]
+fn test_strict_headers_reports_all_extra_columns() {
+    let res = run_fixture("strict_headers", EXTRA_COLUMNS_FIXTURE, &["-f", "dd", "--strict-headers"]);
+    let Err(err) = res else { panic!("extra columns accepted") };
+    assert!(matches!(&err, AppError::UnexpectedHeaderFields(cols) if cols == &["id", "notes"]));
+    assert_eq!(err.to_string(), "Unexpected header field(s): id, notes");
+}
+
+#[test]
+fn test_strict_headers_allowlist() -> Result<(), AppError> {
+    // Extra columns are ignored without the option.
+    run_fixture("strict_headers_off", EXTRA_COLUMNS_FIXTURE, &["-f", "dd"])?;
+
+    let out = run_fixture("strict_headers_allow", EXTRA_COLUMNS_FIXTURE, &[
+        "-f", "dd", "--strict-headers", "--allow-column", "id", "--allow-column", "notes",
+    ])?;
+    assert_eq!(out.lines().count(), 2);
+    Ok(())
+}
+
+/* --- Passthrough --------------------*/
+
+#[test]
+fn test_passthrough_columns() -> Result<(), AppError> {
+    let input = "id,name_a,lat_a,lon_a,name_b,lat_b,lon_b,category,notes\n\
+        7,Paris,48.8567,2.3486,Lyon,45.75,4.833333,hq,\"a, b\"\n";
+
+    let out = run_fixture("passthrough", input, &["-f", "dd", "--passthrough"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].starts_with("id,name_a,"));
+    assert!(rows[0].ends_with(",nearly_both,id_in,category,notes"));
+    assert!(rows[1].starts_with("1,Paris,"));
+    assert!(rows[1].ends_with(",false,7,hq,\"a, b\""));
+
+    let out = run_fixture("passthrough_json", input, &["-f", "dd", "--passthrough", "--output-format", "ndjson"])?;
+    let row: serde_json::Value = serde_json::from_str(out.lines().next().unwrap())?;
+    assert_eq!(row["id"], 1);
+    assert_eq!(row["id_in"], "7");
+    assert_eq!(row["notes"], "a, b");
+    assert!(out.find("\"nearly_both\"") < out.find("\"category\""));
+    Ok(())
+}
+
+#[test]
+fn test_without_passthrough_extra_columns_dropped() -> Result<(), AppError> {
+    let out = run_fixture("passthrough_off", EXTRA_COLUMNS_FIXTURE, &["-f", "dd"])?;
+    assert!(out.lines().next().unwrap().ends_with(",nearly_both"));
+    Ok(())
+}
+
+/* --- Precision audit --------------------*/
+
+#[test]
+fn test_precision_audit_dd() {
+    let mut audit = PrecisionAudit::new(InputFormat::Dd);
+    for raw in ["48", "48.8567", "-2.34861", "48.858056", "2.2945000001", "n/a"] {
+        audit.record(raw);
+    }
+    assert_eq!(audit.count("<= 4 decimals"), 2);
+    assert_eq!(audit.count("5 decimals"), 1);
+    assert_eq!(audit.count(">= 6 decimals"), 2);
+}
+
+#[test]
+fn test_precision_audit_mixed_dms_file() {
+    let headers = StringRecord::from(vec!["name_a", "lat_a", "lon_a", "name_b", "lat_b", "lon_b"]);
+    let rows = [
+        vec!["P1.5", "48°51'29\"N", "2°17'40\"E", "L2", "45°45'0\"N", "4°50'0\"E"],
+        vec!["P3", "48°51'29.6\"N", "2°17'40.25\"E", "L4", "45°45'0.00\"N", "4°50'0\"E"],
+    ];
+
+    let mut audit = PrecisionAudit::new(InputFormat::Dms);
+    for row in rows {
+        audit.record_row(&headers, &StringRecord::from(row));
+    }
+
+    // Names are ignored, even when they contain numbers.
+    assert_eq!(audit.count("whole seconds"), 5);
+    assert_eq!(audit.count("sub-second"), 3);
+    let report = audit.report();
+    assert!(report.starts_with("Precision audit (DMS, 8 coordinate value(s))"));
+    assert!(report.contains("sub-second"));
+    assert!(report.contains("37.5%"));
+}
+
+/* --- Format report --------------------*/
+
+#[test]
+fn test_format_detection() {
+    assert!(matches!(FormatReport::detect(" 48.858056"), Some(InputFormat::Dd)));
+    assert!(matches!(FormatReport::detect("48°51'29.6\"N"), Some(InputFormat::Dms)));
+    assert!(matches!(FormatReport::detect("48° 51.4′ N"), Some(InputFormat::Ddm)));
+    assert!(FormatReport::detect("north").is_none());
+}
+
+#[test]
+fn test_format_report_mixed_file() {
+    let headers = StringRecord::from(vec!["name_a", "lat_a", "lon_a", "name_b", "lat_b", "lon_b"]);
+    let mut rows = vec![vec!["P", "48°51'29\"N", "2°17'40\"E", "L", "45°45'0\"N", "4°50'0\"E"]; 9];
+    rows.push(vec!["P", "48.858056", "2°17'40\"E", "L", "45°45.0'N", "?"]);
+
+    let mut report = FormatReport::new(&headers);
+    for row in rows {
+        report.record_row(&headers, &StringRecord::from(row));
+    }
+
+    let text = report.report();
+    assert!(text.contains("lat_a: 90.0% DMS, 10.0% DD (10 value(s))"));
+    assert!(text.contains("lon_a: 100.0% DMS (10 value(s))"));
+    assert!(text.contains("lat_b: 90.0% DMS, 10.0% DDM"));
+    assert!(text.contains("lon_b: 90.0% DMS, 10.0% other"));
+    assert!(!text.contains("lat:"));
+}
+
+/* --- Manifest --------------------*/
+
+#[test]
+fn test_manifest_fields() -> Result<(), AppError> {
+    let dir = fixture_dir("manifest");
+    let manifest_path = dir.join("manifest.json");
+    let input = format!("{DD_FIXTURE}Bad,x,0,Row,0,0\n");
+
+    let out = run_in(&dir, &input, &["-f", "dd", "--manifest", manifest_path.to_str().unwrap()])?;
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest_path)?)?;
+
+    assert_eq!(manifest["tool"], "ektaon");
+    assert_eq!(manifest["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(manifest["input"]["blake3"], blake3::hash(input.as_bytes()).to_hex().as_str());
+    assert_eq!(manifest["output"]["blake3"], blake3::hash(out.as_bytes()).to_hex().as_str());
+    assert_eq!(manifest["rows"]["read"], 3);
+    assert_eq!(manifest["rows"]["written"], 2);
+    assert_eq!(manifest["rows"]["invalid"], 1);
+    assert_eq!(manifest["options"]["input_format"], "dd");
+    assert_eq!(manifest["options"]["strict"], false);
+    Ok(())
+}
+
+/* --- Waypoints --------------------*/
+
+#[test]
+fn test_intermediate_point_equator() {
+    assert_eq!(intermediate_point(0.0, 0.0, 0.0, 90.0, 0.0), (0.0, 0.0));
+    let (lat, lon) = intermediate_point(0.0, 0.0, 0.0, 90.0, 0.5);
+    assert_eq!((round(lat, 6), round(lon, 6)), (0.0, 45.0));
+    let (lat, lon) = intermediate_point(0.0, 0.0, 0.0, 90.0, 1.0);
+    assert_eq!((round(lat, 6), round(lon, 6)), (0.0, 90.0));
+}
+
+#[test]
+fn test_intermediate_point_meridian_over_pole() {
+    // From 60°N to 60°N on the opposite meridian, the arc passes over the North pole.
+    let (lat, _) = intermediate_point(60.0, 0.0, 60.0, 180.0, 0.5);
+    assert_eq!(round(lat, 6), 90.0);
+}
+
+#[test]
+fn test_intermediate_point_degenerate_fallback() {
+    assert!(arc_is_degenerate(10.0, 20.0, 10.0, 20.0));
+    assert_eq!(intermediate_point(10.0, 20.0, 10.0, 20.0, 0.5), (10.0, 20.0));
+
+    // Antipodal points: infinitely many great circles, linear interpolation.
+    assert!(arc_is_degenerate(0.0, 0.0, 0.0, 180.0));
+    assert_eq!(intermediate_point(0.0, 0.0, 0.0, 180.0, 0.5), (0.0, 90.0));
+}
+
+#[test]
+fn test_waypoints_output() -> Result<(), AppError> {
+    let out = run_fixture("waypoints", DD_FIXTURE, &["-f", "dd", "--waypoints", "3"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows[0], "pair_id,index,fraction,name_a,name_b,lat_dd,lon_dd");
+    assert_eq!(rows.len(), 1 + 2 * 3);
+    assert_eq!(rows[1], "1,0,0.0,Paris,Lyon,48.8567,2.3486");
+    assert!(rows[2].starts_with("1,1,0.5,Paris,Lyon,47.3"));
+    assert_eq!(rows[3], "1,2,1.0,Paris,Lyon,45.75,4.833333");
+    assert!(rows[4].starts_with("2,0,0.0,Eiffel,Copy,"));
+    Ok(())
+}
+
+#[test]
+fn test_output_coord_format() -> Result<(), AppError> {
+    let args = ["-f", "dd", "--waypoints", "3", "--output-coord-format", "ddm"];
+    let out = run_fixture("waypoints_ddm", DD_FIXTURE, &args)?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows[0], "pair_id,index,fraction,name_a,name_b,lat_dd,lon_dd,lat,lon");
+    assert_eq!(rows[3], "1,2,1.0,Paris,Lyon,45.75,4.833333,45°45.0000'N,4°50.0000'E");
+
+    let input = "name_a,lat_a,lon_a,bearing,distance_km\nEquator,0°0'0\"N,0°0'0\"E,270,111.19\n";
+    let args = ["-f", "dms", "--destination", "--output-coord-format", "dd"];
+    let out = run_fixture("destination_dd", input, &args)?;
+    assert!(out.lines().next().unwrap().ends_with(",lat_b_dms,lon_b_dms,lat_b,lon_b"));
+    assert!(out.lines().nth(1).unwrap().ends_with(",0,-0.999956"), "{out}");
+
+    // Minutes rounded up to 60 are carried into the degrees.
+    let ddm = dd_to_ddm(-179.9999999, CoordinateKind::Longitude, 4, Locale::Fr, RoundingMode::HalfUp, DmsSymbols::Ascii);
+    assert_eq!(ddm, "180°0.0000'O");
+    Ok(())
+}
+
+/* --- Elevation --------------------*/
+
+#[test]
+fn test_distance_3d() -> Result<(), Box<dyn std::error::Error>> {
+    // Same point, 1000 m apart vertically.
+    assert_eq!(haversine_3d(45.0, 6.0, 0.0, 45.0, 6.0, 1000.0)?, 1.0);
+
+    // 3-4-5 triangle: 3 km on the ground, 4000 m of climb.
+    assert_eq!(distance_3d(3.0, 200.0, 4200.0)?, 5.0);
+
+    assert!(matches!(
+        distance_3d(3.0, f64::NAN, 0.0),
+        Err(HaversineError::InvalidElevation { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_elevation_columns() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,elev_a,name_b,lat_b,lon_b,elev_b\n\
+        Chamonix,45.9237,6.8694,1035,Mont Blanc,45.8326,6.8652,4808\n\
+        Missing,45.9237,6.8694,,Mont Blanc,45.8326,6.8652,4808\n\
+        Minus,45.9237,6.8694,\u{2212}5,Mont Blanc,45.8326,6.8652,4808\n\
+        NaN,45.9237,6.8694,nan,Mont Blanc,45.8326,6.8652,4808\n\
+        Text,45.9237,6.8694,1035,Mont Blanc,45.8326,6.8652,abc\n";
+
+    // A missing elevation leaves distance_3d_km empty, the row is kept.
+    let out = run_fixture("elevation", input, &["-f", "dd"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].contains(",distance_km,distance_miles,distance_3d_km,"));
+    assert_eq!(rows.len(), 4);
+    assert!(rows[1].contains(",10.14,6.3,10.81,"));
+    assert!(rows[2].contains(",10.14,6.3,,"));
+    assert!(rows[3].contains(",10.14,6.3,11.22,"));
+
+    let strict = run_fixture("elevation_strict", input, &["-f", "dd", "--strict"]);
+    assert!(matches!(strict, Err(AppError::InvalidElevation { line: 5, field: "elev_a", .. })));
+    let input = input.replace("nan", "1035");
+    let strict = run_fixture("elevation_text", &input, &["-f", "dd", "--strict"]).unwrap_err();
+    assert_eq!(strict.to_string(), "Line 6: invalid elev_b `abc` (expected a number of meters)");
+    Ok(())
+}
+
+#[test]
+fn test_no_elevation_columns_unchanged() -> Result<(), AppError> {
+    let out = run_fixture("no_elevation", DD_FIXTURE, &["-f", "dd"])?;
+    assert!(!out.contains("distance_3d_km"));
+    Ok(())
+}
+
+/* --- Longitude separation --------------------*/
+
+#[test]
+fn test_longitude_separation() {
+    assert_eq!(longitude_separation(2.0, 4.5), 2.5);
+    assert_eq!(longitude_separation(0.0, 180.0), 180.0);
+    assert_eq!(longitude_separation(-180.0, 180.0), 0.0);
+}
+
+#[test]
+fn test_longitude_separation_antimeridian() {
+    // The naive difference would be 358 / 340.
+    assert_eq!(round(longitude_separation(179.0, -179.0), 6), 2.0);
+    assert_eq!(round(longitude_separation(-170.0, 170.0), 6), 20.0);
+    assert_eq!(round(longitude_separation(179.5, -179.75), 6), 0.75);
+}
+
+#[test]
+fn test_lon_sep_column() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\nEast,10.0,179.0,West,10.0,-179.0\n";
+    let out = run_fixture("lon_sep", input, &["-f", "dd", "--emit-lon-sep"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,lon_sep_deg"));
+    assert!(rows[1].ends_with(",2.0"));
+    Ok(())
+}
+
+#[test]
+fn test_crosses_antimeridian() -> Result<(), AppError> {
+    assert!(crosses_antimeridian(179.0, -179.0));
+    assert!(crosses_antimeridian(-170.0, 20.0));
+    assert!(!crosses_antimeridian(10.0, -10.0));
+    assert!(!crosses_antimeridian(90.0, -90.0));
+    assert!(!crosses_antimeridian(179.0, 178.0));
+
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        East,0.0,179.0,West,0.0,-179.0\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n";
+    let out = run_fixture("antimeridian", input, &["-f", "dd", "--flag-antimeridian", "--columns", "distance_km,crosses_antimeridian"])?;
+    // 2° of longitude on the equator, not 358°.
+    assert_eq!(out.lines().collect::<Vec<_>>(), ["distance_km,crosses_antimeridian", "222.39,true", "392.93,false"]);
+    Ok(())
+}
+
+/* --- Bearing --------------------*/
+
+#[test]
+fn test_compass_point_boundaries() {
+    assert_eq!(compass_point(0.0), "N");
+    assert_eq!(compass_point(11.249), "N");
+    assert_eq!(compass_point(11.25), "NNE");
+    assert_eq!(compass_point(33.75), "NE");
+    assert_eq!(compass_point(191.25), "SSW");
+    assert_eq!(compass_point(348.749), "NNW");
+    assert_eq!(compass_point(348.75), "N");
+    assert_eq!(compass_point(359.999), "N");
+    assert_eq!(compass_point(360.0), "N");
+    assert_eq!(compass_point(-90.0), "W");
+}
+
+#[test]
+fn test_initial_bearing() {
+    assert_eq!(round(initial_bearing(0.0, 0.0, 10.0, 0.0), 6), 0.0);
+    assert_eq!(round(initial_bearing(0.0, 0.0, 0.0, 10.0), 6), 90.0);
+    assert_eq!(round(initial_bearing(0.0, 0.0, -10.0, 0.0), 6), 180.0);
+    assert_eq!(round(initial_bearing(0.0, 0.0, 0.0, -10.0), 6), 270.0);
+}
+
+#[test]
+fn test_bearing_columns() -> Result<(), AppError> {
+    let out = run_fixture("bearing", DD_FIXTURE, &["-f", "dd", "--emit-bearing"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,bearing_deg,bearing_compass"));
+    // Paris -> Lyon heads south-south-east.
+    assert!(rows[1].ends_with(",150.61,SSE"));
+    Ok(())
+}
+
+/* --- Exact match --------------------*/
+
+#[test]
+fn test_nearly_mode() -> Result<(), AppError> {
+    // Same latitude, longitudes 1° apart.
+    let nearly = |mode| compute_nearly(48.8567, 2.3486, 48.8567, 3.3486, GeoTolerance::DEFAULT, mode);
+    let and = nearly(NearlyMode::And);
+    assert!(and.lat && !and.lon && !and.combined);
+    let or = nearly(NearlyMode::Or);
+    assert!(or.lat && !or.lon && or.combined);
+    assert!(!or.same_point());
+
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,East,48.8567,3.3486\n";
+    let out = run_fixture("nearly_and", input, &["-f", "dd", "--snap-zero"])?;
+    assert!(out.lines().nth(1).unwrap().ends_with(",true,false,false"));
+    let out = run_fixture("nearly_or", input, &["-f", "dd", "--snap-zero", "--nearly-mode", "or"])?;
+    let row = out.lines().nth(1).unwrap();
+    assert!(row.ends_with(",true,false,true"));
+    // --snap-zero still requires the same point.
+    assert!(row.contains(",73.16,"));
+    Ok(())
+}
+
+#[test]
+fn test_emit_deltas() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,East,48.8567,3.3486\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n";
+    let out = run_fixture("deltas", input, &["-f", "dd", "--emit-deltas"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,delta_lat_deg,delta_lon_deg"));
+    assert!(rows[1].ends_with(",true,false,false,0.0,1.0"));
+    assert!(rows[2].ends_with(",false,false,false,3.1067,2.484733"));
+
+    // Rounded like the DD columns.
+    let out = run_fixture("deltas_precision", input, &["-f", "dd", "--emit-deltas", "--coord-precision", "2"])?;
+    assert!(out.lines().nth(2).unwrap().ends_with(",false,3.11,2.48"));
+    Ok(())
+}
+
+#[test]
+fn test_warn_above() -> Result<(), AppError> {
+    let point = |name: &str, lat, lon| ParsedPoint {
+        line: 2, name: name.into(), lat_in: String::new(), lon_in: String::new(), lat, lon,
+    };
+    let cli = Cli::parse_from(["ektaon", "-i", "in", "-o", "out", "-f", "dd"]);
+    let geo = build_normalized_geo(&point("Paris", 48.8567, 2.3486), &point("Lyon", 45.75, 4.833333), &cli);
+    assert_eq!(
+        distance_warning(&geo, 392.93, Some(100.0)).as_deref(),
+        Some("Warning: line 2: 392.93 km between Paris and Lyon (above --warn-above 100 km)"),
+    );
+    assert_eq!(distance_warning(&geo, 392.93, Some(500.0)), None);
+    assert_eq!(distance_warning(&geo, 392.93, None), None);
+
+    // A warning only: every row is written.
+    let out = run_fixture("warn_above", DD_FIXTURE, &["-f", "dd", "--warn-above", "1"])?;
+    assert_eq!(out.lines().count(), DD_FIXTURE.lines().count());
+    assert!(Cli::try_parse_from(["ektaon", "-i", "in", "-o", "out", "-f", "dd", "--warn-above", "-5"]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_emit_format_used() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48°51'24\"N,2°21'7\"E,Lyon,45°45'0\"N,4°50'0\"E\n";
+    let out = run_fixture("format_used", input, &["-f", "dms", "--emit-format-used"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,format_used"));
+    assert!(rows[1].ends_with(",dms"));
+    Ok(())
+}
+
+#[test]
+fn test_exact_match() -> Result<(), AppError> {
+    // Second row: 1e-6° apart on the latitude, within the default tolerance.
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Same,48.858056,2.2945,Copy,48.858056,2.2945\n\
+        Near,0.0,2.2945,Next,0.000001,2.2945\n\
+        Noise,48.8580561,2.2945,Rounded,48.858056,2.2945\n";
+
+    let near = run_fixture("exact_off", input, &["-f", "dd"])?;
+    assert!(near.lines().skip(1).all(|row| row.ends_with(",true,true,true")));
+
+    let exact = run_fixture("exact_on", input, &["-f", "dd", "--exact-match"])?;
+    let rows: Vec<&str> = exact.lines().collect();
+    assert!(rows[1].ends_with(",true,true,true"));
+    assert!(rows[2].ends_with(",false,true,false"));
+    // Compared after rounding to 6 decimals.
+    assert!(rows[3].ends_with(",true,true,true"));
+    Ok(())
+}
+
+#[test]
+fn test_tolerance_per_axis() -> Result<(), AppError> {
+    // At 80°N, 5e-6° of longitude is about 10 cm, like 1e-6° of latitude.
+    let tol = GeoTolerance { lat: 1e-6, lon: 1e-6 / 80f64.to_radians().cos() };
+    let nearly = compute_nearly(80.0, 10.0, 80.000001, 10.000005, tol, NearlyMode::And);
+    assert!(nearly.lat && nearly.lon && nearly.combined);
+    let nearly = compute_nearly(80.0, 10.0, 80.000002, 10.000005, tol, NearlyMode::And);
+    assert!(!nearly.lat && nearly.lon && !nearly.combined);
+    assert!(!compute_nearly(80.0, 10.0, 80.0, 10.000005, GeoTolerance::DEFAULT, NearlyMode::And).lon);
+
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        North,80.0,10.0,Next,80.000001,10.000005\n";
+    let row = |name, args: &[&str]| -> Result<String, AppError> {
+        let out = run_fixture(name, input, &[&["-f", "dd"], args].concat())?;
+        Ok(out.lines().nth(1).unwrap_or_default().to_string())
+    };
+    assert!(row("tol_default", &[])?.ends_with(",true,false,false"));
+    assert!(row("tol_lon", &["--tolerance-lon", "6e-6"])?.ends_with(",true,true,true"));
+    // --tolerance sets both axes, --tolerance-lat overrides it.
+    assert!(row("tol_both", &["--tolerance", "1e-5", "--tolerance-lat", "0"])?.ends_with(",false,true,false"));
+
+    assert!(Cli::try_parse_from(["ektaon", "-i", "in", "-o", "out", "-f", "dd", "--tolerance", "-1"]).is_err());
+    assert!(Cli::try_parse_from(["ektaon", "-i", "in", "-o", "out", "-f", "dd", "--tolerance", "0", "--exact-match"]).is_err());
+    Ok(())
+}
+
+/* --- Snap zero --------------------*/
+
+#[test]
+fn test_snap_zero() -> Result<(), AppError> {
+    // 1e-6° apart: nearly_both, but a nonzero distance, made visible
+    // by a large radius (0.11 m on the Earth).
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Same,0.0,2.2945,Copy,0.000001,2.2945\n";
+    let args = ["-f", "dd", "--output-format", "ndjson", "--earth-radius", "100000000"];
+
+    let out = run_fixture("snap_zero_off", input, &args)?;
+    let row: serde_json::Value = serde_json::from_str(&out)?;
+    assert_eq!(row["nearly_both"], true);
+    assert_eq!(row["distance_km"], 1.75);
+
+    let out = run_fixture("snap_zero_on", input, &[&args[..], &["--snap-zero"]].concat())?;
+    let row: serde_json::Value = serde_json::from_str(&out)?;
+    assert_eq!(row["distance_km"], 0.0);
+    assert_eq!(row["distance_miles"], 0.0);
+    Ok(())
+}
+
+/* --- Long arc --------------------*/
+
+#[test]
+fn test_long_arc_completes_circumference() -> Result<(), AppError> {
+    let out = run_fixture("long_arc", DD_FIXTURE, &["-f", "dd", "--emit-long-arc", "--output-format", "ndjson"])?;
+    let circumference = round(std::f64::consts::TAU * EARTH_RADIUS_KM, 2);
+
+    for line in out.lines() {
+        let row: serde_json::Value = serde_json::from_str(line)?;
+        let short = row["distance_km"].as_f64().unwrap();
+        let long = row["distance_long_km"].as_f64().unwrap();
+        assert!((short + long - circumference).abs() <= 0.01);
+    }
+    // Same point: the long way is the whole circle.
+    assert_eq!(long_arc_km(0.0, 3389.5), std::f64::consts::TAU * 3389.5);
+    Ok(())
+}
+
+/* --- Antipodal --------------------*/
+
+#[test]
+fn test_is_antipodal() {
+    let tol = GeoTolerance::DEFAULT;
+    assert!(is_antipodal(48.8567, 2.3486, -48.8567, -177.6514, tol));
+    assert!(is_antipodal(0.0, 179.5, 0.0, -0.5, tol));
+    // Poles: any longitude.
+    assert!(is_antipodal(90.0, 10.0, -90.0, 75.0, tol));
+    assert!(!is_antipodal(48.8567, 2.3486, -48.8567, 2.3486, tol));
+    assert!(!is_antipodal(48.8567, 2.3486, -48.8, -177.6514, tol));
+}
+
+#[test]
+fn test_antipodal_column() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Antipode,-48.8567,-177.6514\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n";
+    let out = run_fixture("antipodal", input, &["-f", "dd", "--flag-antipodal"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,antipodal"));
+    assert!(rows[1].ends_with(",true"));
+    assert!(rows[2].ends_with(",false"));
+    Ok(())
+}
+
+/* --- Colocated --------------------*/
+
+const COLOCATED_FIXTURE: &str = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+    Inside,0.0,0.0,Near,0.0,0.2\n\
+    Outside,0.0,0.0,Far,0.0,0.25\n";
+
+#[test]
+fn test_colocated_default_radius() -> Result<(), AppError> {
+    let out = run_fixture("colocated_default", COLOCATED_FIXTURE, &["-f", "dd", "--colocated-km"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,colocated"));
+    // 22.24 km and 27.8 km against the default 25 km.
+    assert!(rows[1].ends_with(",22.24,13.82,true,false,false,true"));
+    assert!(rows[2].ends_with(",27.8,17.27,true,false,false,false"));
+    Ok(())
+}
+
+#[test]
+fn test_colocated_custom_radius() -> Result<(), AppError> {
+    let out = run_fixture("colocated_custom", COLOCATED_FIXTURE, &["-f", "dd", "--colocated-km", "30"])?;
+    assert!(out.lines().skip(1).all(|row| row.ends_with(",true")));
+    Ok(())
+}
+
+/* --- Duplicate pairs --------------------*/
+
+#[test]
+fn test_skip_duplicate_pairs() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Lyon,45.75,4.833333,Paris,48.8567,2.3486\n\
+        Paris bis,48.85670000001,2.3486,Lyon bis,45.75,4.833333\n\
+        Paris,48.8567,2.3486,Tokyo,35.683333,139.683333\n";
+
+    let all = run_fixture("dedup_off", input, &["-f", "dd"])?;
+    assert_eq!(all.lines().count(), 5);
+
+    let out = run_fixture("dedup_on", input, &["-f", "dd", "--skip-duplicate-pairs"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[1].starts_with("1,Paris,"));
+    assert!(rows[2].starts_with("2,Paris,") && rows[2].contains(",Tokyo,"));
+    Ok(())
+}
+
+/* --- JSON input --------------------*/
+
+#[test]
+fn test_json_input_matches_csv_output() -> Result<(), AppError> {
+    let json = r#"[
+        {"name_a": "Paris", "lat_a": 48.8567, "lon_a": 2.3486,
+         "name_b": "Lyon", "lat_b": 45.75, "lon_b": 4.833333},
+        {"name_a": "Eiffel", "lat_a": 48.858056, "lon_a": 2.2945,
+         "name_b": "Copy", "lat_b": 48.858056, "lon_b": 2.2945}
+    ]"#;
+
+    let from_json = run_fixture("json_input", json, &["-f", "dd", "--input-format-file", "json"])?;
+    let from_csv = run_fixture("json_input_csv", DD_FIXTURE, &["-f", "dd"])?;
+    assert_eq!(from_json, from_csv);
+    Ok(())
+}
+
+#[test]
+fn test_json_input_dms_strings() -> Result<(), AppError> {
+    let json = r#"[{"name_a": "Eiffel", "lat_a": "48°51'29\"N", "lon_a": "2°17'40\"E",
+                     "name_b": "Liberty", "lat_b": "40°41'21\"N", "lon_b": "74°2'40\"W"}]"#;
+    let out = run_fixture("json_dms", json, &["-f", "dms", "--input-format-file", "json", "--strict"])?;
+    assert!(out.lines().nth(1).unwrap().starts_with("1,Eiffel,\"48°51'29\"\"N\","));
+    Ok(())
+}
+
+#[test]
+fn test_json_input_not_an_array() {
+    let res = run_fixture("json_object", r#"{"name_a": "x"}"#, &["-f", "dd", "--input-format-file", "json"]);
+    assert!(matches!(res, Err(AppError::Input(InputError::NotAnArray))));
+}
+
+#[test]
+fn test_json_input_streamed_errors() -> Result<(), AppError> {
+    let row = r#"{"name_a": "Paris", "lat_a": 48.8567, "lon_a": 2.3486,
+                  "name_b": "Lyon", "lat_b": 45.75, "lon_b": 4.833333}"#;
+    let args = ["-f", "dd", "--input-format-file", "json"];
+
+    // A non-object element is one invalid row.
+    let out = run_fixture("json_stream_element", &format!("[{row}, 7, {row}]"), &args)?;
+    assert_eq!(out.lines().count(), 3);
+
+    // A file cut in the middle stops the run.
+    let res = run_fixture("json_stream_cut", &format!("[{row}, {row}"), &args);
+    assert!(matches!(res, Err(AppError::Input(InputError::Json(_)))));
+    Ok(())
+}
+
+/* --- XLSX input --------------------*/
+
+// Writes `rows` (header first) to a `Points` sheet preceded by an empty
+// sheet, then runs the pipeline on it. Numbers are written as numbers.
+#[cfg(feature = "xlsx")]
+fn run_xlsx(name: &str, rows: &[&[&str]], args: &[&str]) -> Result<String, AppError> {
+    let dir = fixture_dir(name);
+    let (input, output) = (dir.join("input.xlsx"), dir.join("output"));
+
+    let mut workbook = rust_xlsxwriter::Workbook::new();
+    workbook.add_worksheet().set_name("Notes").unwrap();
+    let sheet = workbook.add_worksheet().set_name("Points").unwrap();
+    for (r, row) in rows.iter().enumerate() {
+        for (c, value) in row.iter().enumerate() {
+            let (r, c) = (r as u32, c as u16);
+            match value.parse::<f64>() {
+                Ok(n) => sheet.write_number(r, c, n).unwrap(),
+                Err(_) => sheet.write_string(r, c, *value).unwrap(),
+            };
+        }
+    }
+    workbook.save(&input).unwrap();
+
+    let mut argv = vec!["ektaon", "-i", input.to_str().unwrap(), "-o", output.to_str().unwrap()];
+    argv.extend(["--input-format-file", "xlsx"]);
+    argv.extend(args);
+    run(&Cli::parse_from(argv))?;
+    Ok(std::fs::read_to_string(&output)?)
+}
+
+#[cfg(feature = "xlsx")]
+#[test]
+fn test_xlsx_input_matches_csv_output() -> Result<(), AppError> {
+    let rows: &[&[&str]] = &[
+        &["name_a", "lat_a", "lon_a", "name_b", "lat_b", "lon_b"],
+        &["Paris", "48.8567", "2.3486", "Lyon", "45.75", "4.833333"],
+        &["Eiffel", "48.858056", "2.2945", "Copy", "48.858056", "2.2945"],
+    ];
+    let from_xlsx = run_xlsx("xlsx_input", rows, &["-f", "dd", "--sheet", "Points"])?;
+    let from_csv = run_fixture("xlsx_input_csv", DD_FIXTURE, &["-f", "dd"])?;
+    assert_eq!(from_xlsx, from_csv);
+
+    let res = run_xlsx("xlsx_unknown_sheet", rows, &["-f", "dd", "--sheet", "Missing"]);
+    assert!(matches!(res, Err(AppError::Input(InputError::UnknownSheet(s))) if s == "Missing"));
+    Ok(())
+}
+
+#[cfg(feature = "xlsx")]
+#[test]
+fn test_xlsx_input_required_headers() {
+    let rows: &[&[&str]] = &[
+        &["name_a", "lat_a", "lon_a", "name_b", "lat_b"],
+        &["Paris", "48.8567", "2.3486", "Lyon", "45.75"],
+    ];
+    let res = run_xlsx("xlsx_headers", rows, &["-f", "dd", "--sheet", "Points"]);
+    assert!(matches!(res, Err(AppError::MissingHeaderField(h)) if h == "lon_b"));
+}
+
+#[test]
+fn test_check_earth_distance() -> Result<(), AppError> {
+    // Antipodes on any Earth radius in use: at most half the circumference.
+    for radius in [EARTH_RADIUS_KM, 6378.137] {
+        let km = haversine_with_radius(0.0, 0.0, 0.0, 180.0, radius)?;
+        assert_eq!(check_earth_distance(km)?, km);
+    }
+    // Meters given as kilometers.
+    let km = haversine_with_radius(48.8567, 2.3486, 45.75, 4.833333, 6_371_000.0)?;
+    assert!(matches!(check_earth_distance(km), Err(HaversineError::ImplausibleDistance { .. })));
+
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\nParis,48.8567,2.3486,Tokyo,35.6762,139.6503\n";
+    assert!(run_fixture("check_distance_ok", input, &["-f", "dd", "--check-distance"]).is_ok());
+    let res = run_fixture("check_distance", input, &["-f", "dd", "--check-distance", "--earth-radius", "20000"]);
+    assert!(matches!(res, Err(AppError::Distance(HaversineError::ImplausibleDistance { .. }))));
+    // Other bodies are not checked without the flag.
+    assert!(run_fixture("check_distance_off", input, &["-f", "dd", "--earth-radius", "69911"]).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_checked_distance_snaps_noise() {
+    assert_eq!(checked_distance(-1e-13).unwrap(), 0.0);
+    assert!(checked_distance(-1e-13).unwrap().is_sign_positive());
+    assert_eq!(checked_distance(-GEO_PRECISION).unwrap(), 0.0);
+    assert_eq!(checked_distance(12.5).unwrap(), 12.5);
+    assert!(matches!(checked_distance(-1e-6), Err(HaversineError::NegativeDistance { .. })));
+    assert!(matches!(checked_distance(f64::NAN), Err(HaversineError::InvalidDistance)));
+}
+
+/* --- ISO 6709 --------------------*/
+
+#[test]
+fn test_dd_to_iso6709() {
+    assert_eq!(dd_to_iso6709(48.858056, 2.2945), "+48.858056+002.294500/");
+    assert_eq!(dd_to_iso6709(-33.856784, -70.5), "-33.856784-070.500000/");
+    assert_eq!(dd_to_iso6709(-0.0, -179.999999), "+00.000000-179.999999/");
+    assert_eq!(dd_to_iso6709(-5.25, 0.0), "-05.250000+000.000000/");
+}
+
+#[test]
+fn test_emit_formats() -> Result<(), AppError> {
+    let out = run_fixture("emit_formats", DD_FIXTURE, &["-f", "dd", "--emit-formats", "ddm,iso"])?;
+    let header: Vec<&str> = out.lines().next().unwrap().split(',').collect();
+    assert!(!header.contains(&"lat_a_dms"));
+    assert_eq!(&header[5..8], ["lon_a_dd", "lat_a_ddm", "lon_a_ddm"]);
+    assert!(header.contains(&"point_b_iso"));
+    assert!(out.lines().nth(1).unwrap().contains(",48°51.4020'N,2°20.9160'E,"));
+
+    // Default: the DMS columns only.
+    let out = run_fixture("emit_formats_default", DD_FIXTURE, &["-f", "dd"])?;
+    assert!(out.lines().next().unwrap().contains(",lat_a_dms,lon_a_dms,name_b,"));
+
+    let res = run_fixture("emit_formats_columns", DD_FIXTURE, &["-f", "dd", "--emit-formats", "ddm", "--columns", "lat_a_dms"]);
+    assert!(matches!(res, Err(AppError::ColumnNotWritten("lat_a_dms"))));
+    Ok(())
+}
+
+#[test]
+fn test_iso6709_columns() -> Result<(), AppError> {
+    let out = run_fixture("iso6709", DD_FIXTURE, &["-f", "dd", "--iso6709"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,point_a_iso,point_b_iso"));
+    assert!(rows[1].ends_with(",+48.856700+002.348600/,+45.750000+004.833333/"));
+    Ok(())
+}
+
+/* --- WKT --------------------*/
+
+#[test]
+fn test_wkt_helpers() {
+    let half_up = RoundingMode::HalfUp;
+    assert_eq!(to_wkt_point(48.858056, 2.2945, None, half_up), "POINT(2.2945 48.858056)");
+    assert_eq!(to_wkt_point(-0.0, -179.999999, None, half_up), "POINT(-179.999999 0)");
+    assert_eq!(to_wkt_point(1e-6, 180.0, None, half_up), "POINT(180 0.000001)");
+    assert_eq!(to_wkt_point(-0.001, 2.345, Some(2), half_up), "POINT(2.35 0.00)");
+    assert_eq!(
+        to_wkt_linestring((48.8567, 2.3486), (45.75, 4.833333), None, half_up),
+        "LINESTRING(2.3486 48.8567, 4.833333 45.75)"
+    );
+}
+
+#[test]
+fn test_wkt_columns() -> Result<(), AppError> {
+    let out = run_fixture("wkt", DD_FIXTURE, &["-f", "dd", "--wkt"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,geom_a,geom_b,geom_line"));
+    assert!(rows[1].ends_with(
+        ",POINT(2.3486 48.8567),POINT(4.833333 45.75),\"LINESTRING(2.3486 48.8567, 4.833333 45.75)\""
+    ));
+
+    // Same decimals as the *_dd columns.
+    let out = run_fixture("wkt_precision", DD_FIXTURE, &["-f", "dd", "--wkt", "--coord-precision", "2"])?;
+    let row = out.lines().nth(1).unwrap();
+    assert!(row.contains(",48.86,2.35,"), "{row}");
+    assert!(row.ends_with(",POINT(2.35 48.86),POINT(4.83 45.75),\"LINESTRING(2.35 48.86, 4.83 45.75)\""), "{row}");
+    Ok(())
+}
+
+/* --- Encoded polyline --------------------*/
+
+#[test]
+fn test_encode_polyline() {
+    // Example of the Google polyline algorithm documentation.
+    let points = [(38.5, -120.2), (40.7, -120.95), (43.252, -126.453)];
+    assert_eq!(encode_polyline(&points), "_p~iF~ps|U_ulLnnqC_mqNvxq`@");
+    assert_eq!(encode_polyline(&[]), "");
+    assert_eq!(encode_polyline(&[(0.0, 0.0), (-0.000001, 0.000004)]), "????");
+}
+
+#[test]
+fn test_polyline_column() -> Result<(), AppError> {
+    let args = ["-f", "dd", "--emit-polyline", "--columns", "name_a,polyline"];
+    let out = run_fixture("polyline", DD_FIXTURE, &args)?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows[..2], ["name_a,polyline", "Paris,kieiHwuiMzw}QqhdN"]);
+
+    // N points along the arc: A and B first and last.
+    let out = run_fixture("polyline_arc", DD_FIXTURE, &["-f", "dd", "--emit-polyline", "5", "--columns", "polyline"])?;
+    let arc = out.lines().nth(1).unwrap().to_string();
+    assert!(arc.starts_with("kieiHwuiM") && arc.len() > rows[1].len());
+    Ok(())
+}
+
+/* --- Grid --------------------*/
+
+#[test]
+fn test_grid_cell_around_zero() {
+    assert_eq!(grid_cell(0.0, 0.0, 1.0), (0, 0));
+    assert_eq!(grid_cell(0.5, -0.5, 1.0), (0, -1));
+    assert_eq!(grid_cell(-0.000001, 0.999999, 1.0), (-1, 0));
+    assert_eq!(grid_cell(-1.0, -1.000001, 1.0), (-1, -2));
+    // Boundaries stay in their own cell despite floating-point noise.
+    assert_eq!(grid_cell(0.3, -0.3, 0.1), (3, -3));
+    assert_eq!(grid_cell(48.8567, 2.3486, 0.25), (195, 9));
+}
+
+#[test]
+fn test_grid_columns() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lima,-12.0464,-77.0428\n";
+    let out = run_fixture("grid", input, &["-f", "dd", "--grid-size", "5"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,cell_a,cell_b"));
+    assert!(rows[1].ends_with(",9_0,-3_-16"));
+
+    assert!(Cli::try_parse_from(["ektaon", "-i", "in", "-o", "out", "-f", "dd", "--grid-size", "0"]).is_err());
+    Ok(())
+}
+
+/* --- Web Mercator --------------------*/
+
+#[test]
+fn test_to_web_mercator() {
+    assert_eq!(to_web_mercator(0.0, 0.0), (0.0, 0.0));
+    // Eiffel Tower (EPSG:3857 reference values).
+    let (x, y) = to_web_mercator(48.858056, 2.2945);
+    assert!((x - 255_422.57).abs() < 0.01 && (y - 6_250_810.70).abs() < 0.01);
+    // Corner of the square world map, and the clamp beyond it.
+    let (x, y) = to_web_mercator(WEB_MERCATOR_MAX_LAT, 180.0);
+    assert!((x - 20_037_508.34).abs() < 0.01 && (y - 20_037_508.34).abs() < 0.01);
+    assert_eq!(to_web_mercator(90.0, 0.0).1, to_web_mercator(WEB_MERCATOR_MAX_LAT, 0.0).1);
+    assert_eq!(to_web_mercator(-89.0, -180.0), (-x, -y));
+}
+
+#[test]
+fn test_mercator_columns() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Eiffel,48.858056,2.2945,Lima,-12.0464,-77.0428\n";
+    let out = run_fixture("mercator", input, &["-f", "dd", "--emit-mercator"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,x_a,y_a,x_b,y_b"));
+    assert!(rows[1].ends_with(",255422.57,6250810.7,-8576365.27,-1350989.48"));
+    Ok(())
+}
+
+/* --- Time zones --------------------*/
+
+#[test]
+fn test_nautical_tz() {
+    assert_eq!(nautical_tz(0.0), 0);
+    assert_eq!(nautical_tz(2.3486), 0);
+    assert_eq!(nautical_tz(-77.0428), -5);
+    assert_eq!(nautical_tz(139.6917), 9);
+    assert_eq!(nautical_tz(7.5), 1);
+    assert_eq!(nautical_tz(-7.4), 0);
+    // The 180° meridian splits zone 12.
+    assert_eq!(nautical_tz(180.0), 12);
+    assert_eq!(nautical_tz(-180.0), -12);
+    assert_eq!(nautical_tz(179.9), 12);
+    assert_eq!(nautical_tz(-172.4), -11);
+}
+
+#[test]
+fn test_tz_columns() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lima,-12.0464,-77.0428\n";
+    let out = run_fixture("tz", input, &["-f", "dd", "--emit-tz"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,tz_a,tz_b"));
+    assert!(rows[1].ends_with(",0,-5"));
+    Ok(())
+}
+
+/* --- Subcommands --------------------*/
+
+#[test]
+fn test_subcommands_and_flat_syntax() {
+    let app = parse_app(["ektaon", "-i", "in.csv", "-o", "out.csv", "-f", "dd"]);
+    assert!(app.command.is_none() && app.convert.is_some_and(|cli| !cli.validate));
+
+    let app = parse_app(["ektaon", "convert", "-i", "in.csv", "-o", "out.csv", "-f", "dd"]);
+    assert!(matches!(app.command, Some(Command::Convert(cli)) if cli.output.is_some()));
+
+    let app = parse_app(["ektaon", "validate", "-i", "in.csv", "-f", "dd"]);
+    assert!(matches!(app.command, Some(Command::Validate(cli)) if cli.validate && cli.output.is_none()));
+
+    // Options and subcommands do not mix.
+    assert!(App::try_parse_from(["ektaon", "-i", "in.csv", "convert"]).is_err());
+}
+
+#[test]
+fn test_config_file() -> Result<(), AppError> {
+    let dir = fixture_dir("config");
+    let config = dir.join("ektaon.toml");
+    std::fs::write(&config, "\
+        input-format = \"dms\"\n\
+        dms-precision = 4\n\
+        tolerance = 1e-5\n\
+        strict = true\n\
+        tsv = false\n\
+        map = [\"lat_a=origin_lat\", \"lon_a=origin_lon\"]\n")?;
+    let config = config.to_str().unwrap();
+
+    // File values fill the options missing from the command line, required ones included.
+    let app = parse_app(["ektaon", "--config", config, "-i", "in.csv", "-o", "out.csv"]);
+    let cli = app.convert.expect("flat syntax");
+    assert!(matches!(cli.input_format, InputFormat::Dms));
+    assert_eq!((cli.dms_precision, cli.tolerance, cli.strict, cli.tsv), (4, Some(1e-5), true, false));
+    assert_eq!(cli.header_map.len(), 2);
+
+    // The command line wins, also after a subcommand.
+    let app = parse_app(["ektaon", "validate", "-i", "in.csv", "--config", config, "-f", "dd", "--dms-precision", "1"]);
+    let Some(Command::Validate(cli)) = app.command else {
+        panic!("expected the validate subcommand");
+    };
+    assert!(matches!(cli.input_format, InputFormat::Dd));
+    assert_eq!((cli.dms_precision, cli.tolerance), (1, Some(1e-5)));
+    Ok(())
+}
+
+#[test]
+fn test_distance_subcommand() -> Result<(), AppError> {
+    let args = |from: &str, to: &str, extra: &[&str]| {
+        let mut argv = vec!["ektaon", "distance", "--from", from, "--to", to];
+        argv.extend(extra);
+        match parse_app(argv).command {
+            Some(Command::Distance(args)) => args,
+            other => panic!("unexpected command {other:?}"),
+        }
+    };
+    assert_eq!(round(point_distance(&args("48.8567,2.3486", "45.75,4.833333", &[]))?, 2), 392.93);
+    assert_eq!(round(point_distance(&args("0,0", "0,-1", &["--earth-radius", "3389.5"]))?, 2), 59.16);
+
+    let dms = args("48°51'24\"N, 2°20'55\"E", "45°45'0\"N,4°50'0\"E", &["-f", "dms"]);
+    assert_eq!(round(point_distance(&dms)?, 0), 393.0);
+
+    let res = point_distance(&args("48.8567", "45.75,4.833333", &[]));
+    assert!(matches!(res, Err(AppError::InvalidPointArg { field: "--from", .. })));
+    let res = point_distance(&args("48.8567,2.3486", "95,4.8", &[]));
+    assert!(matches!(res, Err(AppError::InvalidPointArg { field: "--to", .. })));
+    Ok(())
+}
+
+#[test]
+fn test_pair_subcommand() -> Result<(), AppError> {
+    let app = parse_app([
+        "ektaon", "pair", "--a", "48°51'29\"N,2°17'40\"E", "--b", "40°41'21\"N,74°2'40\"W", "-f", "dms",
+    ]);
+    let Some(Command::Pair(args)) = app.command else {
+        panic!("expected the pair subcommand");
+    };
+    assert_eq!(pair_report(&args)?, "distance_km: 5837.42\ndistance_miles: 3627.21\nbearing_deg: 291.76 (WNW)\n");
+
+    let app = parse_app(["ektaon", "pair", "--a", "48.85", "--b", "0,0"]);
+    let Some(Command::Pair(args)) = app.command else {
+        panic!("expected the pair subcommand");
+    };
+    assert!(matches!(pair_report(&args), Err(AppError::InvalidPointArg { field: "--a", .. })));
+    Ok(())
+}
+
+/* --- Fixed DD decimals --------------------*/
+
+#[test]
+fn test_coord_precision_fixed_decimals() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        A,48,2.0,B,45.75,-4.8333333\n";
+    let out = run_fixture("coord_default", input, &["-f", "dd"])?;
+    assert!(out.lines().nth(1).unwrap().starts_with("1,A,48,2.0,48.0,2.0,"));
+
+    let out = run_fixture("coord_fixed", input, &["-f", "dd", "--coord-precision", "6"])?;
+    let row = out.lines().nth(1).unwrap();
+    assert!(row.starts_with("1,A,48,2.0,48.000000,2.000000,"));
+    assert!(row.contains(",B,45.75,-4.8333333,45.750000,-4.833333,"));
+
+    let out = run_fixture("coord_fixed_3", input, &["-f", "dd", "--coord-precision", "3", "--quote-style", "never"])?;
+    assert!(out.lines().nth(1).unwrap().contains(",45.75,-4.8333333,45.750,-4.833,"));
+    Ok(())
+}
+
+#[test]
+fn test_number_locale_fr() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,New York,40.7128,-74.006\n";
+    let out = run_fixture("number_fr", input, &["-f", "dd", "--number-locale", "fr"])?;
+    let row = out.lines().nth(1).unwrap();
+    // Input columns keep their text, DD and distance columns are localized
+    // (and quoted, as they contain the delimiter).
+    assert!(row.starts_with("1,Paris,48.8567,2.3486,\"48,8567\",\"2,3486\","), "{row}");
+    assert!(row.contains(",\"5\u{a0}836,99\",\"3\u{a0}626,94\","), "{row}");
+
+    assert_eq!(localize_number("-1234567.5", Locale::Fr), "-1\u{a0}234\u{a0}567,5");
+    assert_eq!(localize_number("123", Locale::Fr), "123");
+    assert_eq!(localize_number("1e-6", Locale::Fr), "1e-6");
+    assert_eq!(localize_number("1234.5", Locale::En), "1234.5");
+    Ok(())
+}
+
+#[test]
+fn test_number_locale_every_column() -> Result<(), AppError> {
+    let input = "name_a\tlat_a\tlon_a\tname_b\tlat_b\tlon_b\n\
+        Paris\t48.8567\t2.3486\tNew York\t40.7128\t-74.006\n";
+    let args = ["-f", "dd", "--emit-bearing", "--emit-deltas", "--number-locale", "fr", "--tsv"];
+    let out = run_fixture("number_fr_all", input, &args)?;
+    let row: Vec<&str> = out.lines().nth(1).unwrap().split('\t').collect();
+    assert_eq!(row[0], "1");
+    assert_eq!(row[6], "48°51'24,12\"N");
+    assert!(row.contains(&"5\u{a0}836,99"), "{row:?}");
+    assert!(row.contains(&"291,79"), "{row:?}");
+    assert!(row.contains(&"8,1439"), "{row:?}");
+    // Only the *_in columns keep a decimal point.
+    let inputs = [2, 3, 9, 10];
+    let points = row.iter().enumerate().filter(|(i, field)| field.contains('.') && !inputs.contains(i));
+    assert_eq!(points.count(), 0, "{row:?}");
+
+    let input = "name\tlat\tlon\nA\t0\t0\nB\t0\t10\n";
+    let out = run_fixture("number_fr_track", input, &["-f", "dd", "--track", "--number-locale", "fr", "--tsv"])?;
+    assert_eq!(out.lines().nth(2), Some("2\tB\t0\t10\t0,0\t10,0\t1\u{a0}111,95\t1\u{a0}111,95"));
+    Ok(())
+}
+
+#[test]
+fn test_distance_precisions() -> Result<(), AppError> {
+    let columns = ["-f", "dd", "--columns", "distance_km,distance_miles"];
+    let out = run_fixture("distance_precision", DD_FIXTURE, &columns)?;
+    assert_eq!(out.lines().nth(1), Some("392.93,244.16"));
+
+    let args = [&columns[..], &["--km-precision", "0", "--miles-precision", "4"]].concat();
+    let out = run_fixture("distance_precision_split", DD_FIXTURE, &args)?;
+    assert_eq!(out.lines().nth(1), Some("393.0,244.1568"));
+    Ok(())
+}
+
+#[test]
+fn test_coord_precision_other_modes() -> Result<(), AppError> {
+    let input = "name,lat,lon,note\n\
+        P,0,0,x\n";
+    let out = run_fixture("coord_track", input, &["-f", "dd", "--track", "--coord-precision", "2"])?;
+    assert_eq!(out.lines().nth(1), Some("1,P,0,0,0.00,0.00,0.0,0.0"));
+
+    let out = run_fixture("coord_passthrough", DD_FIXTURE, &["-f", "dd", "--coord-precision", "1", "--passthrough"])?;
+    assert!(out.lines().nth(1).unwrap().starts_with("1,Paris,48.8567,2.3486,48.9,2.3,"));
+    Ok(())
+}
+
+/* --- Track --------------------*/
+
+#[test]
+fn test_track_cumulative_length() -> Result<(), AppError> {
+    let input = "name,lat,lon\n\
+        Paris,48.8567,2.3486\n\
+        Lyon,45.75,4.833333\n\
+        Marseille,43.296482,5.36978\n";
+    let out = run_fixture("track", input, &["-f", "dd", "--track"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows[0], "id,name,lat_in,lon_in,lat_dd,lon_dd,segment_km,cumulative_km");
+    assert!(rows[1].ends_with(",0.0,0.0"));
+    assert!(rows[2].ends_with(",392.93,392.93"));
+    assert!(rows[3].ends_with(",276.11,669.04"));
+
+    let out = run_fixture("track_precision", input, &["-f", "dd", "--track", "--km-precision", "1"])?;
+    assert!(out.lines().nth(3).unwrap().ends_with(",276.1,669.0"));
+    Ok(())
+}
+
+#[test]
+fn test_track_skips_invalid_points() -> Result<(), AppError> {
+    // The segment after an invalid row starts from the last valid point.
+    let input = "name,lat,lon\n\
+        Paris,48.8567,2.3486\n\
+        Bad,abc,2.3486\n\
+        Lyon,45.75,4.833333\n";
+    let out = run_fixture("track_invalid", input, &["-f", "dd", "--track"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[2].starts_with("2,Lyon,") && rows[2].ends_with(",392.93,392.93"));
+    Ok(())
+}
+
+/* --- Memory cap --------------------*/
+
+#[test]
+fn test_sort_by() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Three,0,0,B,0,3\n\
+        One,0,0,B,0,1\n\
+        Two,0,0,B,0,2\n\
+        Bis,0,0,B,0,1\n";
+
+    // Shortest first, ties in input order; `id` is the input order.
+    let out = run_fixture("sort_distance", input, &["-f", "dd", "--sort-by", "distance"])?;
+    let names: Vec<String> = out.lines().skip(1).map(|row| row.split(',').take(2).collect::<Vec<_>>().join(",")).collect();
+    assert_eq!(names, ["2,One", "4,Bis", "3,Two", "1,Three"]);
+
+    // --limit applies after the sort.
+    let out = run_fixture("sort_name", input, &["-f", "dd", "--sort-by", "name_a", "--limit", "2"])?;
+    let names: Vec<String> = out.lines().skip(1).map(|row| row.split(',').take(2).collect::<Vec<_>>().join(",")).collect();
+    assert_eq!(names, ["4,Bis", "2,One"]);
+
+    // Held rows count for --max-memory.
+    let res = run_fixture("sort_memory", input, &["-f", "dd", "--sort-by", "distance", "--max-memory", "3"]);
+    assert!(matches!(res, Err(AppError::MemoryLimit { line: 5, limit: 3 })));
+    Ok(())
+}
+
+#[test]
+fn test_max_memory_dedup() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Lyon,45.75,4.833333,Paris,48.8567,2.3486\n\
+        Paris,48.8567,2.3486,Tokyo,35.683333,139.683333\n";
+
+    // Two distinct pairs: the duplicate does not count.
+    let args = ["-f", "dd", "--skip-duplicate-pairs", "--max-memory", "2"];
+    assert_eq!(run_fixture("memory_ok", input, &args)?.lines().count(), 3);
+
+    let args = ["-f", "dd", "--skip-duplicate-pairs", "--max-memory", "1"];
+    let res = run_fixture("memory_dedup", input, &args);
+    assert!(matches!(res, Err(AppError::MemoryLimit { line: 4, limit: 1 })));
+    Ok(())
+}
+
+#[test]
+fn test_max_memory_cross_group() {
+    let input = "name,lat,lon,kind\n\
+        Paris,48.8567,2.3486,office\n\
+        Lyon,45.75,4.833333,office\n\
+        Lille,50.6292,3.0573,store\n";
+    let args = ["-f", "dd", "--cross-group", "kind", "--max-memory", "2"];
+    let res = run_fixture("memory_cross_group", input, &args);
+    assert!(matches!(res, Err(AppError::MemoryLimit { line: 4, limit: 2 })));
+}
+
+/* --- Column selection --------------------*/
+
+#[test]
+fn test_columns_subset_in_order() -> Result<(), AppError> {
+    let args = ["-f", "dd", "--emit-bearing", "--columns", "id,distance_km,name_b,name_a,bearing_compass"];
+    let out = run_fixture("columns", DD_FIXTURE, &args)?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows[0], "id,distance_km,name_b,name_a,bearing_compass");
+    assert_eq!(rows[1], "1,392.93,Lyon,Paris,SSE");
+
+    let args = ["-f", "dd", "--output-format", "ndjson", "--columns", "name_a,distance_km"];
+    let out = run_fixture("columns_ndjson", DD_FIXTURE, &args)?;
+    assert_eq!(out.lines().next(), Some(r#"{"name_a":"Paris","distance_km":392.93}"#));
+    Ok(())
+}
+
+#[test]
+fn test_columns_validation() {
+    let parse = |columns: &str| {
+        Cli::try_parse_from(["ektaon", "-i", "in", "-o", "out", "-f", "dd", "--columns", columns])
+    };
+    assert!(parse("id,distance_km").is_ok());
+    assert!(parse("id,distance").is_err());
+
+    // Known column, but its option is not enabled.
+    let res = run_fixture("columns_missing", DD_FIXTURE, &["-f", "dd", "--columns", "id,bearing_deg"]);
+    assert!(matches!(res, Err(AppError::ColumnNotWritten("bearing_deg"))));
+}
+
+#[test]
+fn test_output_columns_match_record() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b,elev_a,elev_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333,35,170\n";
+    let args = [
+        "-f", "dd", "--emit-long-arc", "--colocated-km", "--flag-antipodal", "--emit-lon-sep", "--flag-antimeridian",
+        "--emit-bearing", "--emit-formats", "dms,ddm,iso", "--emit-polyline", "--wkt", "--grid-size", "1", "--sanity-check",
+        "--flag-seconds-rounded", "--to-pole", "north", "--datum", "wgs84", "--emit-deltas",
+        "--emit-format-used", "--roundtrip-check", "--emit-tz", "--emit-mercator", "--repair",
+        "--expect-bbox", "-90,-180,90,180",
+    ];
+    // A second input file adds `source_file`.
+    let dir = fixture_dir("columns_all");
+    let second = dir.join("second.csv");
+    std::fs::write(&second, input)?;
+    let mut args = args.to_vec();
+    args.extend(["-i", second.to_str().unwrap()]);
+    let out = run_in(&dir, input, &args)?;
+    assert_eq!(out.lines().next(), Some(OUTPUT_COLUMNS.join(",").as_str()));
+    Ok(())
+}
+
+#[test]
+fn test_csv_fields_match_writer() -> Result<(), AppError> {
+    #[derive(Serialize)]
+    struct Row {
+        id: u64,
+        name: String,
+        small: f64,
+        whole: f64,
+        missing: Option<f64>,
+        empty: Option<Option<f64>>,
+        flag: bool,
+        format: InputFormat,
+    }
+    let row = |id, name: &str| Row {
+        id,
+        name: name.to_string(),
+        small: 1e-7,
+        whole: 2.0,
+        missing: None,
+        empty: Some(None),
+        flag: true,
+        format: InputFormat::Dms,
+    };
+    let csv_text = |writer: Writer<Vec<u8>>| -> Result<String, AppError> {
+        Ok(String::from_utf8(writer.into_inner().map_err(|e| e.into_error())?).unwrap())
+    };
+
+    let mut expected = Writer::from_writer(Vec::new());
+    expected.serialize(row(1, "a, \"b\""))?;
+    expected.serialize(row(2, "c"))?;
+    let expected = csv_text(expected)?;
+    assert_eq!(expected.lines().nth(2), Some("2,c,1e-7,2.0,,,true,dms"));
+
+    // The header is built once, the row replaced by every record.
+    let mut actual = Writer::from_writer(Vec::new());
+    let mut fields = CsvFields::default();
+    fields.fill(&row(1, "a, \"b\""))?;
+    actual.write_record(&fields.header)?;
+    actual.write_record(&fields.row)?;
+    fields.fill(&row(2, "c"))?;
+    actual.write_record(&fields.row)?;
+    assert_eq!(csv_text(actual)?, expected);
+    assert_eq!((fields.header.len(), fields.row.len()), (8, 8));
+    Ok(())
+}
+
+/* --- Multiple input files --------------------*/
+
+#[test]
+fn test_multiple_inputs() -> Result<(), AppError> {
+    let dir = fixture_dir("multiple_inputs");
+    let second = dir.join("second.csv");
+    std::fs::write(&second, "name_a,lat_a,lon_a,name_b,lat_b,lon_b\nNice,43.7,7.27,Lyon,45.75,4.833333\n")?;
+    let second = second.to_str().unwrap();
+
+    let out = run_in(&dir, DD_FIXTURE, &["-f", "dd", "-i", second])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows.len(), 4);
+    assert!(rows[0].ends_with(",nearly_both,source_file"));
+    assert!(rows[1].starts_with("1,Paris,") && rows[1].ends_with("input.csv"));
+    // Ids continue across files.
+    assert!(rows[3].starts_with("3,Nice,") && rows[3].ends_with("second.csv"));
+
+    // Every file must have the header of the first one.
+    std::fs::write(second, "name_a,lat_a,lon_a,name_b,lon_b,lat_b\nNice,43.7,7.27,Lyon,4.833333,45.75\n")?;
+    let res = run_in(&dir, DD_FIXTURE, &["-f", "dd", "-i", second]);
+    assert!(matches!(res, Err(AppError::InputHeaderMismatch(path)) if path.ends_with("second.csv")));
+
+    let res = run_in(&dir, DD_FIXTURE, &["-f", "dd", "-i", second, "--track"]);
+    assert!(matches!(res, Err(AppError::SingleInputMode("track"))));
+    Ok(())
+}
+
+/* --- Bounding box --------------------*/
+
+#[test]
+fn test_bbox_naive_and_wrapped() {
+    let mut bbox = BoundingBox::default();
+    assert_eq!(bbox.bounds(false), None);
+
+    bbox.add(10.0, 170.0);
+    bbox.add(-5.5, -170.0);
+    bbox.add(2.0, 175.25);
+    assert_eq!(bbox.bounds(false), Some([-5.5, -170.0, 10.0, 175.25]));
+    // Across the antimeridian: 170 -> -170 (20° wide), not -170 -> 175.25.
+    assert_eq!(bbox.bounds(true), Some([-5.5, 170.0, 10.0, -170.0]));
+
+    let mut bbox = BoundingBox::default();
+    bbox.add(48.8567, 2.3486);
+    bbox.add(45.75, 4.833333);
+    assert_eq!(bbox.bounds(true), bbox.bounds(false));
+    assert_eq!(bbox.bounds(true), Some([45.75, 2.3486, 48.8567, 4.833333]));
+}
+
+#[test]
+fn test_expect_bbox() -> Result<(), AppError> {
+    // Mainland France; Tokyo is not.
+    let input = "name_a,lat_a,lon_a,name_b,lat_b,lon_b\n\
+        Paris,48.8567,2.3486,Lyon,45.75,4.833333\n\
+        Paris,48.8567,2.3486,Tokyo,35.683333,139.683333\n";
+    let args = ["-f", "dd", "--expect-bbox", "41,-5,51,10", "--columns", "name_b,in_expected_bbox"];
+    let out = run_fixture("expect_bbox", input, &args)?;
+    assert_eq!(out, "name_b,in_expected_bbox\nLyon,true\nTokyo,false\n");
+
+    let mut strict = args.to_vec();
+    strict.push("--strict");
+    let res = run_fixture("expect_bbox_strict", input, &strict);
+    assert!(matches!(res, Err(AppError::OutsideExpectedBbox { line: 3, point: "B", .. })));
+
+    // Across the antimeridian (min_lon > max_lon), edges included.
+    assert!(bbox_contains([-20.0, 170.0, 0.0, -170.0], -10.0, 179.5));
+    assert!(bbox_contains([-20.0, 170.0, 0.0, -170.0], 0.0, -170.0));
+    assert!(!bbox_contains([-20.0, 170.0, 0.0, -170.0], -10.0, 0.0));
+
+    assert_eq!(parse_expect_bbox(" 41, -5, 51, 10"), Ok([41.0, -5.0, 51.0, 10.0]));
+    for bad in ["41,-5,51", "41,-5,51,10,0", "41,x,51,10", "41,-5,NaN,10", "51,-5,41,10", "41,-5,91,10"] {
+        assert!(parse_expect_bbox(bad).is_err(), "{bad}");
+    }
+    Ok(())
+}
+
+/* --- Destination --------------------*/
+
+#[test]
+fn test_destination_point_due_north() {
+    let (lat, lon) = destination_point(0.0, 0.0, 0.0, 111.0);
+    assert!((lat - 1.0).abs() < 0.01, "{lat}");
+    assert_eq!(round(lon, 6), 0.0);
+}
+
+#[test]
+fn test_destination_point_inverts_distance() -> Result<(), HaversineError> {
+    let (lat, lon) = destination_point(48.8567, 2.3486, 150.61, 392.93);
+    assert_eq!(round(haversine(48.8567, 2.3486, lat, lon)?, 2), 392.93);
+    assert_eq!(round(initial_bearing(48.8567, 2.3486, lat, lon), 2), 150.61);
+
+    // Crossing the antimeridian stays in [-180, 180].
+    let (_, lon) = destination_point(0.0, 179.5, 90.0, 111.19);
+    assert!((lon + 179.5).abs() < 0.01, "{lon}");
+    Ok(())
+}
+
+#[test]
+fn test_destination_mode() -> Result<(), AppError> {
+    let input = "name_a,lat_a,lon_a,bearing,distance_km\n\
+        Equator,0.0,0.0,0,111.19\n\
+        Bad,0.0,0.0,90,-5\n";
+    let out = run_fixture("destination", input, &["-f", "dd", "--destination"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows[0], "id,name_a,lat_a_in,lon_a_in,lat_a_dd,lon_a_dd,bearing_deg,distance_km,\
+        lat_b_dd,lon_b_dd,lat_b_dms,lon_b_dms");
+    assert!(rows[1].starts_with("1,Equator,0.0,0.0,0.0,0.0,0.0,111.19,0.999956,0.0,"));
+    assert_eq!(rows.len(), 2);
+
+    let err = run_fixture("destination_strict", input, &["-f", "dd", "--destination", "--strict"]);
+    assert!(matches!(err, Err(AppError::InvalidDestination { line: 3, field: "distance_km", .. })));
+    Ok(())
+}
+
+/* --- Cross group --------------------*/
+
+#[test]
+fn test_cross_group_pairs() -> Result<(), AppError> {
+    let input = "name,lat,lon,kind\n\
+        Warehouse 1,48.8567,2.3486,warehouse\n\
+        Store 1,45.75,4.833333,store\n\
+        Warehouse 2,43.2965,5.3698,warehouse\n\
+        Store 2,44.8378,-0.5792,store\n";
+
+    let out = run_fixture("cross_group", input, &["-f", "dd", "--cross-group", "kind"])?;
+    let rows: Vec<&str> = out.lines().collect();
+
+    // 2 x 2 cross pairs, no warehouse-warehouse nor store-store pair.
+    assert_eq!(rows.len(), 1 + 4);
+    assert!(rows[1].starts_with("1,Warehouse 1,") && rows[1].contains(",Store 1,"));
+    assert!(rows[2].starts_with("2,Warehouse 1,") && rows[2].contains(",Store 2,"));
+    assert!(rows[3].starts_with("3,Warehouse 2,") && rows[3].contains(",Store 1,"));
+    assert!(rows[4].starts_with("4,Warehouse 2,") && rows[4].contains(",Store 2,"));
+    Ok(())
+}
+
+#[test]
+fn test_anchor_mode() -> Result<(), AppError> {
+    let input = "name,lat,lon,site_id\n\
+        Lyon,45.75,4.833333,L1\n\
+        Bad,x,0,B1\n\
+        Versailles,48.8049,2.1204,V1\n";
+
+    let args = ["-f", "dd", "--anchor", "48.8567, 2.3486", "--sort-by", "distance", "--passthrough"];
+    let out = run_fixture("anchor", input, &args)?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert_eq!(rows.len(), 1 + 2);
+    assert!(rows[0].ends_with(",nearly_both,site_id"));
+    // The anchor is B, written as given; `id` keeps the input order.
+    assert!(rows[1].starts_with("2,Versailles,") && rows[1].contains(",anchor,48.8567,2.3486,48.8567,2.3486,"));
+    assert!(rows[1].ends_with(",V1"));
+    assert!(rows[2].starts_with("1,Lyon,") && rows[2].contains(",392.93,"));
+
+    let args = ["-f", "dms", "--anchor", "48°51'24\"N"];
+    assert!(matches!(
+        run_fixture("anchor_bad", input, &args),
+        Err(AppError::InvalidPointArg { field: "--anchor", .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_matrix_long() -> Result<(), AppError> {
+    let input = "name,lat,lon\n\
+        Paris,48.8567,2.3486\n\
+        Lyon,45.75,4.833333\n\
+        Marseille,43.2965,5.3698\n";
+    let pairs = |out: &str| -> Vec<String> {
+        out.lines().skip(1).map(|row| {
+            let fields: Vec<&str> = row.split(',').collect();
+            format!("{}-{}", fields[1], fields[8])
+        }).collect()
+    };
+
+    let out = run_fixture("matrix_long", input, &["-f", "dd", "--matrix", "long"])?;
+    assert_eq!(
+        pairs(&out),
+        ["Paris-Lyon", "Paris-Marseille", "Lyon-Paris", "Lyon-Marseille", "Marseille-Paris", "Marseille-Lyon"],
+    );
+
+    let out = run_fixture("matrix_upper", input, &["-f", "dd", "--matrix", "long", "--upper-triangular"])?;
+    assert_eq!(pairs(&out), ["Paris-Lyon", "Paris-Marseille", "Lyon-Marseille"]);
+    Ok(())
+}
+
+#[test]
+fn test_matrix_square() -> Result<(), AppError> {
+    let input = "name,lat,lon\n\
+        Paris,48.8567,2.3486\n\
+        Lyon,45.75,4.833333\n\
+        Marseille,43.2965,5.3698\n";
+
+    let out = run_fixture("matrix_square", input, &["-f", "dd", "--matrix", "square"])?;
+    assert_eq!(
+        out.lines().collect::<Vec<_>>(),
+        [
+            "name,Paris,Lyon,Marseille",
+            "Paris,0,392.93,660.59",
+            "Lyon,392.93,0,276.11",
+            "Marseille,660.59,276.11,0",
+        ],
+    );
+
+    let out = run_fixture("matrix_square_upper", input, &["-f", "dd", "--matrix", "square", "--upper-triangular"])?;
+    assert_eq!(out.lines().nth(3), Some("Marseille,,,0"));
+
+    let out = run_fixture("matrix_square_precision", input, &["-f", "dd", "--matrix", "square", "--km-precision", "4"])?;
+    assert_eq!(out.lines().nth(1), Some("Paris,0,392.9324,660.5863"));
+
+    let res = run_fixture("matrix_square_ndjson", input, &["-f", "dd", "--matrix", "square", "--output-format", "ndjson"]);
+    assert!(matches!(res, Err(AppError::SquareMatrix(_))));
+    Ok(())
+}
+
+#[test]
+fn test_cross_group_third_group() {
+    let input = "name,lat,lon,kind\nW,0,0,warehouse\nS,0,1,store\nX,0,2,depot\n";
+    let res = run_fixture("cross_group_third", input, &["-f", "dd", "--cross-group", "kind", "--strict"]);
+    assert!(matches!(res, Err(AppError::TooManyGroups { line: 4, .. })));
+}
+
+/* --- Fixups --------------------*/
+
+#[test]
+fn test_fixups_override_coordinates() -> Result<(), AppError> {
+    let dir = fixture_dir("fixups");
+    let fixups = dir.join("fixups.csv");
+    std::fs::write(
+        &fixups,
+        "by,key,lat,lon\nline_b,2,48.8567,2.3486\nname,Copy,45.75,4.833333\n",
+    )?;
+
+    let out = run_in(&dir, DD_FIXTURE, &["-f", "dd", "--fixups", fixups.to_str().unwrap()])?;
+    let rows: Vec<&str> = out.lines().collect();
+
+    // Line 2: point B (Lyon) moved onto Paris.
+    assert!(rows[1].contains(",Lyon,48.8567,2.3486,48.8567,2.3486,"));
+    assert!(rows[1].ends_with(",0.0,0.0,true,true,true"));
+    // Line 3: point B matched by name.
+    assert!(rows[2].contains(",Copy,45.75,4.833333,45.75,4.833333,"));
+    Ok(())
+}
+
+#[test]
+fn test_fixups_and_audits_single_point_modes() -> Result<(), AppError> {
+    let dir = fixture_dir("fixups_points");
+    let fixups = dir.join("fixups.csv");
+    std::fs::write(&fixups, "by,key,lat,lon\nline,2,1,1\nname,Lyon,45.75,4.833333\n")?;
+    let input = "name,lat,lon\nParis,48.8567,2.3486\nLyon,0,0\n";
+    let fixups = fixups.to_str().unwrap();
+
+    // Line 2 by line, line 3 by name, in every single-point mode.
+    let out = run_in(&dir, input, &["-f", "dd", "--anchor", "0,10", "--fixups", fixups, "--columns", "lat_a_dd,lon_a_dd"])?;
+    assert_eq!(out, "lat_a_dd,lon_a_dd\n1.0,1.0\n45.75,4.833333\n");
+    let out = run_in(&dir, input, &["-f", "dd", "--track", "--fixups", fixups])?;
+    assert_eq!(out.lines().nth(1), Some("1,Paris,1,1,1.0,1.0,0.0,0.0"));
+    let out = run_in(&dir, input, &["-f", "dd", "--matrix", "long", "--fixups", fixups, "--columns", "lat_b_dd"])?;
+    assert_eq!(out, "lat_b_dd\n45.75\n1.0\n");
+
+    // Audits see the single-point columns, once patched.
+    let cli = Cli::parse_from(["ektaon", "-i", "in", "-o", "out", "-f", "dd", "--fixups", fixups, "--format-report", "--precision-audit"]);
+    let headers = StringRecord::from(vec!["name", "lat", "lon"]);
+    let mut prep = RowPrep::new(&cli, &headers)?;
+    let mut record = StringRecord::from(vec!["Paris", "x", "y"]);
+    prep.apply(2, &headers, &mut record);
+    assert_eq!(&record[1], "1");
+    assert!(prep.format_report.unwrap().report().contains("  lat: 100.0% DD (1 value(s))"));
+    assert_eq!(prep.audit.unwrap().count("<= 4 decimals"), 2);
+    Ok(())
+}
+
+#[test]
+fn test_fixups_invalid_key() {
+    let dir = fixture_dir("fixups_invalid");
+    let fixups = dir.join("fixups.csv");
+    std::fs::write(&fixups, "by,key,lat,lon\nrow,2,0,0\n").unwrap();
+    assert!(matches!(
+        Fixups::load(&fixups),
+        Err(FixupError::UnknownKey { line: 2, .. })
+    ));
+}
+
+/* --- Datum --------------------*/
+
+#[test]
+fn test_nad27_to_wgs84() {
+    // Meades Ranch (Kansas), origin of NAD27, and its published NAD83
+    // position (within 1 m of WGS84 here).
+    let dms = |d: f64, m: f64, s: f64| d + m / 60.0 + s / 3600.0;
+    let (lat, lon) = nad27_to_wgs84(dms(39.0, 13.0, 26.686), -dms(98.0, 32.0, 30.506));
+    let km = haversine(lat, lon, dms(39.0, 13.0, 26.71), -dms(98.0, 32.0, 31.75)).unwrap();
+    assert!(km < 0.005, "{km}");
+
+    // No longitude shift at the pole, and the antimeridian is wrapped.
+    assert_eq!(nad27_to_wgs84(90.0, 10.0).1, 10.0);
+    assert!(nad27_to_wgs84(52.0, -180.0).1 > 179.0);
+}
+
+#[test]
+fn test_datum_column() -> Result<(), AppError> {
+    let out = run_fixture("datum", DD_FIXTURE, &["-f", "dd", "--datum", "nad27"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[0].ends_with(",nearly_both,datum"));
+    assert!(rows[1].starts_with("1,Paris,48.8567,2.3486,48.8567,2.3486,"));
+    assert!(rows[1].ends_with(",NAD27"));
+
+    // Shifted: the DD columns move, the input columns are kept.
+    let out = run_fixture("datum_shift", DD_FIXTURE, &["-f", "dd", "--datum", "nad27", "--datum-shift"])?;
+    let rows: Vec<&str> = out.lines().collect();
+    assert!(rows[1].starts_with("1,Paris,48.8567,2.3486,") && !rows[1].contains(",48.8567,2.3486,48.8567,"));
+    assert!(rows[1].ends_with(",WGS84"));
+
+    assert!(Cli::try_parse_from(["ektaon", "-i", "in", "-o", "out", "-f", "dd", "--datum-shift"]).is_err());
+    Ok(())
+}
@@ -1,24 +1,53 @@
 //src/util.rs
 
+use std::collections::BTreeSet;
+
 use serde::{Serialize};
 
 /* ---------------- CONSTANTES ---------------- */
 
 // Overall numerical precision used for geographical comparisons.
-const GEO_PRECISION: f64 = 1e-10;
+pub const GEO_PRECISION: f64 = 1e-10;
 // Average radius of the Earth in kilometers (spherical model).
-const EARTH_RADIUS_KM: f64 = 6371.0;
+pub const EARTH_RADIUS_KM: f64 = 6371.0;
 // Conversion factor kilometers → miles.
 pub const KM_TO_MILES: f64 = 0.621371;
+// Longest great-circle distance on the Earth (km): half the circumference
+// at the WGS84 equatorial radius (6378.137 km), the largest Earth radius in
+// use, so that every Earth model passes.
+pub const MAX_EARTH_DISTANCE_KM: f64 = std::f64::consts::PI * 6378.137;
 
 /* ---------------- NUMERIC UTILS -------------- */
 
+// Tie-breaking rule of the rounding.
+#[derive(Debug, Copy, Clone, Default, PartialEq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoundingMode {
+    // Half away from zero (2.5 -> 3, -2.5 -> -3), as f64::round.
+    #[default]
+    HalfUp,
+    // Half to even, a.k.a. banker's rounding (2.5 -> 2, 3.5 -> 4).
+    HalfEven,
+}
+
 // Rounding of a floating-point number to N decimal places (max 10).
 // Intentional limit to avoid excessively large exponents.
 pub fn round(value: f64, decimals: u32) -> f64 {
+    round_with(value, decimals, RoundingMode::HalfUp)
+}
+
+// Same as `round`, with the given tie-breaking rule.
+// Ties are exact halves of the scaled binary value: 2.675 is stored
+// slightly below and gives 2.67 in both modes.
+pub fn round_with(value: f64, decimals: u32, mode: RoundingMode) -> f64 {
     let precision = decimals.min(10);
     let factor = 10_f64.powi(precision as i32);
-    (value * factor).round() / factor
+    let scaled = value * factor;
+    let rounded = match mode {
+        RoundingMode::HalfUp => scaled.round(),
+        RoundingMode::HalfEven => scaled.round_ties_even(),
+    };
+    rounded / factor
 }
 
 /* ---------------- GEO DISTANCE--------------- */
@@ -32,12 +61,23 @@ pub enum HaversineError {
     // A negative distance should never happen.
     #[error("negative distance`{dist}`")]
     NegativeDistance { dist: f64 },
+
+    // Longer than half the circumference of the Earth: a bug or a wrong radius.
+    #[error("implausible distance `{dist}` km (above {MAX_EARTH_DISTANCE_KM:.1} km, half the Earth's circumference)")]
+    ImplausibleDistance { dist: f64 },
+
+    // The sphere radius must be strictly positive and finite.
+    #[error("invalid radius `{radius}` (expected a positive number of km)")]
+    InvalidRadius { radius: f64 },
+
+    // Elevations must be finite.
+    #[error("invalid elevation `{elev}`")]
+    InvalidElevation { elev: f64 },
 }
 
-// Calculation of the great circle distance (Haversine).
+// Central angle (radians) between two points, using the Haversine formula.
 // Inputs in decimal degrees.
-// Output in kilometers.
-pub fn haversine(lat1_deg: f64, lon1_deg: f64, lat2_deg: f64, lon2_deg: f64) -> Result<f64, HaversineError> {
+fn central_angle(lat1_deg: f64, lon1_deg: f64, lat2_deg: f64, lon2_deg: f64) -> f64 {
 
     // Conversion degrés → radians
     let lat1 = lat1_deg.to_radians();
@@ -45,6 +85,12 @@ pub fn haversine(lat1_deg: f64, lon1_deg: f64, lat2_deg: f64, lon2_deg: f64) ->
     let lat2 = lat2_deg.to_radians();
     let lon2 = lon2_deg.to_radians();
 
+    central_angle_rad(lat1, lon1, lat2, lon2)
+}
+
+// Central angle (radians) between two points given in radians.
+fn central_angle_rad(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+
     // Angular differences.
     let dlat = lat2 - lat1;
     let dlon = lon2 - lon1;
@@ -53,9 +99,58 @@ pub fn haversine(lat1_deg: f64, lon1_deg: f64, lat2_deg: f64, lon2_deg: f64) ->
     let a = (dlat / 2.0).sin().powi(2)
         + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
 
-    let distance = 2.0 * EARTH_RADIUS_KM * a.sqrt().atan2((1.0 - a).sqrt());
+    2.0 * a.sqrt().atan2((1.0 - a).sqrt())
+}
+
+// Calculation of the great circle distance (Haversine) on the Earth.
+// Inputs in decimal degrees.
+// Output in kilometers.
+// Kept as the Earth shortcut for callers that do not need another body.
+pub fn haversine(lat1_deg: f64, lon1_deg: f64, lat2_deg: f64, lon2_deg: f64) -> Result<f64, HaversineError> {
+    haversine_with_radius(lat1_deg, lon1_deg, lat2_deg, lon2_deg, EARTH_RADIUS_KM)
+}
+
+// Calculation of the great circle distance (Haversine) on a sphere
+// of the given radius (e.g. Mars, Moon).
+// Inputs in decimal degrees, radius in kilometers.
+// Output in kilometers.
+pub fn haversine_with_radius(
+    lat1_deg: f64,
+    lon1_deg: f64,
+    lat2_deg: f64,
+    lon2_deg: f64,
+    radius_km: f64,
+) -> Result<f64, HaversineError> {
+
+    validate_radius(radius_km)?;
+
+    checked_distance(radius_km * central_angle(lat1_deg, lon1_deg, lat2_deg, lon2_deg))
+}
+
+// Haversine distance on the Earth from coordinates already in radians, for
+// callers converting each point once (e.g. one point against many).
+// Output in kilometers.
+pub fn haversine_rad(lat1_rad: f64, lon1_rad: f64, lat2_rad: f64, lon2_rad: f64) -> Result<f64, HaversineError> {
+    checked_distance(EARTH_RADIUS_KM * central_angle_rad(lat1_rad, lon1_rad, lat2_rad, lon2_rad))
+}
+
+// Haversine distances on the Earth of many (lat1, lon1, lat2, lon2) pairs in
+// decimal degrees, one result per pair, in a single allocation.
+// Same results as calling `haversine` on each pair; the flat loop is the
+// place for a later SIMD version.
+pub fn haversine_batch(points: &[(f64, f64, f64, f64)]) -> Vec<Result<f64, HaversineError>> {
+    points
+        .iter()
+        .map(|&(lat1, lon1, lat2, lon2)| {
+            haversine_rad(lat1.to_radians(), lon1.to_radians(), lat2.to_radians(), lon2.to_radians())
+        })
+        .collect()
+}
 
-    // Security checks of the calculation.
+// Security checks of a computed distance.
+// Negatives within GEO_PRECISION are floating-point noise and snap to 0.0;
+// beyond that, or non-finite, the value is impossible.
+pub fn checked_distance(distance: f64) -> Result<f64, HaversineError> {
     if !distance.is_finite() {
         return Err(HaversineError::InvalidDistance);
     }
@@ -63,26 +158,366 @@ pub fn haversine(lat1_deg: f64, lon1_deg: f64, lat2_deg: f64, lon2_deg: f64) ->
         return Err(HaversineError::NegativeDistance {dist: distance});
     }
 
-    Ok(distance)
+    Ok(distance.max(0.0))
+}
+
+// Optional guard of an Earth distance (km): no two points of the Earth are
+// more than half its circumference apart, so a longer distance comes from a
+// bug or from a wrong radius (e.g. meters given as kilometers).
+// Not for other bodies: their distances may legitimately be longer.
+pub fn check_earth_distance(distance_km: f64) -> Result<f64, HaversineError> {
+    if distance_km > MAX_EARTH_DISTANCE_KM + GEO_PRECISION {
+        return Err(HaversineError::ImplausibleDistance { dist: distance_km });
+    }
+    Ok(distance_km)
+}
+
+// Checks that a sphere radius is strictly positive and finite.
+pub fn validate_radius(radius_km: f64) -> Result<f64, HaversineError> {
+    if !radius_km.is_finite() || radius_km <= 0.0 {
+        return Err(HaversineError::InvalidRadius { radius: radius_km });
+    }
+    Ok(radius_km)
+}
+
+// Distance combining a surface distance with an elevation difference
+// (Pythagoras), which is accurate as long as the elevation delta is small
+// compared to the Earth radius.
+// Surface distance in kilometers, elevations in meters.
+// Output in kilometers.
+pub fn distance_3d(surface_km: f64, elev1_m: f64, elev2_m: f64) -> Result<f64, HaversineError> {
+    for elev in [elev1_m, elev2_m] {
+        if !elev.is_finite() {
+            return Err(HaversineError::InvalidElevation { elev });
+        }
+    }
+    let delta_km = (elev2_m - elev1_m) / 1000.0;
+
+    Ok(surface_km.hypot(delta_km))
+}
+
+// Haversine distance on the Earth including the elevation difference.
+// Inputs in decimal degrees and meters.
+// Output in kilometers.
+pub fn haversine_3d(
+    lat1_deg: f64,
+    lon1_deg: f64,
+    elev1_m: f64,
+    lat2_deg: f64,
+    lon2_deg: f64,
+    elev2_m: f64,
+) -> Result<f64, HaversineError> {
+    distance_3d(haversine(lat1_deg, lon1_deg, lat2_deg, lon2_deg)?, elev1_m, elev2_m)
+}
+
+/* ---------------- GREAT CIRCLE --------------- */
+
+// Length of the long way around the great circle between two points,
+// given the short (Haversine) distance: circumference - short.
+// Radius and distance in kilometers.
+pub fn long_arc_km(short_km: f64, radius_km: f64) -> f64 {
+    std::f64::consts::TAU * radius_km - short_km
+}
+
+// Below this value of sin(central angle) the great circle through two
+// points is not defined (coincident or antipodal points).
+const ARC_DEGENERATE_SIN: f64 = 1e-12;
+
+// True when the great circle between two points is undefined
+// (coincident or antipodal points).
+pub fn arc_is_degenerate(lat1_deg: f64, lon1_deg: f64, lat2_deg: f64, lon2_deg: f64) -> bool {
+    central_angle(lat1_deg, lon1_deg, lat2_deg, lon2_deg).sin().abs() < ARC_DEGENERATE_SIN
+}
+
+// Point at `fraction` (0 = first point, 1 = second point) along the great
+// circle arc between two points (spherical linear interpolation).
+// When the arc is degenerate, falls back to a linear interpolation of the
+// coordinates (see `arc_is_degenerate`).
+// Inputs and output (lat, lon) in decimal degrees.
+pub fn intermediate_point(
+    lat1_deg: f64,
+    lon1_deg: f64,
+    lat2_deg: f64,
+    lon2_deg: f64,
+    fraction: f64,
+) -> (f64, f64) {
+    if arc_is_degenerate(lat1_deg, lon1_deg, lat2_deg, lon2_deg) {
+        return (
+            lat1_deg + fraction * (lat2_deg - lat1_deg),
+            lon1_deg + fraction * (lon2_deg - lon1_deg),
+        );
+    }
+
+    let delta = central_angle(lat1_deg, lon1_deg, lat2_deg, lon2_deg);
+    let a = ((1.0 - fraction) * delta).sin() / delta.sin();
+    let b = (fraction * delta).sin() / delta.sin();
+
+    let (lat1, lon1) = (lat1_deg.to_radians(), lon1_deg.to_radians());
+    let (lat2, lon2) = (lat2_deg.to_radians(), lon2_deg.to_radians());
+
+    // Weighted sum of the two unit vectors.
+    let x = a * lat1.cos() * lon1.cos() + b * lat2.cos() * lon2.cos();
+    let y = a * lat1.cos() * lon1.sin() + b * lat2.cos() * lon2.sin();
+    let z = a * lat1.sin() + b * lat2.sin();
+
+    (
+        z.atan2(x.hypot(y)).to_degrees(),
+        y.atan2(x).to_degrees(),
+    )
+}
+
+/* ---------------- BEARING --------------- */
+
+// Initial bearing (forward azimuth) of the great circle from the first
+// point to the second one, in [0, 360), clockwise from the North.
+// 0° by convention when the points coincide (the bearing is undefined).
+// Inputs and output in decimal degrees.
+pub fn initial_bearing(lat1_deg: f64, lon1_deg: f64, lat2_deg: f64, lon2_deg: f64) -> f64 {
+    let (lat1, lat2) = (lat1_deg.to_radians(), lat2_deg.to_radians());
+    let dlon = (lon2_deg - lon1_deg).to_radians();
+
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+// Points of the 16-wind compass rose, clockwise from the North.
+const COMPASS_POINTS: [&str; 16] = [
+    "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE",
+    "S", "SSW", "SW", "WSW", "W", "WNW", "NW", "NNW",
+];
+
+// Compass point (16-wind rose) of a bearing in degrees.
+// Each point covers 22.5°, centered on its direction; a value on the
+// boundary belongs to the next point clockwise (11.25° is NNE).
+// [348.75, 360) wraps around to N.
+pub fn compass_point(bearing_deg: f64) -> &'static str {
+    let sector = (bearing_deg.rem_euclid(360.0) / 22.5 + 0.5).floor() as usize;
+    COMPASS_POINTS[sector % 16]
+}
+
+/* ---------------- DESTINATION --------------- */
+
+// Destination point reached from a start point by following the great
+// circle of the given initial bearing over a distance on the Earth.
+// Kept as the Earth shortcut for callers that do not need another body.
+pub fn destination_point(lat_deg: f64, lon_deg: f64, bearing_deg: f64, distance_km: f64) -> (f64, f64) {
+    destination_point_with_radius(lat_deg, lon_deg, bearing_deg, distance_km, EARTH_RADIUS_KM)
+}
+
+// Destination point on a sphere of the given radius (spherical direct
+// formula). Inverse of `haversine_with_radius` + `initial_bearing`.
+// Inputs and output (lat, lon) in decimal degrees, distance and radius in
+// kilometers. The longitude is normalized to [-180, 180].
+pub fn destination_point_with_radius(
+    lat_deg: f64,
+    lon_deg: f64,
+    bearing_deg: f64,
+    distance_km: f64,
+    radius_km: f64,
+) -> (f64, f64) {
+    let (lat1, lon1) = (lat_deg.to_radians(), lon_deg.to_radians());
+    let bearing = bearing_deg.to_radians();
+    let delta = distance_km / radius_km;   // angular distance
+
+    let lat2 = (lat1.sin() * delta.cos() + lat1.cos() * delta.sin() * bearing.cos()).asin();
+    let lon2 = lon1 + (bearing.sin() * delta.sin() * lat1.cos())
+        .atan2(delta.cos() - lat1.sin() * lat2.sin());
+
+    let lon2_deg = (lon2.to_degrees() + 540.0).rem_euclid(360.0) - 180.0;
+    (lat2.to_degrees(), lon2_deg)
+}
+
+/* ---------------- POLES --------------- */
+
+// Geographic pole used as a fixed target point.
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pole {
+    North,
+    South,
+}
+
+// Distance and initial bearing from a point to a pole.
+#[derive(Debug, Copy, Clone)]
+pub struct PoleVector {
+    pub km: f64,
+    pub bearing_deg: f64,
+}
+
+// Distance and bearing to a pole, computed in closed form.
+// The meridian is the great circle to a pole, so the bearing does not
+// depend on the position: always 0° to the North pole, 180° to the South
+// pole (also used, by convention, at the pole itself where it is undefined).
+// Input latitude in decimal degrees, radius in kilometers.
+pub fn to_pole(lat_deg: f64, pole: Pole, radius_km: f64) -> PoleVector {
+    let (angle_deg, bearing_deg) = match pole {
+        Pole::North => (90.0 - lat_deg, 0.0),
+        Pole::South => (90.0 + lat_deg, 180.0),
+    };
+
+    PoleVector {
+        km: radius_km * angle_deg.to_radians(),
+        bearing_deg,
+    }
+}
+
+/* ---------------- LONGITUDE --------------- */
+
+// Minimal angular separation between two longitudes, in [0, 180].
+// Handles the antimeridian: 179 and -179 are 2 degrees apart, not 358.
+// Inputs and output in decimal degrees.
+pub fn longitude_separation(lon_a: f64, lon_b: f64) -> f64 {
+    let d = (lon_a - lon_b).abs() % 360.0;
+    if d > 180.0 { 360.0 - d } else { d }
+}
+
+// True when the shortest path between two longitudes crosses the antimeridian
+// (180°): they are on both sides of it (opposite signs) and more than 180°
+// apart directly, e.g. 179 and -179 (2° apart across 180°, not 358°).
+// Exactly 180° apart, both ways are as short: false.
+// Inputs in decimal degrees, in [-180, 180].
+pub fn crosses_antimeridian(lon_a: f64, lon_b: f64) -> bool {
+    lon_a.signum() != lon_b.signum() && (lon_a - lon_b).abs() > 180.0
+}
+
+// Nautical time zone of a longitude: round(lon / 15) hours from UTC, east
+// positive, clamped to [-12, 12]. The 180° meridian splits zone 12: 180 is +12
+// and -180 is -12. This is the naive 15° band, not a political (IANA) zone.
+// Input in decimal degrees.
+pub fn nautical_tz(lon_deg: f64) -> i32 {
+    ((lon_deg / 15.0).round() as i32).clamp(-12, 12)
+}
+
+/* ---------------- GRID --------------- */
+
+// Cell of a regular grid of `size_deg` degrees containing a point:
+// (row, column) indices, floored, so that cell 0 covers [0, size) and
+// cell -1 covers [-size, 0) (-0.5 is in cell -1, not 0).
+// The quotient is rounded to 9 decimals first, so that a value on a cell
+// boundary (0.3 with 0.1° cells) is not pushed into the previous cell by
+// floating-point noise.
+// Inputs in decimal degrees, `size_deg` strictly positive.
+pub fn grid_cell(lat_deg: f64, lon_deg: f64, size_deg: f64) -> (i64, i64) {
+    let index = |value: f64| round(value / size_deg, 9).floor() as i64;
+    (index(lat_deg), index(lon_deg))
+}
+
+/* ---------------- BOUNDING BOX --------------- */
+
+// Running extent of a set of points.
+// Longitudes are kept (in micro-degrees, deduplicated) so that the
+// antimeridian-aware box can be computed at the end.
+#[derive(Debug, Default)]
+pub struct BoundingBox {
+    lat: Option<(f64, f64)>,        // min, max
+    lons: BTreeSet<i64>,            // micro-degrees
+}
+
+impl BoundingBox {
+    pub fn add(&mut self, lat: f64, lon: f64) {
+        let (min, max) = self.lat.get_or_insert((lat, lat));
+        *min = min.min(lat);
+        *max = max.max(lat);
+        self.lons.insert((lon * 1e6).round() as i64);
+    }
+
+    // Number of distinct longitudes kept.
+    pub fn len(&self) -> usize {
+        self.lons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lat.is_none()
+    }
+
+    // `[min_lat, min_lon, max_lat, max_lon]`, None without points.
+    // Naive (plain min / max) unless `wrap`: the longitude range is then the
+    // smallest one covering every point, possibly across the antimeridian
+    // (min_lon > max_lon, e.g. 170 to -170).
+    pub fn bounds(&self, wrap: bool) -> Option<[f64; 4]> {
+        let (min_lat, max_lat) = self.lat?;
+        let first = *self.lons.first()?;
+        let last = *self.lons.last()?;
+
+        let (mut west, mut east) = (first, last);
+        if wrap {
+            // The box is the complement of the largest gap between two
+            // consecutive longitudes, the gap across the antimeridian included.
+            let mut largest = first + 360_000_000 - last;
+            for (&a, &b) in self.lons.iter().zip(self.lons.iter().skip(1)) {
+                if b - a > largest {
+                    (largest, west, east) = (b - a, b, a);
+                }
+            }
+        }
+
+        Some([min_lat, west as f64 / 1e6, max_lat, east as f64 / 1e6])
+    }
+}
+
+// Whether a point lies in `[min_lat, min_lon, max_lat, max_lon]`, edges
+// included. min_lon > max_lon is a box across the antimeridian, as written
+// by `BoundingBox::bounds`.
+pub fn bbox_contains(bounds: [f64; 4], lat: f64, lon: f64) -> bool {
+    let [min_lat, min_lon, max_lat, max_lon] = bounds;
+    let in_lon = if min_lon <= max_lon {
+        (min_lon..=max_lon).contains(&lon)
+    } else {
+        lon >= min_lon || lon <= max_lon
+    };
+    (min_lat..=max_lat).contains(&lat) && in_lon
 }
 
 /* ---------------- GEO COMPARISON --------------- */
 
-// Tolerance expressed in decimal degrees.
-// Used to compare two coordinates with a margin of error.
+// Tolerance expressed in decimal degrees, per axis.
+// Used to compare two coordinates with a margin of error. A degree of
+// longitude is cos(lat) times shorter than a degree of latitude, so a wider
+// `lon` keeps the same margin in meters away from the equator.
 #[derive(Debug, Copy, Clone)]
 pub struct GeoTolerance {
-    pub deg: f64,
+    pub lat: f64,
+    pub lon: f64,
 }
 
 impl GeoTolerance {
     // Default tolerance (~11 cm at the equator).
-    pub const DEFAULT: Self = Self { deg: 1e-6 };
+    pub const DEFAULT: Self = Self::uniform(1e-6);
+    // Zero tolerance: only identical values compare equal.
+    pub const EXACT: Self = Self::uniform(0.0);
+
+    // Same tolerance on both axes.
+    pub const fn uniform(deg: f64) -> Self {
+        Self { lat: deg, lon: deg }
+    }
 }
 
 // Simple comparison of two angular values.
-fn nearly_equal_deg(a: f64, b: f64, tol: GeoTolerance) -> bool {
-    (a - b).abs() <= tol.deg
+fn nearly_equal_deg(a: f64, b: f64, tol_deg: f64) -> bool {
+    (a - b).abs() <= tol_deg
+}
+
+// True when the two points are (within tolerance) exact antipodes:
+// opposite latitudes and longitudes 180° apart. At the poles the
+// longitude is meaningless and only the latitudes are compared.
+// Haversine is ill-conditioned there (see `arc_is_degenerate`).
+pub fn is_antipodal(lat_a: f64, lon_a: f64, lat_b: f64, lon_b: f64, tol: GeoTolerance) -> bool {
+    let at_pole = nearly_equal_deg(lat_a.abs(), 90.0, tol.lat);
+    nearly_equal_deg(lat_a, -lat_b, tol.lat)
+        && (at_pole || nearly_equal_deg(longitude_separation(lon_a, lon_b), 180.0, tol.lon))
+}
+
+// How the two axes combine into the overall comparison.
+#[derive(Debug, Copy, Clone, Default, PartialEq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NearlyMode {
+    // Both latitude and longitude match (same point).
+    #[default]
+    And,
+    // Either axis matches (fuzzy joins).
+    Or,
 }
 
 // Structured result of geographical comparison.
@@ -90,24 +525,36 @@ fn nearly_equal_deg(a: f64, b: f64, tol: GeoTolerance) -> bool {
 pub struct Nearly {
     pub lat: bool,
     pub lon: bool,
-    pub both: bool,
+    pub combined: bool,     // lat and/or lon, per NearlyMode
+}
+
+impl Nearly {
+    // Same point on both axes, whatever the mode.
+    pub fn same_point(&self) -> bool {
+        self.lat && self.lon
+    }
 }
 
 // Compare two geographical positions with a given tolerance.
-// Each axis is evaluated independently.
+// Each axis is evaluated independently, with its own tolerance, then
+// combined according to `mode`.
 pub fn compute_nearly(
     lat_a: f64,
     lon_a: f64,
     lat_b: f64,
     lon_b: f64,
     tol: GeoTolerance,
+    mode: NearlyMode,
 ) -> Nearly {
-    let lat = nearly_equal_deg(lat_a, lat_b, tol);
-    let lon = nearly_equal_deg(lon_a, lon_b, tol);
+    let lat = nearly_equal_deg(lat_a, lat_b, tol.lat);
+    let lon = nearly_equal_deg(lon_a, lon_b, tol.lon);
 
     Nearly {
         lat,
         lon,
-        both: lat && lon,
+        combined: match mode {
+            NearlyMode::And => lat && lon,
+            NearlyMode::Or => lat || lon,
+        },
     }
 }